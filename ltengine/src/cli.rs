@@ -1,9 +1,14 @@
 use std::io::{Read, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use anyhow::{Context, Result, bail};
+use clap::builder::RangedU64ValueParser;
 use clap::{ArgGroup, Args as ClapArgs, Parser, Subcommand};
 
+use crate::document::{
+    BatchOutcome, Bilingual, DEFAULT_MAX_SLICE_TOKENS, DEFAULT_NAME_TEMPLATE, DocumentRequest,
+    translate_directory, translate_document, translate_document_to_stdout,
+};
 use crate::models::{DEFAULT_MODEL, MODELS};
 use crate::translation::{Inference, TranslationRequest, translate};
 
@@ -34,7 +39,7 @@ pub struct Args {
 
 #[derive(Debug, Subcommand)]
 pub enum Command {
-    /// Translate text or a .txt document
+    /// Translate text or a document
     Translate(TranslateArgs),
 }
 
@@ -45,6 +50,11 @@ pub enum Command {
         .multiple(false)
         .args(["text", "stdin", "input"])
 ))]
+#[command(group(
+    ArgGroup::new("document_output")
+        .multiple(false)
+        .args(["output", "output_dir"])
+))]
 pub struct TranslateArgs {
     /// Source language code, or auto
     #[arg(long)]
@@ -62,17 +72,81 @@ pub struct TranslateArgs {
     #[arg(long)]
     pub stdin: bool,
 
-    /// Read a UTF-8 .txt document
-    #[arg(long, requires = "output")]
+    /// Read a document in a supported format, such as .txt, .md, .html, .docx, .xliff, or .json,
+    /// or a directory whose supported files are all translated
+    #[arg(long, requires = "document_output")]
     pub input: Option<PathBuf>,
 
-    /// Write a translated .txt document without overwriting an existing file
+    /// Write the translated document, in the input format, or - for stdout; Android and Apple
+    /// resources given a directory go into its values-<lang> or <lang>.lproj, and a directory
+    /// input is mirrored into this directory, skipping existing outputs
     #[arg(long, requires = "input")]
     pub output: Option<PathBuf>,
 
+    /// Write the translated document into this directory, named by --name-template
+    #[arg(long, value_name = "DIR", requires = "input")]
+    pub output_dir: Option<PathBuf>,
+
+    /// Output file name for --output-dir, from {stem}, {ext}, {source}, and {target};
+    /// defaults to {stem}.{target}.{ext}
+    #[arg(
+        long,
+        value_name = "TEMPLATE",
+        requires = "output_dir",
+        conflicts_with = "output"
+    )]
+    pub name_template: Option<String>,
+
+    /// Replace existing output files; the input file itself is never overwritten
+    #[arg(long, requires = "input")]
+    pub force: bool,
+
+    /// Keep the source of a .txt or .html document with its translation, each paragraph
+    /// followed by its translation or in two aligned columns
+    #[arg(long, value_parser = ["interleaved", "columns"], requires = "input")]
+    pub bilingual: Option<String>,
+
     /// Maximum document input size in bytes
     #[arg(long, default_value_t = DEFAULT_MAX_DOCUMENT_BYTES)]
     pub max_input_bytes: u64,
+
+    /// Estimated token budget for each document slice sent to the model
+    #[arg(long, default_value_t = DEFAULT_MAX_SLICE_TOKENS, value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+    pub max_slice_tokens: usize,
+
+    /// Translate only JSON values at or below this JSON pointer, such as /ui; repeatable
+    #[arg(long, value_name = "POINTER", requires = "input")]
+    pub include: Vec<String>,
+
+    /// Keep JSON values at or below this JSON pointer untranslated; repeatable
+    #[arg(long, value_name = "POINTER", requires = "input")]
+    pub exclude: Vec<String>,
+
+    /// Translate this CSV or TSV column, by header name or 1-based index; repeatable
+    #[arg(long = "column", value_name = "NAME|INDEX", requires = "input")]
+    pub columns: Vec<String>,
+
+    /// Replace the selected CSV or TSV columns instead of adding a translated column after each
+    #[arg(long, requires = "columns")]
+    pub replace_columns: bool,
+
+    /// Rename a top-level YAML or TOML key naming the source language, such as sv, to the target
+    #[arg(long, requires = "input")]
+    pub rename_language_key: bool,
+
+    /// Encoding of a text-based input document, such as windows-1252, iso-8859-1, or utf-16;
+    /// detected from a byte order mark or UTF-8 validity when omitted
+    #[arg(long, value_name = "LABEL", requires = "input")]
+    pub input_encoding: Option<String>,
+
+    /// Encoding of the translated document, or same to reuse the input encoding; defaults to UTF-8
+    #[arg(long, value_name = "LABEL|same", requires = "input")]
+    pub output_encoding: Option<String>,
+
+    /// Continue an interrupted document translation from the journal next to its output;
+    /// the input document and translation options must be unchanged
+    #[arg(long, requires = "input")]
+    pub resume: bool,
 }
 
 pub fn run_translate(
@@ -81,15 +155,80 @@ pub fn run_translate(
     mut stdin: impl Read,
     mut stdout: impl Write,
 ) -> Result<()> {
-    if let (Some(input), Some(output)) = (&args.input, &args.output) {
-        return crate::document::translate_document(
+    if let Some(input) = &args.input {
+        let (output, output_template) = match (&args.output, &args.output_dir) {
+            (Some(output), _) => (output.as_path(), None),
+            (None, Some(directory)) => (
+                directory.as_path(),
+                Some(
+                    args.name_template
+                        .as_deref()
+                        .unwrap_or(DEFAULT_NAME_TEMPLATE),
+                ),
+            ),
+            (None, None) => bail!("--input requires --output or --output-dir"),
+        };
+        let to_stdout = output_template.is_none() && output == Path::new("-");
+        let request = DocumentRequest {
             input,
             output,
-            args.max_input_bytes,
-            &args.source,
-            &args.target,
-            inference,
+            max_input_bytes: args.max_input_bytes,
+            max_slice_tokens: args.max_slice_tokens,
+            source: &args.source,
+            target: &args.target,
+            include: &args.include,
+            exclude: &args.exclude,
+            columns: &args.columns,
+            replace_columns: args.replace_columns,
+            rename_language_key: args.rename_language_key,
+            input_encoding: args.input_encoding.as_deref(),
+            output_encoding: args.output_encoding.as_deref(),
+            resume: args.resume,
+            output_template,
+            force: args.force,
+            bilingual: args.bilingual.as_deref().map(|layout| match layout {
+                "columns" => Bilingual::Columns,
+                _ => Bilingual::Interleaved,
+            }),
+            progress: &|progress| {
+                eprintln!(
+                    "Translated {}/{}: {}",
+                    progress.completed, progress.total, progress.part
+                );
+            },
+        };
+        if !input.is_dir() {
+            if to_stdout {
+                return translate_document_to_stdout(inference, &request, &mut stdout);
+            }
+            return translate_document(inference, &request);
+        }
+        if to_stdout {
+            bail!(
+                "--output - writes a single document; a directory input needs an output directory"
+            );
+        }
+
+        let summary = translate_directory(inference, &request, &|file| match file.outcome {
+            BatchOutcome::Translated => eprintln!("Translated {}", file.path.display()),
+            BatchOutcome::Skipped => {
+                eprintln!("Skipped {}: output already exists", file.path.display());
+            }
+            BatchOutcome::Unsupported(reason) => {
+                eprintln!("Skipped {}: {reason}", file.path.display());
+            }
+            BatchOutcome::Failed(error) => {
+                eprintln!("Failed {}: {error:#}", file.path.display());
+            }
+        })?;
+        eprintln!(
+            "{} translated, {} skipped, {} failed",
+            summary.translated, summary.skipped, summary.failed
         );
+        if summary.failed > 0 {
+            bail!("{} documents could not be translated", summary.failed);
+        }
+        return Ok(());
     }
 
     let text = if let Some(text) = &args.text {
@@ -366,9 +505,40 @@ mod tests {
             "translated.txt",
         ]);
 
+        let both_outputs = Args::try_parse_from([
+            "ltengine",
+            "translate",
+            "--source",
+            "sv",
+            "--target",
+            "en",
+            "--input",
+            "source.txt",
+            "--output",
+            "translated.txt",
+            "--output-dir",
+            "translated",
+        ]);
+        let template_without_directory = Args::try_parse_from([
+            "ltengine",
+            "translate",
+            "--source",
+            "sv",
+            "--target",
+            "en",
+            "--input",
+            "source.txt",
+            "--output",
+            "translated.txt",
+            "--name-template",
+            "{stem}.{ext}",
+        ]);
+
         assert!(missing_output.is_err());
         assert!(missing_input.is_err());
         assert!(mixed_modes.is_err());
+        assert!(both_outputs.is_err());
+        assert!(template_without_directory.is_err());
     }
 
     #[test]
@@ -1,39 +1,263 @@
-use std::fs::{self, File, OpenOptions};
+use std::fs::{self, File};
 use std::io::{Read, Write};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, anyhow, bail};
+
+use crate::languages::{android_values_directory, apple_lproj_directory};
+use crate::translation::Inference;
+
+mod android;
+mod apple;
+mod batch;
+mod bilingual;
+mod delimited;
+mod docx;
+mod encoding;
+mod epub;
+mod fluent;
+mod gettext;
+mod html;
+mod journal;
+mod json;
+mod markdown;
+mod newlines;
+mod odt;
+mod output;
+mod package;
+mod placeholders;
+mod properties;
+mod qt;
+mod segments;
+mod subtitles;
+#[cfg(test)]
+mod testing;
+#[cfg(test)]
+mod tests;
+mod text;
+mod toml;
+mod wrap;
+mod xliff;
+mod xml;
+mod yaml;
+
+pub use batch::{BatchOutcome, translate_directory};
+pub use bilingual::Bilingual;
+use delimited::{ColumnSelection, Delimiter};
+use encoding::TextEncoding;
+use journal::Journal;
+use json::PointerFilter;
+use newlines::LineEndings;
+pub use output::DEFAULT_NAME_TEMPLATE;
+pub use segments::DEFAULT_MAX_SLICE_TOKENS;
+use segments::SegmentTranslator;
+use subtitles::SubtitleFormat;
+
+pub struct DocumentRequest<'a> {
+    pub input: &'a Path,
+    pub output: &'a Path,
+    pub max_input_bytes: u64,
+    pub max_slice_tokens: usize,
+    pub source: &'a str,
+    pub target: &'a str,
+    /// JSON pointers limiting which values of a JSON document are translated.
+    pub include: &'a [String],
+    /// JSON pointers whose values are copied untranslated.
+    pub exclude: &'a [String],
+    /// CSV or TSV columns to translate, by header name or 1-based index.
+    pub columns: &'a [String],
+    /// Replace the selected CSV or TSV columns instead of adding translated columns.
+    pub replace_columns: bool,
+    /// Rename a top-level YAML or TOML key naming the source language to the target code.
+    pub rename_language_key: bool,
+    /// Encoding label of a text-based input document; detected when `None`.
+    pub input_encoding: Option<&'a str>,
+    /// Encoding label of the output document, or `same` for the input's; UTF-8 when `None`.
+    pub output_encoding: Option<&'a str>,
+    /// Continue from the journal an interrupted run left next to the output.
+    pub resume: bool,
+    /// Name the output file from this template inside the `output` directory, as for
+    /// `--output-dir`.
+    pub output_template: Option<&'a str>,
+    /// Replace an existing output file; the input file itself is still refused.
+    pub force: bool,
+    /// Keep each source paragraph of a text or HTML document next to its translation.
+    pub bilingual: Option<Bilingual>,
+    pub progress: &'a dyn Fn(Progress<'_>),
+}
 
-use anyhow::{Context, Result, bail};
+/// Completed parts of a multi-part document, such as the chapters of a book.
+pub struct Progress<'a> {
+    pub completed: usize,
+    pub total: usize,
+    pub part: &'a str,
+}
 
-use crate::translation::{Inference, TranslationRequest, translate};
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum DocumentFormat {
+    Text,
+    Markdown,
+    Html,
+    Subtitles(SubtitleFormat),
+    Docx,
+    Odt,
+    Epub,
+    Xliff,
+    Gettext,
+    Json,
+    Delimited(Delimiter),
+    Yaml,
+    Toml,
+    AndroidStrings,
+    AppleStrings,
+    AppleStringsDict,
+    Fluent,
+    Properties,
+    QtTs,
+}
 
-pub fn translate_document(
-    input: &Path,
-    output: &Path,
-    max_input_bytes: u64,
-    source: &str,
-    target: &str,
-    inference: &impl Inference,
-) -> Result<()> {
-    require_txt(input, "Input")?;
-    require_txt(output, "Output")?;
+const SUPPORTED_EXTENSIONS: &str = ".txt, .md, .html, .htm, .srt, .vtt, .docx, .odt, .epub, .xlf, .xliff, .po, .pot, .json, .csv, .tsv, .yml, .yaml, .toml, .xml, .strings, .stringsdict, .ftl, .properties, .ts";
+
+impl DocumentFormat {
+    fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "txt" => Some(Self::Text),
+            "md" | "markdown" => Some(Self::Markdown),
+            "html" | "htm" => Some(Self::Html),
+            "srt" => Some(Self::Subtitles(SubtitleFormat::Srt)),
+            "vtt" => Some(Self::Subtitles(SubtitleFormat::WebVtt)),
+            "docx" => Some(Self::Docx),
+            "odt" => Some(Self::Odt),
+            "epub" => Some(Self::Epub),
+            "xlf" | "xliff" => Some(Self::Xliff),
+            "po" | "pot" => Some(Self::Gettext),
+            "json" => Some(Self::Json),
+            "csv" => Some(Self::Delimited(Delimiter::Csv)),
+            "tsv" => Some(Self::Delimited(Delimiter::Tsv)),
+            "yml" | "yaml" => Some(Self::Yaml),
+            "toml" => Some(Self::Toml),
+            "xml" => Some(Self::AndroidStrings),
+            "strings" => Some(Self::AppleStrings),
+            "stringsdict" => Some(Self::AppleStringsDict),
+            "ftl" => Some(Self::Fluent),
+            "properties" => Some(Self::Properties),
+            "ts" => Some(Self::QtTs),
+            _ => None,
+        }
+    }
 
-    let input_path = fs::canonicalize(input)
-        .with_context(|| format!("Failed to resolve input document {}", input.display()))?;
-    if !input_path.is_file() {
-        bail!("Input document is not a regular file: {}", input.display());
+    /// Locale directory that mobile resources for `target` belong in, such as `values-sv`.
+    fn locale_directory(self, target: &str) -> Option<String> {
+        match self {
+            Self::AndroidStrings => Some(android_values_directory(target)),
+            Self::AppleStrings | Self::AppleStringsDict => Some(apple_lproj_directory(target)),
+            _ => None,
+        }
     }
+}
 
-    if output.exists() {
+pub fn translate_document(inference: &impl Inference, request: &DocumentRequest<'_>) -> Result<()> {
+    let input = request.input;
+    let format = require_format(input, "Input")?;
+    let located;
+    let output = match (
+        request.output_template,
+        format.locale_directory(request.target),
+        input.file_name(),
+    ) {
+        (Some(template), _, _) => {
+            let name = output::template_name(template, input, request.source, request.target)?;
+            located = request.output.join(name);
+            located.as_path()
+        }
+        (None, Some(directory), Some(name)) if request.output.is_dir() => {
+            located = request.output.join(directory).join(name);
+            located.as_path()
+        }
+        _ => request.output,
+    };
+    if require_format(output, "Output")? != format {
+        bail!("Output document must use the same format as the input document");
+    }
+    check_format_options(request, format)?;
+
+    let input_path = resolve_input(input)?;
+    if let Ok(existing) = fs::metadata(output) {
         let output_path = fs::canonicalize(output)
             .with_context(|| format!("Failed to resolve output path {}", output.display()))?;
-        if input_path == output_path {
+        if input_path == output_path || output::same_file(&fs::metadata(&input_path)?, &existing) {
             bail!("Input and output paths refer to the same file");
         }
-        bail!("Output path already exists: {}", output.display());
+        if !request.force {
+            bail!(
+                "Output path already exists: {}; pass --force to replace it",
+                output.display()
+            );
+        }
+        if !existing.is_file() {
+            bail!("Output path is not a regular file: {}", output.display());
+        }
+    }
+
+    let bytes = read_input(&input_path, request)?;
+    let journal = Journal::open(output, &bytes, &journal_settings(request), request.resume)?;
+    let output_bytes = translate_bytes(inference, request, format, &bytes, Some(&journal))?;
+
+    if let Some(directory) = output.parent()
+        && (request.output_template.is_some() || format.locale_directory(request.target).is_some())
+    {
+        fs::create_dir_all(directory).with_context(|| {
+            format!("Failed to create output directory {}", directory.display())
+        })?;
+    }
+    output::write(output, &output_bytes, request.force)?;
+    journal.finish()
+}
+
+/// Translates a document to standard output, passed in as `stdout`, for `--output -`.
+///
+/// No journal is kept, so an interrupted run cannot be resumed.
+pub fn translate_document_to_stdout(
+    inference: &impl Inference,
+    request: &DocumentRequest<'_>,
+    stdout: &mut dyn Write,
+) -> Result<()> {
+    let format = require_format(request.input, "Input")?;
+    check_format_options(request, format)?;
+    if request.resume {
+        bail!("--resume needs an output file to keep a journal next to");
+    }
+    let input_path = resolve_input(request.input)?;
+    if output::is_stdout(&input_path) {
+        bail!("Input document and standard output refer to the same file");
+    }
+
+    let bytes = read_input(&input_path, request)?;
+    let output_bytes = translate_bytes(inference, request, format, &bytes, None)?;
+    stdout
+        .write_all(&output_bytes)
+        .and_then(|()| stdout.flush())
+        .context("Failed to write translated document to stdout")
+}
+
+fn resolve_input(input: &Path) -> Result<PathBuf> {
+    let input_path = fs::canonicalize(input)
+        .with_context(|| format!("Failed to resolve input document {}", input.display()))?;
+    if !input_path.is_file() {
+        bail!("Input document is not a regular file: {}", input.display());
     }
+    Ok(input_path)
+}
 
+fn read_input(input_path: &Path, request: &DocumentRequest<'_>) -> Result<Vec<u8>> {
+    let DocumentRequest {
+        input,
+        max_input_bytes,
+        ..
+    } = *request;
     let mut bytes = Vec::new();
-    File::open(&input_path)
+    File::open(input_path)
         .with_context(|| format!("Failed to open input document {}", input.display()))?
         .take(max_input_bytes.saturating_add(1))
         .read_to_end(&mut bytes)
@@ -44,269 +268,171 @@ pub fn translate_document(
             input.display()
         );
     }
+    Ok(bytes)
+}
 
-    let text = String::from_utf8(bytes).with_context(|| {
-        format!(
-            "Input document must contain valid UTF-8: {}",
-            input.display()
-        )
-    })?;
-    if text.trim().is_empty() {
-        bail!("Input document is empty: {}", input.display());
-    }
-
-    let content_start = text.len() - text.trim_start_matches(char::is_whitespace).len();
-    let content_end = text.trim_end_matches(char::is_whitespace).len();
-    let leading_whitespace = &text[..content_start];
-    let content = &text[content_start..content_end];
-    let trailing_whitespace = &text[content_end..];
-    let translated = translate(
+fn translate_bytes(
+    inference: &impl Inference,
+    request: &DocumentRequest<'_>,
+    format: DocumentFormat,
+    bytes: &[u8],
+    journal: Option<&Journal>,
+) -> Result<Vec<u8>> {
+    let max_input_bytes = request.max_input_bytes;
+    let mut translator = SegmentTranslator::new(
         inference,
-        TranslationRequest {
-            text: content,
-            source,
-            target,
-            format: "text",
-        },
-    )?;
-
-    let output_text = leading_whitespace.to_string() + &translated.text + trailing_whitespace;
-
-    let mut output_file = OpenOptions::new()
-        .write(true)
-        .create_new(true)
-        .open(output)
-        .with_context(|| format!("Failed to create output document {}", output.display()))?;
-    if let Err(error) = output_file.write_all(output_text.as_bytes()) {
-        return Err(error).with_context(|| {
-            format!(
-                "Failed to write output document {}; partial output may remain",
-                output.display()
-            )
-        });
+        request.source,
+        request.target,
+        request.max_slice_tokens,
+    );
+    if let Some(journal) = journal {
+        translator = translator.with_journal(journal);
     }
-
-    Ok(())
+    Ok(match format {
+        DocumentFormat::Docx => docx::translate_docx(&translator, bytes, max_input_bytes)?,
+        DocumentFormat::Odt => odt::translate_odt(&translator, bytes, max_input_bytes)?,
+        DocumentFormat::Epub => {
+            epub::translate_epub(&translator, bytes, max_input_bytes, request.progress)?
+        }
+        DocumentFormat::Properties => properties::translate_properties(&translator, bytes)?,
+        format => {
+            let input = request.input;
+            let named = request
+                .input_encoding
+                .map(TextEncoding::from_label)
+                .transpose()?;
+            let (text, input_encoding) = encoding::decode(bytes, named)
+                .with_context(|| format!("Failed to decode {}", input.display()))?;
+            if text.trim().is_empty() {
+                bail!("Input document is empty: {}", input.display());
+            }
+            let output_encoding = match request.output_encoding {
+                None => TextEncoding::UTF_8,
+                Some(label) if label.eq_ignore_ascii_case("same") => input_encoding,
+                Some(label) => TextEncoding::from_label(label)?,
+            }
+            .keeping_bom(input_encoding);
+            // CSV and TSV keep record terminators and quoted line breaks on their own.
+            let translated = if matches!(format, DocumentFormat::Delimited(_)) {
+                translate_text_format(&translator, request, format, &text)?
+            } else {
+                let (text, line_endings) = LineEndings::normalize(&text);
+                line_endings.restore(&translate_text_format(&translator, request, format, &text)?)
+            };
+            encoding::encode(&translated, output_encoding)?
+        }
+    })
 }
 
-fn require_txt(path: &Path, label: &str) -> Result<()> {
-    let is_txt = path
-        .extension()
-        .and_then(|extension| extension.to_str())
-        .is_some_and(|extension| extension.eq_ignore_ascii_case("txt"));
-    if !is_txt {
-        bail!("{label} document is unsupported. Only .txt files are accepted");
-    }
-    Ok(())
+/// Options that change how a document is segmented or what is sent to the model.
+fn journal_settings(request: &DocumentRequest<'_>) -> String {
+    format!(
+        "{:?}",
+        (
+            request.source,
+            request.target,
+            request.max_slice_tokens,
+            request.include,
+            request.exclude,
+            request.columns,
+            request.replace_columns,
+            request.rename_language_key,
+            request.input_encoding,
+        )
+    )
 }
 
-#[cfg(test)]
-mod tests {
-    use std::cell::{Cell, RefCell};
-    use std::fs;
-    use std::path::{Path, PathBuf};
-    use std::sync::atomic::{AtomicU64, Ordering};
-
-    use anyhow::{Result, anyhow};
-
-    use super::translate_document;
-    use crate::translation::Inference;
-
-    struct TestDirectory(PathBuf);
-
-    impl TestDirectory {
-        fn new() -> Self {
-            static NEXT_DIRECTORY: AtomicU64 = AtomicU64::new(0);
-            let path = std::env::temp_dir().join(format!(
-                "ltengine-document-{}-{}",
-                std::process::id(),
-                NEXT_DIRECTORY.fetch_add(1, Ordering::Relaxed)
-            ));
-            fs::create_dir(&path).expect("test directory should be created");
-            Self(path)
+/// Rejects options that do not apply to the document format.
+fn check_format_options(request: &DocumentRequest<'_>, format: DocumentFormat) -> Result<()> {
+    let filter = PointerFilter::new(request.include, request.exclude)?;
+    if !filter.is_empty() && format != DocumentFormat::Json {
+        bail!("--include and --exclude apply only to JSON documents");
+    }
+    match format {
+        DocumentFormat::Delimited(_) if request.columns.is_empty() => {
+            bail!("CSV and TSV documents need at least one --column to translate")
         }
-
-        fn path(&self, name: &str) -> PathBuf {
-            self.0.join(name)
+        DocumentFormat::Delimited(_) => {}
+        _ if !request.columns.is_empty() => {
+            bail!("--column applies only to CSV and TSV documents")
         }
+        _ => {}
     }
-
-    impl Drop for TestDirectory {
-        fn drop(&mut self) {
-            fs::remove_dir_all(&self.0).expect("test directory should be removed");
-        }
+    if request.rename_language_key && !matches!(format, DocumentFormat::Yaml | DocumentFormat::Toml)
+    {
+        bail!("--rename-language-key applies only to YAML and TOML documents");
     }
-
-    struct ControlledInference {
-        calls: Cell<usize>,
-        response: RefCell<Option<Result<String>>>,
+    if request.bilingual.is_some() && !matches!(format, DocumentFormat::Text | DocumentFormat::Html)
+    {
+        bail!("--bilingual applies only to .txt and .html documents");
     }
-
-    impl ControlledInference {
-        fn returning(response: &str) -> Self {
-            Self {
-                calls: Cell::new(0),
-                response: RefCell::new(Some(Ok(response.to_string()))),
-            }
+    if request.input_encoding.is_some() || request.output_encoding.is_some() {
+        if matches!(
+            format,
+            DocumentFormat::Docx
+                | DocumentFormat::Odt
+                | DocumentFormat::Epub
+                | DocumentFormat::Properties
+        ) {
+            bail!(
+                "--input-encoding and --output-encoding do not apply to DOCX, ODT, EPUB, or .properties documents"
+            );
         }
-
-        fn unused() -> Self {
-            Self {
-                calls: Cell::new(0),
-                response: RefCell::new(None),
-            }
+        for label in request.input_encoding.into_iter().chain(
+            request
+                .output_encoding
+                .filter(|label| !label.eq_ignore_ascii_case("same")),
+        ) {
+            TextEncoding::from_label(label)?;
         }
     }
+    Ok(())
+}
 
-    impl Inference for ControlledInference {
-        fn run_prompt(&self, _system: String, _user: String) -> Result<String> {
-            self.calls.set(self.calls.get() + 1);
-            self.response
-                .borrow_mut()
-                .take()
-                .expect("controlled response must be configured")
+fn translate_text_format(
+    translator: &SegmentTranslator<'_, impl Inference>,
+    request: &DocumentRequest<'_>,
+    format: DocumentFormat,
+    text: &str,
+) -> Result<String> {
+    match format {
+        DocumentFormat::Text => text::translate_text(translator, text, request.bilingual),
+        DocumentFormat::Markdown => markdown::translate_markdown(translator, text),
+        DocumentFormat::Html => html::translate_html(translator, text, request.bilingual),
+        DocumentFormat::Subtitles(format) => {
+            subtitles::translate_subtitles(translator, text, format)
+        }
+        DocumentFormat::Xliff => xliff::translate_xliff(translator, text),
+        DocumentFormat::Gettext => gettext::translate_gettext(translator, text),
+        DocumentFormat::Json => {
+            let filter = PointerFilter::new(request.include, request.exclude)?;
+            json::translate_json(translator, text, filter)
+        }
+        DocumentFormat::Delimited(delimiter) => {
+            let columns = ColumnSelection {
+                columns: request.columns,
+                replace: request.replace_columns,
+            };
+            delimited::translate_delimited(translator, text, delimiter, columns)
+        }
+        DocumentFormat::Yaml => yaml::translate_yaml(translator, text, request.rename_language_key),
+        DocumentFormat::Toml => toml::translate_toml(translator, text, request.rename_language_key),
+        DocumentFormat::AndroidStrings => android::translate_android(translator, text),
+        DocumentFormat::AppleStrings => apple::translate_strings(translator, text),
+        DocumentFormat::AppleStringsDict => apple::translate_stringsdict(translator, text),
+        DocumentFormat::Fluent => fluent::translate_fluent(translator, text),
+        DocumentFormat::QtTs => qt::translate_ts(translator, text),
+        DocumentFormat::Docx
+        | DocumentFormat::Odt
+        | DocumentFormat::Epub
+        | DocumentFormat::Properties => {
+            unreachable!("packaged and .properties formats are not decoded as text")
         }
     }
+}
 
-    fn translate(
-        input: &Path,
-        output: &Path,
-        max_input_bytes: u64,
-        inference: &ControlledInference,
-    ) -> Result<()> {
-        translate_document(input, output, max_input_bytes, "sv", "en", inference)
-    }
-
-    #[test]
-    fn translates_swedish_multiline_document_to_selected_path() {
-        let directory = TestDirectory::new();
-        let input = directory.path("source.txt");
-        let output = directory.path("translated.txt");
-        fs::write(&input, "Första stycket.\n\nAndra stycket.\n")
-            .expect("fixture should be written");
-        let inference = ControlledInference::returning("First paragraph.\n\nSecond paragraph.\n");
-
-        translate(&input, &output, 10 * 1024 * 1024, &inference)
-            .expect("document translation should succeed");
-
-        assert_eq!(
-            fs::read_to_string(output).expect("output should be readable"),
-            "First paragraph.\n\nSecond paragraph.\n"
-        );
-        assert_eq!(inference.calls.get(), 1);
-    }
-
-    #[test]
-    fn identity_translation_preserves_document_boundary_whitespace() {
-        let directory = TestDirectory::new();
-        let input = directory.path("source.txt");
-        let output = directory.path("translated.txt");
-        let source = "  Rubrik\n\nText  \n";
-        fs::write(&input, source).expect("fixture should be written");
-        let inference = ControlledInference::unused();
-
-        translate_document(&input, &output, 100, "sv", "sv", &inference)
-            .expect("identity document translation should succeed");
-
-        assert_eq!(fs::read_to_string(output).unwrap(), source);
-        assert_eq!(inference.calls.get(), 0);
-    }
-
-    #[test]
-    fn rejects_document_over_configured_byte_limit() {
-        let directory = TestDirectory::new();
-        let input = directory.path("source.txt");
-        let output = directory.path("translated.txt");
-        fs::write(&input, "123456").expect("fixture should be written");
-        let inference = ControlledInference::unused();
-
-        let error =
-            translate(&input, &output, 5, &inference).expect_err("oversized document must fail");
-
-        assert!(error.to_string().contains("exceeds the 5-byte limit"));
-        assert!(!output.exists());
-        assert_eq!(inference.calls.get(), 0);
-    }
-
-    #[test]
-    fn rejects_invalid_utf8_without_creating_output() {
-        let directory = TestDirectory::new();
-        let input = directory.path("source.txt");
-        let output = directory.path("translated.txt");
-        fs::write(&input, [0xff, 0xfe]).expect("fixture should be written");
-        let inference = ControlledInference::unused();
-
-        let error =
-            translate(&input, &output, 10, &inference).expect_err("invalid UTF-8 must fail");
-
-        assert!(error.to_string().contains("valid UTF-8"));
-        assert!(!output.exists());
-        assert_eq!(inference.calls.get(), 0);
-    }
-
-    #[test]
-    fn refuses_to_overwrite_existing_output() {
-        let directory = TestDirectory::new();
-        let input = directory.path("source.txt");
-        let output = directory.path("translated.txt");
-        fs::write(&input, "Hej.").expect("fixture should be written");
-        fs::write(&output, "keep me").expect("existing output should be written");
-        let inference = ControlledInference::unused();
-
-        let error = translate(&input, &output, 10, &inference)
-            .expect_err("existing output must not be overwritten");
-
-        assert!(error.to_string().contains("already exists"));
-        assert_eq!(fs::read_to_string(output).unwrap(), "keep me");
-        assert_eq!(inference.calls.get(), 0);
-    }
-
-    #[test]
-    fn rejects_same_input_and_output_path() {
-        let directory = TestDirectory::new();
-        let input = directory.path("source.txt");
-        fs::write(&input, "Hej.").expect("fixture should be written");
-        let inference = ControlledInference::unused();
-
-        let error = translate(&input, &input, 10, &inference)
-            .expect_err("input path must not be used as output");
-
-        assert!(error.to_string().contains("same file"));
-        assert_eq!(fs::read_to_string(input).unwrap(), "Hej.");
-        assert_eq!(inference.calls.get(), 0);
-    }
-
-    #[test]
-    fn rejects_non_txt_input() {
-        let directory = TestDirectory::new();
-        let input = directory.path("source.md");
-        let output = directory.path("translated.txt");
-        fs::write(&input, "Hej.").expect("fixture should be written");
-        let inference = ControlledInference::unused();
-
-        let error =
-            translate(&input, &output, 10, &inference).expect_err("unsupported input must fail");
-
-        assert!(error.to_string().contains("Only .txt"));
-        assert!(!output.exists());
-        assert_eq!(inference.calls.get(), 0);
-    }
-
-    #[test]
-    fn leaves_no_output_when_translation_fails() {
-        let directory = TestDirectory::new();
-        let input = directory.path("source.txt");
-        let output = directory.path("translated.txt");
-        fs::write(&input, "Hej.").expect("fixture should be written");
-        let inference = ControlledInference {
-            calls: Cell::new(0),
-            response: RefCell::new(Some(Err(anyhow!("controlled failure")))),
-        };
-
-        let error = translate(&input, &output, 10, &inference)
-            .expect_err("translation failure must be returned");
-
-        assert!(error.to_string().contains("controlled failure"));
-        assert!(!output.exists());
-    }
+fn require_format(path: &Path, label: &str) -> Result<DocumentFormat> {
+    DocumentFormat::from_path(path).ok_or_else(|| {
+        anyhow!("{label} document is unsupported. Accepted extensions: {SUPPORTED_EXTENSIONS}")
+    })
 }
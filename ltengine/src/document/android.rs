@@ -0,0 +1,510 @@
+use std::collections::HashMap;
+use std::ops::Range;
+
+use anyhow::{Result, bail};
+
+use super::placeholders::format_specifiers;
+use super::segments::SegmentTranslator;
+use super::xml::{self, XmlKind, XmlToken};
+use crate::languages::plural_categories;
+use crate::translation::Inference;
+
+/// Resources whose `<item>` children hold the text.
+const ITEM_ELEMENTS: &[&str] = &["string-array", "plurals"];
+/// Inline elements whose content is copied untranslated, such as `<xliff:g>John</xliff:g>`.
+const KEEP_ELEMENTS: &[&str] = &["xliff:g"];
+
+/// A resource value split into text runs and the raw markup between them.
+#[derive(Default)]
+struct Value {
+    runs: Vec<String>,
+    markups: Vec<String>,
+    /// Whether each run lies inside a CDATA section, where XML escaping does not apply.
+    cdata: Vec<bool>,
+}
+
+/// An `<item>` of a `<plurals>` resource.
+struct PluralItem {
+    quantity: String,
+    tag: Range<usize>,
+    content: Range<usize>,
+    translated: Option<String>,
+}
+
+/// Translates the `<string>`, `<string-array>`, and `<plurals>` resources of an Android
+/// `strings.xml`.
+///
+/// `<plurals>` items are regenerated for the target language's CLDR plural categories: a
+/// category the source lacks gets the translation of `other`, and one the target does not use
+/// is dropped. Resources marked `translatable="false"` and references such as `@string/name`
+/// are copied unchanged. Format specifiers such as `%1$s`, escapes such as `\n`, quote
+/// delimiters, and inline markup such as `<b>` or `<xliff:g>` are kept out of the model's hands,
+/// and quotes and apostrophes in translations are escaped the way Android expects.
+pub fn translate_android(
+    translator: &SegmentTranslator<'_, impl Inference>,
+    source: &str,
+) -> Result<String> {
+    let tokens = xml::tokenize(source)?;
+    if !tokens
+        .iter()
+        .any(|token| token.kind == XmlKind::Start && token.name == "resources")
+    {
+        bail!("Android string resources must have a resources root element");
+    }
+
+    let mut translations: HashMap<&str, Option<String>> = HashMap::new();
+    let mut edits = Vec::new();
+    let mut in_items = false;
+    let mut index = 0;
+    while index < tokens.len() {
+        let token = &tokens[index];
+        index += 1;
+        if token.kind == XmlKind::End && ITEM_ELEMENTS.contains(&token.name) {
+            in_items = false;
+        }
+        if token.kind != XmlKind::Start {
+            continue;
+        }
+        let translatable = xml::attribute(&source[token.range.clone()], "translatable").as_deref()
+            != Some("false");
+        match token.name {
+            "string" => {}
+            "item" if in_items => {}
+            "plurals" if translatable => {
+                let end = closing_index(&tokens, index, "plurals")?;
+                let items = &tokens[index..end];
+                edits.extend(translate_plurals(
+                    translator,
+                    source,
+                    items,
+                    &mut translations,
+                )?);
+                index = end + 1;
+                continue;
+            }
+            name if ITEM_ELEMENTS.contains(&name) => {
+                if translatable {
+                    in_items = true;
+                } else {
+                    index = closing_index(&tokens, index, name)? + 1;
+                }
+                continue;
+            }
+            _ => continue,
+        }
+
+        let end = closing_index(&tokens, index, token.name)?;
+        let content = token.range.end..tokens[end].range.start;
+        let raw = &source[content.clone()];
+        if translatable {
+            let translated = match translations.get(raw) {
+                Some(translated) => translated.clone(),
+                None => {
+                    let translated = translate_value(translator, source, &tokens[index..end])?;
+                    translations.insert(raw, translated.clone());
+                    translated
+                }
+            };
+            if let Some(translated) = translated {
+                edits.push((content, translated));
+            }
+        }
+        index = end + 1;
+    }
+    Ok(xml::apply_edits(source, edits))
+}
+
+fn closing_index(tokens: &[XmlToken<'_>], start: usize, name: &str) -> Result<usize> {
+    match tokens[start..]
+        .iter()
+        .position(|token| token.kind == XmlKind::End && token.name == name)
+    {
+        Some(offset) => Ok(start + offset),
+        None => bail!("Malformed Android resources: unclosed {name} element"),
+    }
+}
+
+/// Translates the items of a `<plurals>` resource, given the tokens between its tags, and
+/// returns the edits that write them back for the target language's plural categories.
+fn translate_plurals<'s>(
+    translator: &SegmentTranslator<'_, impl Inference>,
+    source: &'s str,
+    tokens: &[XmlToken<'s>],
+    translations: &mut HashMap<&'s str, Option<String>>,
+) -> Result<Vec<(Range<usize>, String)>> {
+    let mut items = Vec::new();
+    let mut index = 0;
+    while index < tokens.len() {
+        let token = &tokens[index];
+        index += 1;
+        if token.kind != XmlKind::Start || token.name != "item" {
+            continue;
+        }
+        let end = closing_index(tokens, index, "item")?;
+        let content = token.range.end..tokens[end].range.start;
+        let raw = &source[content.clone()];
+        let translated = match translations.get(raw) {
+            Some(translated) => translated.clone(),
+            None => {
+                let translated = translate_value(translator, source, &tokens[index..end])?;
+                translations.insert(raw, translated.clone());
+                translated
+            }
+        };
+        items.push(PluralItem {
+            quantity: xml::attribute(&source[token.range.clone()], "quantity").unwrap_or_default(),
+            tag: token.range.clone(),
+            content,
+            translated,
+        });
+        index = end + 1;
+    }
+
+    let categories = plural_categories(translator.target());
+    let unchanged = items.len() == categories.len()
+        && categories
+            .iter()
+            .all(|(category, _)| items.iter().any(|item| item.quantity == *category));
+    let other = items.iter().find(|item| item.quantity == "other");
+    let (Some(other), false, Some(first), Some(last)) =
+        (other, unchanged, tokens.first(), tokens.last())
+    else {
+        return Ok(items
+            .into_iter()
+            .filter_map(|item| Some((item.content, item.translated?)))
+            .collect());
+    };
+
+    let whitespace = |token: &XmlToken<'_>| {
+        let text = &source[token.range.clone()];
+        if token.kind == XmlKind::Text && text.trim().is_empty() {
+            text
+        } else {
+            ""
+        }
+    };
+    let mut regenerated = String::new();
+    for (category, _) in categories {
+        let item = items
+            .iter()
+            .find(|item| item.quantity == *category)
+            .unwrap_or(other);
+        regenerated.push_str(whitespace(first));
+        if item.quantity == *category {
+            regenerated.push_str(&source[item.tag.clone()]);
+        } else {
+            regenerated.push_str(&format!("<item quantity=\"{category}\">"));
+        }
+        regenerated.push_str(
+            item.translated
+                .as_deref()
+                .unwrap_or(&source[item.content.clone()]),
+        );
+        regenerated.push_str("</item>");
+    }
+    regenerated.push_str(whitespace(last));
+    Ok(vec![(first.range.start..last.range.end, regenerated)])
+}
+
+/// Translates the content of one resource, or returns `None` when there is nothing to translate.
+fn translate_value(
+    translator: &SegmentTranslator<'_, impl Inference>,
+    source: &str,
+    tokens: &[XmlToken<'_>],
+) -> Result<Option<String>> {
+    let first = tokens
+        .first()
+        .map_or("", |token| &source[token.range.clone()]);
+    if first.trim_start().starts_with(['@', '?']) {
+        return Ok(None);
+    }
+
+    let mut value = Value::default();
+    let mut keep_depth = 0;
+    for token in tokens {
+        let text = &source[token.range.clone()];
+        if keep_depth == 0 && token.kind == XmlKind::Text {
+            value.split_text(text, false);
+            continue;
+        }
+        if let Some(inner) = text
+            .strip_prefix("<![CDATA[")
+            .and_then(|text| text.strip_suffix("]]>"))
+            .filter(|_| keep_depth == 0)
+        {
+            value.markup("<![CDATA[");
+            let mut position = 0;
+            while let Some(start) = inner[position..].find('<') {
+                let start = position + start;
+                let end = inner[start..]
+                    .find('>')
+                    .map_or(inner.len(), |end| start + end + 1);
+                value.split_text(&inner[position..start], true);
+                value.markup(&inner[start..end]);
+                position = end;
+            }
+            value.split_text(&inner[position..], true);
+            value.markup("]]>");
+            continue;
+        }
+        if KEEP_ELEMENTS.contains(&token.name) {
+            match token.kind {
+                XmlKind::Start => keep_depth += 1,
+                XmlKind::End => keep_depth -= 1,
+                _ => {}
+            }
+        }
+        value.markup(text);
+    }
+    if value.runs.len() == value.markups.len() {
+        value.runs.push(String::new());
+        value.cdata.push(false);
+    }
+    if !value
+        .runs
+        .iter()
+        .any(|run| run.contains(char::is_alphabetic))
+    {
+        return Ok(None);
+    }
+
+    let translated = translator.translate_marked(&value.runs, &value.markups, "text")?;
+    let mut content = String::new();
+    for (index, run) in translated.iter().enumerate() {
+        if index > 0 {
+            content.push_str(&value.markups[index - 1]);
+        }
+        content.push_str(&encode(run, value.cdata[index], index == 0));
+    }
+    Ok(Some(content))
+}
+
+impl Value {
+    fn text(&mut self, text: &str, cdata: bool) {
+        if text.is_empty() {
+            return;
+        }
+        if self.runs.len() == self.markups.len() {
+            self.runs.push(String::new());
+            self.cdata.push(cdata);
+        }
+        self.runs
+            .last_mut()
+            .expect("a run was pushed")
+            .push_str(text);
+    }
+
+    /// Appends markup, merging it with the previous markup when no text lies between them.
+    fn markup(&mut self, markup: &str) {
+        if self.runs.len() == self.markups.len() {
+            if let Some(last) = self.markups.last_mut() {
+                last.push_str(markup);
+                return;
+            }
+            self.runs.push(String::new());
+            self.cdata.push(false);
+        }
+        self.markups.push(markup.to_string());
+    }
+
+    /// Adds raw resource text, keeping format specifiers, `\n`-style escapes, and quote
+    /// delimiters as markup.
+    fn split_text(&mut self, raw: &str, cdata: bool) {
+        let bytes = raw.as_bytes();
+        let mut protected = format_specifiers(raw);
+        let mut index = 0;
+        while index < bytes.len() {
+            let length = match bytes[index] {
+                b'\\' => match bytes.get(index + 1) {
+                    Some(b'n' | b't') => 2,
+                    Some(b'u')
+                        if raw
+                            .get(index + 2..index + 6)
+                            .is_some_and(|hex| hex.chars().all(|c| c.is_ascii_hexdigit())) =>
+                    {
+                        6
+                    }
+                    _ => {
+                        index += 2;
+                        continue;
+                    }
+                },
+                b'"' => 1,
+                _ => {
+                    index += 1;
+                    continue;
+                }
+            };
+            protected.push(index..index + length);
+            index += length;
+        }
+        protected.sort_by_key(|range| range.start);
+
+        let mut position = 0;
+        for range in protected {
+            if range.start < position {
+                continue;
+            }
+            self.text(&decode(&raw[position..range.start], cdata), cdata);
+            self.markup(&raw[range.clone()]);
+            position = range.end;
+        }
+        self.text(&decode(&raw[position..], cdata), cdata);
+    }
+}
+
+fn decode(raw: &str, cdata: bool) -> String {
+    let text = if cdata {
+        raw.to_string()
+    } else {
+        xml::unescape(raw)
+    };
+    let mut decoded = String::with_capacity(text.len());
+    let mut characters = text.chars();
+    while let Some(character) = characters.next() {
+        match character {
+            '\\' => match characters.next() {
+                Some(escaped @ ('\'' | '"' | '\\' | '@' | '?')) => decoded.push(escaped),
+                Some(other) => {
+                    decoded.push('\\');
+                    decoded.push(other);
+                }
+                None => decoded.push('\\'),
+            },
+            _ => decoded.push(character),
+        }
+    }
+    decoded
+}
+
+fn encode(text: &str, cdata: bool, at_start: bool) -> String {
+    let mut encoded = String::with_capacity(text.len());
+    for (index, character) in text.char_indices() {
+        let leading = at_start && text[..index].trim().is_empty();
+        if matches!(character, '\\' | '\'' | '"') || (leading && matches!(character, '@' | '?')) {
+            encoded.push('\\');
+        }
+        encoded.push(character);
+    }
+    if cdata {
+        encoded
+    } else {
+        xml::escape_text(&encoded)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::translate_android;
+    use crate::document::testing::translate_with;
+
+    const PHRASES: &[(&str, &str)] = &[
+        ("Välkommen", "Welcome"),
+        ("Du har", "You have"),
+        ("ett meddelande", "one message"),
+        ("meddelanden", "messages"),
+        ("Rad ett", "Line one"),
+        ("rad två", "line two"),
+        ("Öppna", "Open"),
+        ("Spara", "Save"),
+        ("Lägg till", "Add"),
+        ("Hej", "Hello"),
+        ("Kan inte", "Can't"),
+        ("fetstil", "bold"),
+        ("stäng", "close"),
+    ];
+
+    fn translate(source: &str) -> (String, Vec<String>) {
+        translate_with(PHRASES, "en", |translator| {
+            translate_android(translator, source)
+        })
+    }
+
+    #[test]
+    fn translates_strings_arrays_and_plurals() {
+        let source = r#"<?xml version="1.0" encoding="utf-8"?>
+<resources xmlns:xliff="urn:oasis:names:tc:xliff:document:1.2">
+    <!-- Greeting -->
+    <string name="app_name" translatable="false">Välkommen</string>
+    <string name="welcome">Välkommen, %1$s!</string>
+    <string name="hello">Hej <xliff:g id="name">Anna</xliff:g></string>
+    <string name="lines">Rad ett\nrad två</string>
+    <string name="alias">@string/welcome</string>
+    <string-array name="actions">
+        <item>Öppna</item>
+        <item>Spara</item>
+    </string-array>
+    <string-array name="codes" translatable="false">
+        <item>Spara</item>
+    </string-array>
+    <plurals name="messages">
+        <item quantity="one">Du har ett meddelande</item>
+        <item quantity="other">Du har %d meddelanden</item>
+    </plurals>
+    <item name="label" type="string">Lägg till</item>
+</resources>
+"#;
+
+        let (output, texts) = translate(source);
+
+        assert_eq!(
+            output,
+            source
+                .replace(">Välkommen, %1$s!<", ">Welcome, %1$s!<")
+                .replace(">Hej <xliff", ">Hello <xliff")
+                .replace("Rad ett\\nrad två", "Line one\\nline two")
+                .replace("<item>Öppna</item>", "<item>Open</item>")
+                .replace(
+                    "<item>Spara</item>\n    </string-array>\n    <string-array",
+                    "<item>Save</item>\n    </string-array>\n    <string-array"
+                )
+                .replace("Du har ett meddelande", "You have one message")
+                .replace("Du har %d meddelanden", "You have %d messages")
+        );
+        assert_eq!(
+            texts,
+            [
+                "Välkommen, %1$s!",
+                "Hej <xliff:g id=\"name\">Anna</xliff:g>",
+                "Rad ett\\nrad två",
+                "Öppna",
+                "Spara",
+                "Du har ett meddelande",
+                "Du har %d meddelanden"
+            ]
+        );
+    }
+
+    #[test]
+    fn regenerates_plural_items_for_the_target_language() {
+        let source = "<resources>\n    <plurals name=\"files\">\n        <item quantity=\"one\">en fil</item>\n        <item quantity=\"other\">%d filer</item>\n    </plurals>\n</resources>\n";
+        let translate = |target| {
+            let phrases = &[("en fil", "один файл"), ("filer", "файлов")];
+            translate_with(phrases, target, |translator| {
+                translate_android(translator, source)
+            })
+            .0
+        };
+
+        assert_eq!(
+            translate("ru"),
+            "<resources>\n    <plurals name=\"files\">\n        <item quantity=\"one\">один файл</item>\n        <item quantity=\"few\">%d файлов</item>\n        <item quantity=\"many\">%d файлов</item>\n        <item quantity=\"other\">%d файлов</item>\n    </plurals>\n</resources>\n"
+        );
+        assert_eq!(
+            translate("ja"),
+            "<resources>\n    <plurals name=\"files\">\n        <item quantity=\"other\">%d файлов</item>\n    </plurals>\n</resources>\n"
+        );
+    }
+
+    #[test]
+    fn escapes_apostrophes_and_keeps_markup() {
+        let source = "<resources>\n<string name=\"a\">Kan inte <b>fetstil</b> \\'x\\'</string>\n<string name=\"b\"><![CDATA[<i>stäng</i> & Spara]]></string>\n</resources>";
+
+        let (output, _) = translate(source);
+
+        assert_eq!(
+            output,
+            "<resources>\n<string name=\"a\">Can\\'t <b>bold</b> \\'x\\'</string>\n<string name=\"b\"><![CDATA[<i>close</i> & Save]]></string>\n</resources>"
+        );
+    }
+}
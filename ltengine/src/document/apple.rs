@@ -0,0 +1,516 @@
+use std::collections::HashMap;
+use std::ops::Range;
+
+use anyhow::{Result, bail};
+
+use super::placeholders::format_specifiers;
+use super::segments::SegmentTranslator;
+use super::xml::{self, XmlKind, XmlToken};
+use crate::languages::plural_categories;
+use crate::translation::Inference;
+
+/// Keys of the CLDR plural categories in an `NSStringPluralRuleType` dictionary.
+const PLURAL_KEYS: &[&str] = &["zero", "one", "two", "few", "many", "other"];
+
+/// A plural category key and its `<string>` value in an `NSStringPluralRuleType` dictionary.
+struct PluralEntry {
+    category: String,
+    /// From the whitespace before `<key>` to the end of `</string>`.
+    entry: Range<usize>,
+    key_start: usize,
+    /// Between `</key>` and `<string>`.
+    gap: Range<usize>,
+    value: Range<usize>,
+}
+
+/// A `<dict>` being scanned for plural category entries.
+#[derive(Default)]
+struct Dictionary {
+    plural_rule: bool,
+    entries: Vec<PluralEntry>,
+    /// The last key and the index of its start tag.
+    key: Option<(String, usize)>,
+}
+
+/// Translates the values of an Apple `.strings` file.
+///
+/// Keys, `/* */` and `//` comments, and the layout between entries are copied unchanged.
+/// Format specifiers such as `%@` or `%1$d` and escapes such as `\n` are protected, and quotes
+/// and backslashes in translations are escaped.
+pub fn translate_strings(
+    translator: &SegmentTranslator<'_, impl Inference>,
+    source: &str,
+) -> Result<String> {
+    let mut translations: HashMap<&str, String> = HashMap::new();
+    let mut edits = Vec::new();
+    for value in string_values(source)? {
+        let raw = &source[value.clone()];
+        let translated = match translations.get(raw) {
+            Some(translated) => translated.clone(),
+            None => {
+                let translated = translate_escaped(translator, raw)?;
+                translations.insert(raw, translated.clone());
+                translated
+            }
+        };
+        if translated != raw {
+            edits.push((value, translated));
+        }
+    }
+    Ok(xml::apply_edits(source, edits))
+}
+
+/// Translates the format strings of an Apple `.stringsdict` property list.
+///
+/// `NSStringLocalizedFormatKey` values and plural variants such as `one` and `other` are
+/// translated with `%#@name@` variables and format specifiers protected. Keys and the
+/// `NSStringFormatSpecTypeKey` and `NSStringFormatValueTypeKey` settings are copied unchanged.
+/// Plural variants follow the target language's CLDR categories: a missing category gets the
+/// translation of `other`, and one the target does not use is removed.
+pub fn translate_stringsdict(
+    translator: &SegmentTranslator<'_, impl Inference>,
+    source: &str,
+) -> Result<String> {
+    let tokens = xml::tokenize(source)?;
+    if !tokens
+        .iter()
+        .any(|token| token.kind == XmlKind::Start && token.name == "plist")
+    {
+        bail!("Apple .stringsdict documents must have a plist root element");
+    }
+
+    let categories = plural_categories(translator.target());
+    let mut edits = Vec::new();
+    let mut dropped = Vec::new();
+    let mut missing = Vec::new();
+    let rules = plural_rules(source, &tokens);
+    for entries in &rules {
+        let Some(other) = entries.iter().find(|entry| entry.category == "other") else {
+            continue;
+        };
+        for entry in entries {
+            if !categories
+                .iter()
+                .any(|(category, _)| *category == entry.category)
+            {
+                edits.push((entry.entry.clone(), String::new()));
+                dropped.push(entry.value.start);
+            }
+        }
+        let position = |category: &str| {
+            categories
+                .iter()
+                .position(|(target, _)| *target == category)
+        };
+        for (index, (category, _)) in categories.iter().enumerate() {
+            if entries.iter().any(|entry| entry.category == *category) {
+                continue;
+            }
+            // The kept entry that follows this category in CLDR order; `other` comes last.
+            let before = entries
+                .iter()
+                .filter_map(|entry| Some((position(&entry.category)?, entry)))
+                .filter(|(later, _)| *later > index)
+                .min_by_key(|(later, _)| *later)
+                .map_or(other, |(_, entry)| entry);
+            missing.push((*category, other, before));
+        }
+    }
+
+    let mut translations: HashMap<String, String> = HashMap::new();
+    let mut key = String::new();
+    for window in tokens.windows(3) {
+        let [start, text, end] = window else {
+            continue;
+        };
+        if start.kind != XmlKind::Start
+            || text.kind != XmlKind::Text
+            || end.kind != XmlKind::End
+            || end.name != start.name
+        {
+            continue;
+        }
+        let value = xml::unescape(&source[text.range.clone()]);
+        match start.name {
+            "key" => key = value,
+            "string" if !key.ends_with("TypeKey") && !dropped.contains(&text.range.start) => {
+                if !value.contains(char::is_alphabetic) {
+                    continue;
+                }
+                let translated = match translations.get(&value) {
+                    Some(translated) => translated.clone(),
+                    None => {
+                        let translated = translator.translate_protected(
+                            &value,
+                            &format_specifiers(&value),
+                            "text",
+                        )?;
+                        translations.insert(value, translated.clone());
+                        translated
+                    }
+                };
+                edits.push((text.range.clone(), xml::escape_text(&translated)));
+            }
+            _ => {}
+        }
+    }
+
+    // New categories go before the next kept category, keeping the CLDR order.
+    for (category, other, before) in missing {
+        let value = xml::unescape(&source[other.value.clone()]);
+        let translated = translations.get(&value).unwrap_or(&value);
+        edits.push((
+            before.key_start..before.key_start,
+            format!(
+                "<key>{category}</key>{}<string>{}</string>{}",
+                &source[other.gap.clone()],
+                xml::escape_text(translated),
+                &source[before.entry.start..before.key_start]
+            ),
+        ));
+    }
+    Ok(xml::apply_edits(source, edits))
+}
+
+/// Finds the plural category entries of every `NSStringPluralRuleType` dictionary.
+fn plural_rules(source: &str, tokens: &[XmlToken<'_>]) -> Vec<Vec<PluralEntry>> {
+    let mut open: Vec<Dictionary> = Vec::new();
+    let mut rules = Vec::new();
+    for (index, token) in tokens.iter().enumerate() {
+        match (token.kind, token.name) {
+            (XmlKind::Start, "dict") => open.push(Dictionary::default()),
+            (XmlKind::End, "dict") => {
+                if let Some(dictionary) = open.pop().filter(|dictionary| dictionary.plural_rule) {
+                    rules.push(dictionary.entries);
+                }
+            }
+            (XmlKind::Start, "key" | "string") => {
+                let (Some(text), Some(end), Some(dictionary)) = (
+                    tokens.get(index + 1),
+                    tokens.get(index + 2),
+                    open.last_mut(),
+                ) else {
+                    continue;
+                };
+                if text.kind != XmlKind::Text || end.kind != XmlKind::End || end.name != token.name
+                {
+                    continue;
+                }
+                let value = xml::unescape(&source[text.range.clone()]);
+                if token.name == "key" {
+                    dictionary.key = Some((value, index));
+                    continue;
+                }
+                let Some((name, key_index)) = dictionary.key.take() else {
+                    continue;
+                };
+                if name == "NSStringFormatSpecTypeKey" {
+                    dictionary.plural_rule |= value == "NSStringPluralRuleType";
+                } else if PLURAL_KEYS.contains(&name.as_str()) {
+                    let key_start = tokens[key_index].range.start;
+                    let entry_start = key_index
+                        .checked_sub(1)
+                        .map(|before| &tokens[before])
+                        .filter(|before| {
+                            before.kind == XmlKind::Text
+                                && source[before.range.clone()].trim().is_empty()
+                        })
+                        .map_or(key_start, |before| before.range.start);
+                    dictionary.entries.push(PluralEntry {
+                        category: name,
+                        entry: entry_start..end.range.end,
+                        key_start,
+                        gap: tokens[key_index + 2].range.end..token.range.start,
+                        value: text.range.clone(),
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+    rules
+}
+
+/// Returns the ranges between the quotes of every value in a `.strings` file.
+fn string_values(source: &str) -> Result<Vec<Range<usize>>> {
+    let line = |position: usize| source[..position].matches('\n').count() + 1;
+    let mut values = Vec::new();
+    let mut position = 0;
+    // Expected next token: a key, `=`, a value, or `;`.
+    let mut state = 0;
+    while position < source.len() {
+        let rest = &source[position..];
+        let character = rest.chars().next().expect("position is inside the source");
+        if character.is_whitespace() {
+            position += character.len_utf8();
+            continue;
+        }
+        if let Some(comment) = rest.strip_prefix("/*") {
+            let Some(end) = comment.find("*/") else {
+                bail!(
+                    "Malformed .strings file: unterminated comment on line {}",
+                    line(position)
+                );
+            };
+            position += end + 4;
+            continue;
+        }
+        if rest.starts_with("//") {
+            position += rest.find('\n').unwrap_or(rest.len());
+            continue;
+        }
+
+        let length = match (state, character) {
+            (0 | 2, '"') => {
+                let Some(length) = quoted_length(rest) else {
+                    bail!(
+                        "Malformed .strings file: unterminated string on line {}",
+                        line(position)
+                    );
+                };
+                if state == 2 {
+                    values.push(position + 1..position + length - 1);
+                }
+                length
+            }
+            (0, _) if character.is_alphanumeric() || "_.:$/-".contains(character) => rest
+                .find(|character: char| {
+                    !(character.is_alphanumeric() || "_.:$/-".contains(character))
+                })
+                .unwrap_or(rest.len()),
+            (1, '=') | (3, ';') => 1,
+            (1, ';') => {
+                state = 0;
+                position += 1;
+                continue;
+            }
+            _ => bail!(
+                "Malformed .strings file: unexpected {character:?} on line {}",
+                line(position)
+            ),
+        };
+        position += length;
+        state = (state + 1) % 4;
+    }
+    if state != 0 {
+        bail!("Malformed .strings file: the last entry is incomplete");
+    }
+    Ok(values)
+}
+
+/// Returns the length of the quoted string at the start of `text`, quotes included.
+fn quoted_length(text: &str) -> Option<usize> {
+    let mut escaped = false;
+    for (index, character) in text.char_indices().skip(1) {
+        match character {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '"' => return Some(index + 1),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Translates a backslash-escaped `.strings` value, keeping `\n`-style escapes verbatim.
+fn translate_escaped(
+    translator: &SegmentTranslator<'_, impl Inference>,
+    raw: &str,
+) -> Result<String> {
+    let bytes = raw.as_bytes();
+    let mut protected = format_specifiers(raw);
+    let mut index = 0;
+    while index < bytes.len() {
+        if bytes[index] != b'\\' {
+            index += 1;
+            continue;
+        }
+        let length = match bytes.get(index + 1) {
+            Some(b'n' | b't' | b'r') => 2,
+            Some(b'U' | b'u')
+                if raw
+                    .get(index + 2..index + 6)
+                    .is_some_and(|hex| hex.chars().all(|c| c.is_ascii_hexdigit())) =>
+            {
+                6
+            }
+            _ => {
+                index += 2;
+                continue;
+            }
+        };
+        protected.push(index..index + length);
+        index += length;
+    }
+    protected.sort_by_key(|range| range.start);
+
+    let mut runs = Vec::new();
+    let mut markups = Vec::new();
+    let mut position = 0;
+    for range in protected {
+        if range.start < position {
+            continue;
+        }
+        runs.push(unescape(&raw[position..range.start]));
+        markups.push(&raw[range.clone()]);
+        position = range.end;
+    }
+    runs.push(unescape(&raw[position..]));
+    if !runs.iter().any(|run| run.contains(char::is_alphabetic)) {
+        return Ok(raw.to_string());
+    }
+
+    let translated = translator.translate_marked(&runs, &markups, "text")?;
+    let mut value = String::with_capacity(raw.len());
+    for (index, run) in translated.iter().enumerate() {
+        if index > 0 {
+            value.push_str(markups[index - 1]);
+        }
+        for character in run.chars() {
+            match character {
+                '\\' | '"' => {
+                    value.push('\\');
+                    value.push(character);
+                }
+                '\n' => value.push_str("\\n"),
+                _ => value.push(character),
+            }
+        }
+    }
+    Ok(value)
+}
+
+fn unescape(raw: &str) -> String {
+    let mut text = String::with_capacity(raw.len());
+    let mut characters = raw.chars();
+    while let Some(character) = characters.next() {
+        if character == '\\' {
+            text.extend(characters.next());
+        } else {
+            text.push(character);
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{translate_strings, translate_stringsdict};
+    use crate::document::segments::SegmentTranslator;
+    use crate::document::testing::{FakeInference, translate_with};
+
+    const PHRASES: &[(&str, &str)] = &[
+        ("Spara", "Save"),
+        ("Hej", "Hello"),
+        ("Klicka på", "Click"),
+        ("Rad ett", "Line one"),
+        ("rad två", "line two"),
+        ("Du har", "You have"),
+        ("en fil", "one file"),
+        ("filer", "files"),
+    ];
+
+    #[test]
+    fn translates_strings_values_and_keeps_keys_and_comments() {
+        let source = "/* Knapp: Spara */\n\"save\" = \"Spara\";\n// Hälsning\n\"greeting\" = \"Hej %@!\";\nquote = \"Klicka på \\\"Spara\\\"\";\n\"lines\"=\"Rad ett\\nrad två\";\n\"again\" = \"Spara\";\n";
+
+        let (output, texts) = translate_with(PHRASES, "en", |translator| {
+            translate_strings(translator, source)
+        });
+
+        assert_eq!(
+            output,
+            "/* Knapp: Spara */\n\"save\" = \"Save\";\n// Hälsning\n\"greeting\" = \"Hello %@!\";\nquote = \"Click \\\"Save\\\"\";\n\"lines\"=\"Line one\\nline two\";\n\"again\" = \"Save\";\n"
+        );
+        assert_eq!(
+            texts,
+            [
+                "Spara",
+                "Hej %@!",
+                "Klicka på \"Spara\"",
+                "Rad ett\\nrad två"
+            ]
+        );
+    }
+
+    #[test]
+    fn translates_stringsdict_variants() {
+        let source = r#"<?xml version="1.0" encoding="UTF-8"?>
+<plist version="1.0">
+<dict>
+    <key>files</key>
+    <dict>
+        <key>NSStringLocalizedFormatKey</key>
+        <string>Du har %#@files@</string>
+        <key>files</key>
+        <dict>
+            <key>NSStringFormatSpecTypeKey</key>
+            <string>NSStringPluralRuleType</string>
+            <key>NSStringFormatValueTypeKey</key>
+            <string>d</string>
+            <key>one</key>
+            <string>en fil</string>
+            <key>other</key>
+            <string>%d filer</string>
+        </dict>
+    </dict>
+</dict>
+</plist>
+"#;
+
+        let (output, texts) = translate_with(PHRASES, "en", |translator| {
+            translate_stringsdict(translator, source)
+        });
+
+        assert_eq!(
+            output,
+            source
+                .replace("Du har %#@files@", "You have %#@files@")
+                .replace("<string>en fil<", "<string>one file<")
+                .replace("%d filer", "%d files")
+        );
+        assert_eq!(texts, ["Du har %#@files@", "en fil", "%d filer"]);
+    }
+
+    #[test]
+    fn regenerates_stringsdict_plural_categories_for_the_target_language() {
+        let source = "<plist><dict><key>files</key><dict>\n  <key>NSStringFormatSpecTypeKey</key>\n  <string>NSStringPluralRuleType</string>\n  <key>one</key>\n  <string>en fil</string>\n  <key>other</key>\n  <string>%d filer</string>\n</dict></dict></plist>";
+        let translate = |target| {
+            let phrases = &[("en fil", "один файл"), ("filer", "файлов")];
+            translate_with(phrases, target, |translator| {
+                translate_stringsdict(translator, source)
+            })
+            .0
+        };
+
+        assert_eq!(
+            translate("ru"),
+            "<plist><dict><key>files</key><dict>\n  <key>NSStringFormatSpecTypeKey</key>\n  <string>NSStringPluralRuleType</string>\n  <key>one</key>\n  <string>один файл</string>\n  <key>few</key>\n  <string>%d файлов</string>\n  <key>many</key>\n  <string>%d файлов</string>\n  <key>other</key>\n  <string>%d файлов</string>\n</dict></dict></plist>"
+        );
+        assert_eq!(
+            translate("ja"),
+            "<plist><dict><key>files</key><dict>\n  <key>NSStringFormatSpecTypeKey</key>\n  <string>NSStringPluralRuleType</string>\n  <key>other</key>\n  <string>%d файлов</string>\n</dict></dict></plist>"
+        );
+        let keys: Vec<String> = translate("ar")
+            .split("<key>")
+            .skip(3)
+            .map(|key| key[..key.find('<').unwrap()].to_string())
+            .collect();
+        assert_eq!(keys, ["zero", "one", "two", "few", "many", "other"]);
+    }
+
+    #[test]
+    fn rejects_malformed_strings_files() {
+        let inference = FakeInference::replacing(PHRASES);
+        let translator = SegmentTranslator::new(&inference, "sv", "en", 1024);
+
+        let error = translate_strings(&translator, "\"a\" = \"Spara\";\n\"b\" = \"Hej\"\n")
+            .expect_err("a missing semicolon must fail");
+
+        assert_eq!(
+            error.to_string(),
+            "Malformed .strings file: the last entry is incomplete"
+        );
+        assert_eq!(inference.calls(), 0);
+    }
+}
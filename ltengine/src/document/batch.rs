@@ -0,0 +1,202 @@
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, bail};
+
+use super::output::template_name;
+use super::{DocumentFormat, DocumentRequest, translate_document, xml};
+use crate::translation::Inference;
+
+/// What happened to one file of a directory batch.
+pub enum BatchOutcome {
+    Translated,
+    /// The output already existed and was left alone.
+    Skipped,
+    /// The file has a supported extension but other content, such as an `.xml` file that is
+    /// not Android string resources; it is counted as skipped.
+    Unsupported(&'static str),
+    Failed(anyhow::Error),
+}
+
+/// Bytes read from the start of an `.xml` or `.ts` file to find its root element.
+const SNIFF_BYTES: u64 = 64 * 1024;
+
+pub struct BatchFile<'a> {
+    /// Path relative to the input directory, which is also its directory in the output.
+    pub path: &'a Path,
+    pub outcome: BatchOutcome,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct BatchSummary {
+    pub translated: usize,
+    pub skipped: usize,
+    pub failed: usize,
+}
+
+/// Translates every supported file below `request.input` into the same relative path below
+/// `request.output`.
+///
+/// Files are visited in path order. Symbolic links are not followed, and an output directory
+/// inside the input directory is not read. With `request.output_template`, each output is named
+/// from the template in its mirrored directory. `.xml` and `.ts` files whose root element is not
+/// `<resources>` or `<TS>` are reported as unsupported, since layouts and TypeScript sources
+/// share those extensions. A file whose output exists is skipped unless `request.force` is set,
+/// a file that fails is reported and the batch goes on, and options that do not apply to a
+/// file's format, such as `--column` for a Markdown file, are ignored for that file.
+pub fn translate_directory(
+    inference: &impl Inference,
+    request: &DocumentRequest<'_>,
+    report: &dyn Fn(BatchFile<'_>),
+) -> Result<BatchSummary> {
+    let input = fs::canonicalize(request.input).with_context(|| {
+        format!(
+            "Failed to resolve input directory {}",
+            request.input.display()
+        )
+    })?;
+    if request.output.exists() && !request.output.is_dir() {
+        bail!(
+            "Output must be a directory when the input is a directory: {}",
+            request.output.display()
+        );
+    }
+    fs::create_dir_all(request.output).with_context(|| {
+        format!(
+            "Failed to create output directory {}",
+            request.output.display()
+        )
+    })?;
+    let output = fs::canonicalize(request.output)?;
+
+    let mut files = Vec::new();
+    collect_files(&input, &output, &mut files)?;
+
+    let mut summary = BatchSummary::default();
+    for file in &files {
+        let path = file
+            .strip_prefix(&input)
+            .expect("files are below the input directory");
+        let target = match request.output_template {
+            Some(template) => request.output.join(path).with_file_name(template_name(
+                template,
+                file,
+                request.source,
+                request.target,
+            )?),
+            None => request.output.join(path),
+        };
+        let format = DocumentFormat::from_path(file).expect("only supported files are collected");
+        let unsupported = match unsupported_content(file, format) {
+            Ok(unsupported) => unsupported,
+            Err(error) => {
+                summary.failed += 1;
+                report(BatchFile {
+                    path,
+                    outcome: BatchOutcome::Failed(error),
+                });
+                continue;
+            }
+        };
+        let outcome = if let Some(reason) = unsupported {
+            summary.skipped += 1;
+            BatchOutcome::Unsupported(reason)
+        } else if target.exists() && !request.force {
+            summary.skipped += 1;
+            BatchOutcome::Skipped
+        } else {
+            let created = match target.parent() {
+                Some(parent) => fs::create_dir_all(parent).with_context(|| {
+                    format!("Failed to create output directory {}", parent.display())
+                }),
+                None => Ok(()),
+            };
+            match created.and_then(|()| {
+                translate_document(inference, &for_format(request, file, &target, format))
+            }) {
+                Ok(()) => {
+                    summary.translated += 1;
+                    BatchOutcome::Translated
+                }
+                Err(error) => {
+                    summary.failed += 1;
+                    BatchOutcome::Failed(error)
+                }
+            }
+        };
+        report(BatchFile { path, outcome });
+    }
+    Ok(summary)
+}
+
+/// Returns why a file with a shared extension is not in the format it names, if it is not.
+fn unsupported_content(file: &Path, format: DocumentFormat) -> Result<Option<&'static str>> {
+    let (root, reason) = match format {
+        DocumentFormat::AndroidStrings => (
+            "resources",
+            "not Android string resources (no <resources> root element)",
+        ),
+        DocumentFormat::QtTs => ("TS", "not a Qt Linguist file (no <TS> root element)"),
+        _ => return Ok(None),
+    };
+    let mut start = Vec::new();
+    File::open(file)
+        .and_then(|opened| opened.take(SNIFF_BYTES).read_to_end(&mut start))
+        .with_context(|| format!("Failed to read input document {}", file.display()))?;
+    let found = xml::root_element(&String::from_utf8_lossy(&start)) == Some(root);
+    Ok((!found).then_some(reason))
+}
+
+/// Collects supported regular files below `directory` in path order, leaving out `skip`.
+fn collect_files(directory: &Path, skip: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    let mut entries = fs::read_dir(directory)
+        .with_context(|| format!("Failed to read directory {}", directory.display()))?
+        .collect::<std::io::Result<Vec<_>>>()
+        .with_context(|| format!("Failed to read directory {}", directory.display()))?;
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        let path = entry.path();
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() && path != skip {
+            collect_files(&path, skip, files)?;
+        } else if file_type.is_file() && DocumentFormat::from_path(&path).is_some() {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// The request for one file, without the options its format does not take.
+fn for_format<'a>(
+    request: &DocumentRequest<'a>,
+    input: &'a Path,
+    output: &'a Path,
+    format: DocumentFormat,
+) -> DocumentRequest<'a> {
+    let binary = matches!(
+        format,
+        DocumentFormat::Docx
+            | DocumentFormat::Odt
+            | DocumentFormat::Epub
+            | DocumentFormat::Properties
+    );
+    let language_keys = matches!(format, DocumentFormat::Yaml | DocumentFormat::Toml);
+    let json = format == DocumentFormat::Json;
+    let delimited = matches!(format, DocumentFormat::Delimited(_));
+    let bilingual = matches!(format, DocumentFormat::Text | DocumentFormat::Html);
+    DocumentRequest {
+        input,
+        output,
+        include: if json { request.include } else { &[] },
+        exclude: if json { request.exclude } else { &[] },
+        columns: if delimited { request.columns } else { &[] },
+        replace_columns: delimited && request.replace_columns,
+        rename_language_key: language_keys && request.rename_language_key,
+        input_encoding: request.input_encoding.filter(|_| !binary),
+        output_encoding: request.output_encoding.filter(|_| !binary),
+        output_template: None,
+        bilingual: request.bilingual.filter(|_| bilingual),
+        ..*request
+    }
+}
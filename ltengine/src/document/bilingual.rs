@@ -0,0 +1,159 @@
+use super::wrap::{Wrapped, display_width, wrap_line};
+use crate::languages::language_tag;
+
+/// Display width of each column of a side-by-side text document; two columns and the rule
+/// between them fit in 80 columns.
+const COLUMN_WIDTH: usize = 38;
+const COLUMN_RULE: &str = " │ ";
+
+/// How a bilingual document shows each source segment next to its translation.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Bilingual {
+    /// The source paragraph, then its translation.
+    Interleaved,
+    /// Source and translation side by side, each segment starting on the same row.
+    Columns,
+}
+
+impl Bilingual {
+    /// Writes a plain-text paragraph as written and its translation.
+    ///
+    /// A hard-wrapped paragraph's translation is re-wrapped like the source, or with both in
+    /// columns, both are re-wrapped at the column width with the paragraph's prefixes.
+    pub fn push_text(
+        self,
+        source: &str,
+        translated: &str,
+        wrapped: Option<&Wrapped>,
+        output: &mut String,
+    ) {
+        match self {
+            Self::Interleaved => {
+                output.push_str(source);
+                output.push_str("\n\n");
+                match wrapped {
+                    Some(wrapped) => output.push_str(&wrapped.rewrap(translated)),
+                    None => output.push_str(translated),
+                }
+            }
+            Self::Columns => {
+                let (left, right) = match wrapped {
+                    Some(wrapped) => (
+                        column_lines(&wrapped.one_line(&wrapped.text)),
+                        column_lines(&wrapped.one_line(translated)),
+                    ),
+                    None => (column_lines(source), column_lines(translated)),
+                };
+                for row in 0..left.len().max(right.len()) {
+                    if row > 0 {
+                        output.push('\n');
+                    }
+                    let left = left.get(row).map_or("", String::as_str);
+                    output.push_str(left);
+                    let padding = COLUMN_WIDTH.saturating_sub(display_width(left));
+                    output.push_str(&" ".repeat(padding));
+                    output.push_str(COLUMN_RULE);
+                    output.push_str(right.get(row).map_or("", String::as_str));
+                    let trimmed = output.trim_end_matches(' ').len();
+                    output.truncate(trimmed);
+                }
+            }
+        }
+    }
+
+    /// Writes an HTML segment and its translation as a pair that the page style lays out.
+    pub fn push_html(
+        self,
+        source: &str,
+        translated: &str,
+        languages: (&str, &str),
+        output: &mut String,
+    ) {
+        output.push_str(&format!(
+            "<span class=\"ltengine-pair\"><span class=\"ltengine-source\"{}>{source}</span><span class=\"ltengine-translation\"{}>{translated}</span></span>",
+            lang_attribute(languages.0),
+            lang_attribute(languages.1)
+        ));
+    }
+
+    /// The `<style>` element that lays out the segment pairs.
+    pub fn html_style(self) -> String {
+        let layout = match self {
+            Self::Interleaved => ".ltengine-pair>span{display:block}",
+            Self::Columns => {
+                ".ltengine-pair{display:grid;grid-template-columns:1fr 1fr;column-gap:2em}"
+            }
+        };
+        format!("<style>{layout}.ltengine-source{{color:#555}}</style>")
+    }
+}
+
+/// A `lang` attribute for a language code; an `auto` source gets none.
+fn lang_attribute(code: &str) -> String {
+    if code == "auto" {
+        String::new()
+    } else {
+        format!(" lang=\"{}\"", language_tag(code))
+    }
+}
+
+/// Wraps each line of a paragraph at [`COLUMN_WIDTH`], keeping indentation, quote markers, and
+/// list markers.
+fn column_lines(text: &str) -> Vec<String> {
+    text.lines()
+        .flat_map(|line| {
+            wrap_line(line, COLUMN_WIDTH)
+                .lines()
+                .map(str::to_string)
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Bilingual;
+    use crate::document::wrap::display_width;
+
+    #[test]
+    fn lays_out_text_segments_side_by_side() {
+        let mut output = String::new();
+
+        Bilingual::Columns.push_text(
+            "Avtalet gäller från och med den dag det undertecknas av båda parter.",
+            "The agreement applies from the day both parties sign it.",
+            None,
+            &mut output,
+        );
+
+        assert_eq!(
+            output,
+            "Avtalet gäller från och med den dag    │ The agreement applies from the day\n\
+             det undertecknas av båda parter.       │ both parties sign it."
+        );
+    }
+
+    #[test]
+    fn aligns_wide_characters_and_keeps_list_and_quote_prefixes() {
+        let mut output = String::new();
+
+        Bilingual::Columns.push_text(
+            "- Köp mjölk, bröd och ost på vägen hem från jobbet\n> Glöm inte kvittot",
+            "- 下班回家的路上买牛奶、面包和奶酪，然后做晚饭\n> 别忘了收据",
+            None,
+            &mut output,
+        );
+
+        assert_eq!(
+            output,
+            "- Köp mjölk, bröd och ost på vägen hem │ - 下班回家的路上买牛奶、面包和奶酪，然\n\
+             \x20 från jobbet                          │   后做晚饭\n\
+             > Glöm inte kvittot                    │ > 别忘了收据"
+        );
+        let rules: Vec<usize> = output
+            .lines()
+            .map(|line| display_width(&line[..line.find('│').unwrap()]))
+            .collect();
+        assert_eq!(rules, [39, 39, 39]);
+    }
+}
@@ -0,0 +1,303 @@
+use std::collections::HashMap;
+use std::ops::Range;
+
+use anyhow::{Result, bail};
+
+use super::segments::SegmentTranslator;
+use crate::languages::language_tag;
+use crate::translation::Inference;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Delimiter {
+    /// Comma-separated; a header with more semicolons than commas switches to `;`.
+    Csv,
+    Tsv,
+}
+
+/// Columns to translate, by header name or 1-based index, and where the translations go.
+#[derive(Clone, Copy)]
+pub struct ColumnSelection<'a> {
+    pub columns: &'a [String],
+    /// Replace the source cells instead of adding a translated column after each one.
+    pub replace: bool,
+}
+
+struct Field {
+    /// Raw bytes of the field, quotes included.
+    range: Range<usize>,
+    quoted: bool,
+    value: String,
+}
+
+struct Record {
+    fields: Vec<Field>,
+    /// Line ending after the record, empty at the end of input.
+    terminator: Range<usize>,
+}
+
+/// Translates selected columns of a CSV or TSV table.
+///
+/// The first row is the header. Translations replace the selected cells or go into new
+/// columns named `<header> (<target>)` right after them. Delimiters, quoting, line endings,
+/// blank lines, and unselected cells are copied unchanged; repeated cells are translated once.
+pub fn translate_delimited(
+    translator: &SegmentTranslator<'_, impl Inference>,
+    source: &str,
+    delimiter: Delimiter,
+    selection: ColumnSelection<'_>,
+) -> Result<String> {
+    let delimiter = match delimiter {
+        Delimiter::Tsv => '\t',
+        Delimiter::Csv => {
+            let header = source.lines().next().unwrap_or_default();
+            if header.matches(';').count() > header.matches(',').count() {
+                ';'
+            } else {
+                ','
+            }
+        }
+    };
+    let records = parse(source, delimiter)?;
+    let Some(header) = records.first() else {
+        bail!("CSV document has no header row");
+    };
+    let mut columns = Vec::new();
+    for selector in selection.columns {
+        let column = select_column(header, selector)?;
+        if !columns.contains(&column) {
+            columns.push(column);
+        }
+    }
+
+    let tag = language_tag(translator.target());
+    let mut translations: HashMap<&str, String> = HashMap::new();
+    let mut output = String::with_capacity(source.len() * 2);
+    for (row, record) in records.iter().enumerate() {
+        if record.fields.len() == 1 && record.fields[0].range.is_empty() {
+            output.push_str(&source[record.terminator.clone()]);
+            continue;
+        }
+        for (index, field) in record.fields.iter().enumerate() {
+            if index > 0 {
+                output.push(delimiter);
+            }
+            if !columns.contains(&index) {
+                output.push_str(&source[field.range.clone()]);
+                continue;
+            }
+            if row == 0 {
+                output.push_str(&source[field.range.clone()]);
+                if !selection.replace {
+                    let name = format!("{} ({tag})", field.value);
+                    output.push(delimiter);
+                    output.push_str(&encode_field(&name, field.quoted, delimiter));
+                }
+                continue;
+            }
+            let translated = match translations.get(field.value.as_str()) {
+                Some(translated) => translated.clone(),
+                None => {
+                    let translated = translator.translate(&field.value, "text")?;
+                    translations.insert(&field.value, translated.clone());
+                    translated
+                }
+            };
+            if !selection.replace {
+                output.push_str(&source[field.range.clone()]);
+                output.push(delimiter);
+            }
+            output.push_str(&encode_field(&translated, field.quoted, delimiter));
+        }
+        output.push_str(&source[record.terminator.clone()]);
+    }
+    Ok(output)
+}
+
+fn select_column(header: &Record, selector: &str) -> Result<usize> {
+    if let Some(index) = header
+        .fields
+        .iter()
+        .position(|field| field.value.trim() == selector.trim())
+    {
+        return Ok(index);
+    }
+    match selector.trim().parse::<usize>() {
+        Ok(index) if (1..=header.fields.len()).contains(&index) => Ok(index - 1),
+        Ok(index) => bail!(
+            "Column index {index} is out of range; the header has {} columns",
+            header.fields.len()
+        ),
+        Err(_) => bail!("Column not found in the header row: {selector}"),
+    }
+}
+
+fn parse(source: &str, delimiter: char) -> Result<Vec<Record>> {
+    let bytes = source.as_bytes();
+    let mut records = Vec::new();
+    let mut position = 0;
+    let mut line = 1;
+    while position < source.len() {
+        let mut fields = Vec::new();
+        loop {
+            let start = position;
+            let field = if bytes.get(position) == Some(&b'"') {
+                let mut value = String::new();
+                position += 1;
+                loop {
+                    let Some(length) = source[position..].find('"') else {
+                        bail!("Malformed CSV: unterminated quoted field starting on line {line}");
+                    };
+                    value.push_str(&source[position..position + length]);
+                    position += length + 1;
+                    if bytes.get(position) == Some(&b'"') {
+                        value.push('"');
+                        position += 1;
+                    } else {
+                        break;
+                    }
+                }
+                line += value.matches('\n').count();
+                Field {
+                    range: start..position,
+                    quoted: true,
+                    value,
+                }
+            } else {
+                let length = source[position..]
+                    .find([delimiter, '\r', '\n'])
+                    .unwrap_or(source.len() - position);
+                position += length;
+                Field {
+                    range: start..position,
+                    quoted: false,
+                    value: source[start..position].to_string(),
+                }
+            };
+            fields.push(field);
+            match source[position..].chars().next() {
+                Some(character) if character == delimiter => position += character.len_utf8(),
+                None | Some('\r' | '\n') => break,
+                Some(_) => {
+                    bail!("Malformed CSV: unexpected text after a closing quote on line {line}")
+                }
+            }
+        }
+        let rest = &source[position..];
+        let terminator = if rest.starts_with("\r\n") {
+            2
+        } else {
+            usize::from(rest.starts_with(['\r', '\n']))
+        };
+        records.push(Record {
+            fields,
+            terminator: position..position + terminator,
+        });
+        position += terminator;
+        line += 1;
+    }
+    Ok(records)
+}
+
+fn encode_field(value: &str, quoted: bool, delimiter: char) -> String {
+    if quoted || value.contains([delimiter, '"', '\r', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ColumnSelection, Delimiter, translate_delimited};
+    use crate::document::segments::SegmentTranslator;
+    use crate::document::testing::{FakeInference, translate_with};
+
+    const PHRASES: &[(&str, &str)] = &[
+        ("Röd stol", "Red chair"),
+        ("Bord, ek", "Table, oak"),
+        ("Lampa med \"dimmer\"", "Lamp with \"dimmer\""),
+        ("Mjuk\noch skön", "Soft\nand comfy"),
+        ("Stol", "Chair"),
+    ];
+
+    fn translate(
+        source: &str,
+        delimiter: Delimiter,
+        columns: &[&str],
+        replace: bool,
+    ) -> (String, Vec<String>) {
+        let columns: Vec<String> = columns.iter().map(|column| column.to_string()).collect();
+        let selection = ColumnSelection {
+            columns: &columns,
+            replace,
+        };
+        translate_with(PHRASES, "en", |translator| {
+            translate_delimited(translator, source, delimiter, selection)
+        })
+    }
+
+    #[test]
+    fn adds_translated_columns_and_keeps_quoting() {
+        let source = "sku,name,price,description\r\nA1,Röd stol,499,\"Bord, ek\"\r\nA2,\"Lampa med \"\"dimmer\"\"\",\"1,5\",\"Mjuk\noch skön\"\r\n\r\nA3,Röd stol,10\r\n";
+
+        let (output, texts) = translate(source, Delimiter::Csv, &["name", "4"], false);
+
+        assert_eq!(
+            output,
+            "sku,name,name (en),price,description,description (en)\r\nA1,Röd stol,Red chair,499,\"Bord, ek\",\"Table, oak\"\r\nA2,\"Lampa med \"\"dimmer\"\"\",\"Lamp with \"\"dimmer\"\"\",\"1,5\",\"Mjuk\noch skön\",\"Soft\nand comfy\"\r\n\r\nA3,Röd stol,Red chair,10\r\n"
+        );
+        assert_eq!(
+            texts,
+            [
+                "Röd stol",
+                "Bord, ek",
+                "Lampa med \"dimmer\"",
+                "Mjuk\noch skön"
+            ]
+        );
+    }
+
+    #[test]
+    fn replaces_columns_in_place() {
+        let source = "id\tnamn\n1\tStol\n2\tRöd stol";
+        let (tsv, _) = translate(source, Delimiter::Tsv, &["namn"], true);
+        let (semicolons, _) = translate("id;namn\n1;Stol\n", Delimiter::Csv, &["2"], true);
+
+        assert_eq!(tsv, "id\tnamn\n1\tChair\n2\tRed chair");
+        assert_eq!(semicolons, "id;namn\n1;Chair\n");
+    }
+
+    #[test]
+    fn rejects_unknown_columns_and_broken_quotes() {
+        let inference = FakeInference::replacing(PHRASES);
+        let translator = SegmentTranslator::new(&inference, "sv", "en", 1024);
+        let title = ["title".to_string()];
+        let name = ["name".to_string()];
+        let selection = |columns| ColumnSelection {
+            columns,
+            replace: false,
+        };
+
+        let missing = translate_delimited(
+            &translator,
+            "id,name\n1,Stol\n",
+            Delimiter::Csv,
+            selection(&title),
+        )
+        .expect_err("unknown column must fail");
+        let broken = translate_delimited(
+            &translator,
+            "id,name\n1,\"Stol\"x\n",
+            Delimiter::Csv,
+            selection(&name),
+        )
+        .expect_err("text after a closing quote must fail");
+
+        assert_eq!(
+            missing.to_string(),
+            "Column not found in the header row: title"
+        );
+        assert!(broken.to_string().contains("line 2"));
+        assert_eq!(inference.calls(), 0);
+    }
+}
@@ -0,0 +1,316 @@
+use std::collections::HashMap;
+use std::ops::Range;
+
+use anyhow::{Context, Result, bail};
+
+use super::package::Package;
+use super::segments::SegmentTranslator;
+use super::xml::{self, XmlKind};
+use crate::translation::Inference;
+
+const DOCUMENT_PART: &str = "word/document.xml";
+/// Run children that do not interrupt the text around them.
+const INVISIBLE_RUN_ELEMENTS: &[&str] = &["w:lastRenderedPageBreak", "w:proofErr"];
+
+/// A `w:t` element inside a paragraph.
+struct TextElement {
+    range: Range<usize>,
+    start_tag: Range<usize>,
+    text: String,
+    /// Run properties and hyperlink that must be identical for elements to share a segment.
+    style: (Option<Range<usize>>, Option<usize>),
+    /// A tab, break, drawing, or field character separates this element from the previous one.
+    separated: bool,
+}
+
+#[derive(Default)]
+struct Paragraph {
+    elements: Vec<TextElement>,
+    separated: bool,
+}
+
+#[derive(Default)]
+struct Run {
+    properties: Option<Range<usize>>,
+    properties_start: Option<usize>,
+}
+
+/// Translates the body, headers, footers, footnotes, and endnotes of a `.docx` package.
+///
+/// Text is translated per paragraph across its runs; each stretch of identically formatted text
+/// keeps its run, so bold, italic, and hyperlinks stay on the matching words.
+pub fn translate_docx(
+    translator: &SegmentTranslator<'_, impl Inference>,
+    bytes: &[u8],
+    max_input_bytes: u64,
+) -> Result<Vec<u8>> {
+    let mut package = Package::open(bytes, max_input_bytes)?;
+    if !package.contains(DOCUMENT_PART) {
+        bail!("DOCX document is missing {DOCUMENT_PART}");
+    }
+
+    let mut parts = HashMap::new();
+    for name in package.names() {
+        if !is_text_part(&name) {
+            continue;
+        }
+        let source = package.read_text(&name)?;
+        let translated = translate_part(translator, &source)
+            .with_context(|| format!("Failed to translate DOCX part {name}"))?;
+        parts.insert(name, translated);
+    }
+    package.repack(&parts)
+}
+
+fn is_text_part(name: &str) -> bool {
+    let Some(file) = name
+        .strip_prefix("word/")
+        .and_then(|file| file.strip_suffix(".xml"))
+    else {
+        return false;
+    };
+    matches!(file, "document" | "footnotes" | "endnotes")
+        || ["header", "footer"].iter().any(|prefix| {
+            file.strip_prefix(prefix)
+                .is_some_and(|number| number.chars().all(|c| c.is_ascii_digit()))
+        })
+}
+
+fn translate_part(
+    translator: &SegmentTranslator<'_, impl Inference>,
+    source: &str,
+) -> Result<String> {
+    let tokens = xml::tokenize(source)?;
+    let mut paragraphs: Vec<Paragraph> = Vec::new();
+    let mut runs: Vec<Run> = Vec::new();
+    let mut hyperlinks: Vec<usize> = Vec::new();
+    let mut edits: Vec<(Range<usize>, String)> = Vec::new();
+
+    let mut index = 0;
+    while index < tokens.len() {
+        let token = &tokens[index];
+        index += 1;
+        let in_properties = runs
+            .last()
+            .is_some_and(|run| run.properties_start.is_some());
+        match (token.kind, token.name) {
+            (XmlKind::Start, "w:p") => paragraphs.push(Paragraph::default()),
+            (XmlKind::End, "w:p") => {
+                if let Some(paragraph) = paragraphs.pop() {
+                    edits.extend(translate_paragraph(translator, source, paragraph)?);
+                }
+            }
+            (XmlKind::Start, "w:hyperlink") => hyperlinks.push(token.range.start),
+            (XmlKind::End, "w:hyperlink") => {
+                hyperlinks.pop();
+            }
+            (XmlKind::Start, "w:r") => runs.push(Run::default()),
+            (XmlKind::End, "w:r") => {
+                runs.pop();
+            }
+            (XmlKind::Empty, "w:rPr") if !in_properties => {
+                if let Some(run) = runs.last_mut() {
+                    run.properties = Some(token.range.clone());
+                }
+            }
+            (XmlKind::Start, "w:rPr") if !in_properties => {
+                if let Some(run) = runs.last_mut() {
+                    run.properties_start = Some(token.range.start);
+                }
+            }
+            (XmlKind::End, "w:rPr") => {
+                if let Some(run) = runs.last_mut() {
+                    run.properties = run
+                        .properties_start
+                        .take()
+                        .map(|start| start..token.range.end);
+                }
+            }
+            (XmlKind::Start, "w:t") if !runs.is_empty() => {
+                let Some(end) = tokens[index..]
+                    .iter()
+                    .position(|token| token.kind == XmlKind::End && token.name == "w:t")
+                else {
+                    bail!("Malformed DOCX: unclosed w:t element");
+                };
+                let end = &tokens[index + end];
+                let content = token.range.end..end.range.start;
+                if let Some(paragraph) = paragraphs.last_mut() {
+                    let properties = runs.last().and_then(|run| run.properties.clone());
+                    paragraph.elements.push(TextElement {
+                        range: token.range.start..end.range.end,
+                        start_tag: token.range.clone(),
+                        text: xml::unescape(&source[content]),
+                        style: (properties, hyperlinks.last().copied()),
+                        separated: std::mem::take(&mut paragraph.separated),
+                    });
+                }
+                index = tokens.partition_point(|token| token.range.start < end.range.end);
+            }
+            (XmlKind::Start | XmlKind::Empty, name)
+                if !runs.is_empty()
+                    && !in_properties
+                    && !INVISIBLE_RUN_ELEMENTS.contains(&name) =>
+            {
+                if let Some(paragraph) = paragraphs.last_mut() {
+                    paragraph.separated = true;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(xml::apply_edits(source, edits))
+}
+
+/// Translates a paragraph as one prompt and returns replacements for its `w:t` elements.
+///
+/// Consecutive elements with the same formatting form one segment whose translation goes into
+/// the first element; the others are emptied.
+fn translate_paragraph(
+    translator: &SegmentTranslator<'_, impl Inference>,
+    source: &str,
+    paragraph: Paragraph,
+) -> Result<Vec<(Range<usize>, String)>> {
+    let mut segments: Vec<(String, Vec<&TextElement>)> = Vec::new();
+    for element in &paragraph.elements {
+        match segments.last_mut() {
+            Some((text, elements))
+                if !element.separated
+                    && style_text(source, &elements[0].style)
+                        == style_text(source, &element.style) =>
+            {
+                text.push_str(&element.text);
+                elements.push(element);
+            }
+            _ => segments.push((element.text.clone(), vec![element])),
+        }
+    }
+    if segments.iter().all(|(text, _)| text.trim().is_empty()) {
+        return Ok(Vec::new());
+    }
+
+    let texts: Vec<&str> = segments.iter().map(|(text, _)| text.as_str()).collect();
+    let translated = translator.translate_runs(&texts, "text")?;
+    let mut edits = Vec::new();
+    for ((_, elements), text) in segments.iter().zip(translated) {
+        for (position, element) in elements.iter().enumerate() {
+            let start_tag =
+                xml::set_attribute(&source[element.start_tag.clone()], "xml:space", "preserve");
+            let content = if position == 0 {
+                xml::escape_text(&text)
+            } else {
+                String::new()
+            };
+            edits.push((element.range.clone(), format!("{start_tag}{content}</w:t>")));
+        }
+    }
+    Ok(edits)
+}
+
+fn style_text<'a>(
+    source: &'a str,
+    (properties, hyperlink): &(Option<Range<usize>>, Option<usize>),
+) -> (Option<&'a str>, Option<usize>) {
+    (properties.clone().map(|range| &source[range]), *hyperlink)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::translate_docx;
+    use crate::document::segments::SegmentTranslator;
+    use crate::document::testing::{FakeInference, translate_with, zip_entries, zip_package};
+
+    const PHRASES: &[(&str, &str)] = &[
+        ("Hej", "Hello"),
+        ("världen", "world"),
+        ("länken", "the link"),
+        ("God morgon", "Good morning"),
+        ("Sidfot", "Footer"),
+        ("Ost & bröd", "Cheese & bread"),
+    ];
+
+    fn document(body: &str) -> String {
+        format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n<w:document xmlns:w=\"http://schemas.openxmlformats.org/wordprocessingml/2006/main\"><w:body>{body}</w:body></w:document>"
+        )
+    }
+
+    fn translate(files: &[(&str, &[u8])]) -> (Vec<(String, Vec<u8>)>, Vec<String>) {
+        let (output, texts) = translate_with(PHRASES, "en", |translator| {
+            translate_docx(translator, &zip_package(files), 1 << 20)
+        });
+        (zip_entries(&output), texts)
+    }
+
+    #[test]
+    fn maps_translations_back_onto_formatted_runs() {
+        let body = document(
+            "<w:p><w:r><w:t>Hej </w:t></w:r><w:r><w:rPr><w:b/></w:rPr><w:t>världen</w:t></w:r><w:r><w:t xml:space=\"preserve\"> och </w:t></w:r><w:hyperlink r:id=\"rId5\"><w:r><w:t>länken</w:t></w:r></w:hyperlink></w:p><w:p><w:r><w:rPr><w:i/></w:rPr><w:t>God</w:t></w:r><w:proofErr w:type=\"spellStart\"/><w:r><w:rPr><w:i/></w:rPr><w:t xml:space=\"preserve\"> morgon</w:t></w:r><w:r><w:tab/><w:t>Ost &amp; bröd</w:t></w:r></w:p>",
+        );
+
+        let (entries, texts) = translate(&[("word/document.xml", body.as_bytes())]);
+
+        assert_eq!(
+            String::from_utf8(entries[0].1.clone()).unwrap(),
+            document(
+                "<w:p><w:r><w:t xml:space=\"preserve\">Hello </w:t></w:r><w:r><w:rPr><w:b/></w:rPr><w:t xml:space=\"preserve\">world</w:t></w:r><w:r><w:t xml:space=\"preserve\"> och </w:t></w:r><w:hyperlink r:id=\"rId5\"><w:r><w:t xml:space=\"preserve\">the link</w:t></w:r></w:hyperlink></w:p><w:p><w:r><w:rPr><w:i/></w:rPr><w:t xml:space=\"preserve\">Good morning</w:t></w:r><w:proofErr w:type=\"spellStart\"/><w:r><w:rPr><w:i/></w:rPr><w:t xml:space=\"preserve\"></w:t></w:r><w:r><w:tab/><w:t xml:space=\"preserve\">Cheese &amp; bread</w:t></w:r></w:p>"
+            )
+        );
+        assert_eq!(
+            texts,
+            ["Hej ⟦1⟧världen⟦2⟧ och ⟦3⟧länken", "God morgon⟦1⟧Ost & bröd"]
+        );
+    }
+
+    #[test]
+    fn translates_footers_and_copies_other_entries_unchanged() {
+        let content_types = b"<Types/>".as_slice();
+        let footer = document("<w:p><w:r><w:t>Sidfot</w:t></w:r></w:p>");
+        let styles = b"<w:styles><w:t>Hej</w:t></w:styles>".as_slice();
+        let image = [0x89, b'P', b'N', b'G', 0, 1, 2];
+
+        let (entries, texts) = translate(&[
+            ("[Content_Types].xml", content_types),
+            ("word/document.xml", document("").as_bytes()),
+            ("word/footer1.xml", footer.as_bytes()),
+            ("word/styles.xml", styles),
+            ("word/media/image1.png", &image),
+        ]);
+
+        let names: Vec<&str> = entries.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "[Content_Types].xml",
+                "word/document.xml",
+                "word/footer1.xml",
+                "word/styles.xml",
+                "word/media/image1.png"
+            ]
+        );
+        assert_eq!(entries[0].1, content_types);
+        assert!(
+            String::from_utf8_lossy(&entries[2].1)
+                .contains("<w:t xml:space=\"preserve\">Footer</w:t>")
+        );
+        assert_eq!(entries[3].1, styles);
+        assert_eq!(entries[4].1, image);
+        assert_eq!(texts, ["Sidfot"]);
+    }
+
+    #[test]
+    fn rejects_packages_without_a_document_part() {
+        let inference = FakeInference::replacing(PHRASES);
+        let translator = SegmentTranslator::new(&inference, "sv", "en", 1024);
+
+        let missing = translate_docx(&translator, &zip_package(&[("a.xml", b"<a/>")]), 1 << 20)
+            .expect_err("missing document part must fail");
+        let invalid = translate_docx(&translator, b"not a zip", 1 << 20)
+            .expect_err("invalid package must fail");
+
+        assert!(missing.to_string().contains("word/document.xml"));
+        assert!(invalid.to_string().contains("not a valid ZIP package"));
+        assert_eq!(inference.calls(), 0);
+    }
+}
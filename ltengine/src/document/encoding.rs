@@ -0,0 +1,258 @@
+use anyhow::{Result, anyhow, bail};
+use encoding_rs::{EncoderResult, Encoding, UTF_8, UTF_16BE, UTF_16LE, WINDOWS_1252};
+
+/// Unencodable characters listed in an encoding error before the rest are counted.
+const MAX_REPORTED_CHARACTERS: usize = 5;
+
+/// Labels that encoding_rs resolves to windows-1252, following the WHATWG Encoding Standard,
+/// but that name ISO-8859-1, where bytes 0x80 to 0x9F are C1 control characters.
+const LATIN1_LABELS: &[&str] = &[
+    "cp819",
+    "csisolatin1",
+    "ibm819",
+    "iso-8859-1",
+    "iso-ir-100",
+    "iso8859-1",
+    "iso88591",
+    "iso_8859-1",
+    "iso_8859-1:1987",
+    "l1",
+    "latin1",
+];
+
+/// The character encoding of a text document and whether it starts with a byte order mark.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TextEncoding {
+    encoding: &'static Encoding,
+    bom: bool,
+    /// Real ISO-8859-1: every byte is the code point of the same value, and characters above
+    /// U+00FF cannot be encoded. `encoding` is then windows-1252, its closest relative.
+    latin1: bool,
+}
+
+impl TextEncoding {
+    pub const UTF_8: Self = Self {
+        encoding: UTF_8,
+        bom: false,
+        latin1: false,
+    };
+
+    /// Resolves an `--input-encoding` or `--output-encoding` label such as `windows-1252`,
+    /// `latin1`, or `utf-16le`.
+    pub fn from_label(label: &str) -> Result<Self> {
+        let encoding = Encoding::for_label(label.trim().as_bytes())
+            .ok_or_else(|| anyhow!("Unknown encoding: {label}"))?;
+        Ok(Self {
+            encoding,
+            // UTF-16 output is unreadable to most tools without a byte order mark.
+            bom: is_utf16(encoding),
+            latin1: LATIN1_LABELS.contains(&label.trim().to_ascii_lowercase().as_str()),
+        })
+    }
+
+    /// Adds a byte order mark when the input had one; encodings without one ignore it.
+    pub fn keeping_bom(self, input: TextEncoding) -> Self {
+        Self {
+            bom: self.bom || input.bom,
+            ..self
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        if self.latin1 {
+            "ISO-8859-1"
+        } else {
+            self.encoding.name()
+        }
+    }
+}
+
+fn is_utf16(encoding: &'static Encoding) -> bool {
+    encoding == UTF_16LE || encoding == UTF_16BE
+}
+
+/// Decodes document bytes in the named encoding, or detects it.
+///
+/// Without a name, a byte order mark selects UTF-8 or UTF-16, valid UTF-8 stays UTF-8, and
+/// anything else is read as Windows-1252, the superset of ISO-8859-1 that legacy Western
+/// European text uses. A byte order mark is removed from the text.
+pub fn decode(bytes: &[u8], named: Option<TextEncoding>) -> Result<(String, TextEncoding)> {
+    if let Some(named) = named.filter(|named| named.latin1) {
+        return Ok((bytes.iter().copied().map(char::from).collect(), named));
+    }
+    let (encoding, bom_length) = match (named, Encoding::for_bom(bytes)) {
+        (Some(named), Some((sniffed, length)))
+            if named.encoding == sniffed || is_utf16(named.encoding) && is_utf16(sniffed) =>
+        {
+            (sniffed, length)
+        }
+        (Some(named), _) => (named.encoding, 0),
+        (None, Some((sniffed, length))) => (sniffed, length),
+        (None, None) if std::str::from_utf8(bytes).is_ok() => (UTF_8, 0),
+        (None, None) => (WINDOWS_1252, 0),
+    };
+    let Some(text) =
+        encoding.decode_without_bom_handling_and_without_replacement(&bytes[bom_length..])
+    else {
+        bail!("Input document is not valid {}", encoding.name());
+    };
+    let encoding = TextEncoding {
+        encoding,
+        bom: bom_length > 0,
+        latin1: false,
+    };
+    Ok((text.into_owned(), encoding))
+}
+
+/// Encodes a translated document, failing with the characters the encoding cannot represent
+/// and the lines they are on.
+pub fn encode(text: &str, output: TextEncoding) -> Result<Vec<u8>> {
+    let TextEncoding {
+        encoding,
+        bom,
+        latin1,
+    } = output;
+    let mut bytes = Vec::with_capacity(text.len() + 3);
+    if encoding == UTF_8 {
+        if bom {
+            bytes.extend([0xEF, 0xBB, 0xBF]);
+        }
+        bytes.extend(text.as_bytes());
+        return Ok(bytes);
+    }
+    if is_utf16(encoding) {
+        // encoding_rs decodes UTF-16 but only encodes into ASCII-compatible encodings.
+        let little_endian = encoding == UTF_16LE;
+        for unit in bom.then_some(0xFEFF).into_iter().chain(text.encode_utf16()) {
+            bytes.extend(if little_endian {
+                unit.to_le_bytes()
+            } else {
+                unit.to_be_bytes()
+            });
+        }
+        return Ok(bytes);
+    }
+
+    let mut unmappable: Vec<(char, usize)> = Vec::new();
+    if latin1 {
+        let mut line = 1;
+        for character in text.chars() {
+            match u8::try_from(character) {
+                Ok(byte) => bytes.push(byte),
+                Err(_) => unmappable.push((character, line)),
+            }
+            line += usize::from(character == '\n');
+        }
+        return report_unmappable(bytes, &unmappable, output);
+    }
+
+    let mut encoder = encoding.new_encoder();
+    let mut position = 0;
+    loop {
+        let remaining = text.len() - position;
+        bytes.reserve(
+            encoder
+                .max_buffer_length_from_utf8_without_replacement(remaining)
+                .unwrap_or(remaining),
+        );
+        let (result, read) = encoder.encode_from_utf8_to_vec_without_replacement(
+            &text[position..],
+            &mut bytes,
+            true,
+        );
+        position += read;
+        match result {
+            EncoderResult::InputEmpty => break,
+            EncoderResult::OutputFull => {}
+            EncoderResult::Unmappable(character) => {
+                let line = text[..position].matches('\n').count() + 1;
+                unmappable.push((character, line));
+            }
+        }
+    }
+    report_unmappable(bytes, &unmappable, output)
+}
+
+/// Returns the encoded bytes, or an error listing the characters that could not be encoded.
+fn report_unmappable(
+    bytes: Vec<u8>,
+    unmappable: &[(char, usize)],
+    output: TextEncoding,
+) -> Result<Vec<u8>> {
+    if unmappable.is_empty() {
+        return Ok(bytes);
+    }
+
+    let mut reported: Vec<(char, usize)> = Vec::new();
+    for &(character, line) in unmappable {
+        if !reported.iter().any(|&(seen, _)| seen == character) {
+            reported.push((character, line));
+        }
+    }
+    let mut listed: Vec<String> = reported
+        .iter()
+        .take(MAX_REPORTED_CHARACTERS)
+        .map(|(character, line)| {
+            format!(
+                "{character:?} (U+{:04X}) on line {line}",
+                u32::from(*character)
+            )
+        })
+        .collect();
+    if reported.len() > MAX_REPORTED_CHARACTERS {
+        listed.push(format!(
+            "and {} more",
+            reported.len() - MAX_REPORTED_CHARACTERS
+        ));
+    }
+    bail!(
+        "Translated document cannot be encoded as {}: {}",
+        output.name(),
+        listed.join(", ")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TextEncoding, decode, encode};
+
+    #[test]
+    fn detects_byte_order_marks_utf8_and_windows_1252() {
+        let (text, encoding) = decode(b"\xFF\xFEH\0e\0j\0", None).unwrap();
+        assert_eq!((text.as_str(), encoding.name()), ("Hej", "UTF-16LE"));
+        assert_eq!(encode(&text, encoding).unwrap(), b"\xFF\xFEH\0e\0j\0");
+
+        let (text, encoding) = decode("Hej då".as_bytes(), None).unwrap();
+        assert_eq!((text.as_str(), encoding), ("Hej då", TextEncoding::UTF_8));
+
+        let (text, encoding) = decode(b"Hej d\xE5 \x93v\xE4n\x94", None).unwrap();
+        assert_eq!(
+            (text.as_str(), encoding.name()),
+            ("Hej då “vän”", "windows-1252")
+        );
+
+        let latin1 = TextEncoding::from_label("latin1").unwrap();
+        assert_eq!(decode(b"\xD6l\x80", Some(latin1)).unwrap().0, "Öl\u{80}");
+        assert!(TextEncoding::from_label("klingon").is_err());
+    }
+
+    #[test]
+    fn reports_characters_the_output_encoding_cannot_represent() {
+        let encoding = TextEncoding::from_label("iso-8859-1").unwrap();
+
+        let error = encode("Zażółć\ngęślą ł", encoding).expect_err("Polish letters must fail");
+
+        assert_eq!(
+            error.to_string(),
+            "Translated document cannot be encoded as ISO-8859-1: 'ż' (U+017C) on line 1, 'ł' (U+0142) on line 1, 'ć' (U+0107) on line 1, 'ę' (U+0119) on line 2, 'ś' (U+015B) on line 2, and 1 more"
+        );
+        assert_eq!(
+            encode("Öl för två", encoding).unwrap(),
+            b"\xD6l f\xF6r tv\xE5"
+        );
+        let error = encode("5 €", encoding).expect_err("ISO-8859-1 has no euro sign");
+        assert!(error.to_string().ends_with("'€' (U+20AC) on line 1"));
+        let windows_1252 = TextEncoding::from_label("windows-1252").unwrap();
+        assert_eq!(encode("5 €", windows_1252).unwrap(), b"5 \x80");
+    }
+}
@@ -0,0 +1,332 @@
+use std::collections::HashMap;
+
+use anyhow::{Context, Result, anyhow, bail};
+
+use super::Progress;
+use super::html::translate_html;
+use super::package::Package;
+use super::segments::SegmentTranslator;
+use super::xml::{self, XmlKind, XmlToken};
+use crate::languages::language_tag;
+use crate::translation::Inference;
+
+const CONTAINER_PART: &str = "META-INF/container.xml";
+const XHTML_MEDIA_TYPE: &str = "application/xhtml+xml";
+const NCX_MEDIA_TYPE: &str = "application/x-dtbncx+xml";
+const TRANSLATED_METADATA: &[&str] = &["dc:title", "dc:description"];
+
+struct Item {
+    path: String,
+    media_type: String,
+    properties: String,
+}
+
+/// Translates the spine documents, navigation, and title and description of an EPUB book.
+///
+/// Each XHTML document goes through the HTML path and is reported to `progress` when done. The
+/// package document's `dc:language` is set to the target, and every other entry is copied.
+pub fn translate_epub(
+    translator: &SegmentTranslator<'_, impl Inference>,
+    bytes: &[u8],
+    max_input_bytes: u64,
+    progress: &dyn Fn(Progress<'_>),
+) -> Result<Vec<u8>> {
+    let mut package = Package::open(bytes, max_input_bytes)?;
+    let container = package.read_text(CONTAINER_PART)?;
+    let opf_path = rootfile(&container)?;
+    let opf = package.read_text(&opf_path)?;
+    let (items, spine) = read_manifest(&opf, &opf_path)?;
+
+    let mut documents: Vec<&str> = Vec::new();
+    for id in &spine {
+        let item = items
+            .get(id)
+            .ok_or_else(|| anyhow!("EPUB spine refers to a missing manifest item: {id}"))?;
+        if item.media_type == XHTML_MEDIA_TYPE && !documents.contains(&item.path.as_str()) {
+            documents.push(&item.path);
+        }
+    }
+    if documents.is_empty() {
+        bail!("EPUB spine contains no XHTML documents");
+    }
+    if let Some(nav) = items
+        .values()
+        .find(|item| {
+            item.properties
+                .split_whitespace()
+                .any(|property| property == "nav")
+        })
+        .filter(|nav| !documents.contains(&nav.path.as_str()))
+    {
+        documents.push(&nav.path);
+    }
+
+    let mut parts = HashMap::new();
+    for (index, path) in documents.iter().enumerate() {
+        let source = package.read_text(path)?;
+        let translated = translate_html(translator, &source, None)
+            .with_context(|| format!("Failed to translate EPUB document {path}"))?;
+        parts.insert(path.to_string(), translated);
+        progress(Progress {
+            completed: index + 1,
+            total: documents.len(),
+            part: path,
+        });
+    }
+    for ncx in items
+        .values()
+        .filter(|item| item.media_type == NCX_MEDIA_TYPE)
+    {
+        let source = package.read_text(&ncx.path)?;
+        let translated = translate_elements(translator, &source, &["text"], |_, _| None)?;
+        parts.insert(ncx.path.clone(), translated);
+    }
+
+    let language = language_tag(translator.target());
+    let translated_opf =
+        translate_elements(translator, &opf, TRANSLATED_METADATA, |token, tag| {
+            match (token.kind, token.name) {
+                (XmlKind::Start, "package") => Some(xml::set_attribute(tag, "xml:lang", language)),
+                _ => None,
+            }
+        })?;
+    parts.insert(opf_path, set_language(&translated_opf, language)?);
+    package.repack(&parts)
+}
+
+fn rootfile(container: &str) -> Result<String> {
+    xml::tokenize(container)?
+        .iter()
+        .filter(|token| matches!(token.kind, XmlKind::Start | XmlKind::Empty))
+        .filter(|token| token.name == "rootfile")
+        .find_map(|token| xml::attribute(&container[token.range.clone()], "full-path"))
+        .ok_or_else(|| anyhow!("EPUB container does not name a package document"))
+}
+
+/// Returns manifest items by id, with paths resolved against the package document, and the
+/// spine order.
+fn read_manifest(opf: &str, opf_path: &str) -> Result<(HashMap<String, Item>, Vec<String>)> {
+    let base = opf_path
+        .rsplit_once('/')
+        .map_or("", |(directory, _)| directory);
+    let mut items = HashMap::new();
+    let mut spine = Vec::new();
+    for token in xml::tokenize(opf)? {
+        if !matches!(token.kind, XmlKind::Start | XmlKind::Empty) {
+            continue;
+        }
+        let tag = &opf[token.range.clone()];
+        let attribute = |name| xml::attribute(tag, name).unwrap_or_default();
+        match token.name {
+            "item" => {
+                items.insert(
+                    attribute("id"),
+                    Item {
+                        path: resolve(base, &attribute("href")),
+                        media_type: attribute("media-type"),
+                        properties: attribute("properties"),
+                    },
+                );
+            }
+            "itemref" => spine.push(attribute("idref")),
+            _ => {}
+        }
+    }
+    Ok((items, spine))
+}
+
+/// Resolves a manifest href relative to `base`, decoding percent escapes and `..` segments.
+fn resolve(base: &str, href: &str) -> String {
+    let href = href.split('#').next().unwrap_or_default();
+    let mut segments: Vec<String> = base
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .map(str::to_string)
+        .collect();
+    for segment in href.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            segment => segments.push(percent_decode(segment)),
+        }
+    }
+    segments.join("/")
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let escaped = (bytes[index] == b'%')
+            .then(|| text.get(index + 1..index + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                index += 3;
+            }
+            None => {
+                decoded.push(bytes[index]);
+                index += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Translates the text content of the named elements and lets `rewrite_tag` replace start tags.
+fn translate_elements(
+    translator: &SegmentTranslator<'_, impl Inference>,
+    source: &str,
+    names: &[&str],
+    rewrite_tag: impl Fn(&XmlToken<'_>, &str) -> Option<String>,
+) -> Result<String> {
+    let tokens = xml::tokenize(source)?;
+    let mut edits = Vec::new();
+    for (index, token) in tokens.iter().enumerate() {
+        if let Some(tag) = rewrite_tag(token, &source[token.range.clone()]) {
+            edits.push((token.range.clone(), tag));
+        }
+        if token.kind != XmlKind::Start || !names.contains(&token.name) {
+            continue;
+        }
+        if let Some(text) = tokens
+            .get(index + 1)
+            .filter(|text| text.kind == XmlKind::Text)
+        {
+            let translated =
+                translator.translate(&xml::unescape(&source[text.range.clone()]), "text")?;
+            edits.push((text.range.clone(), xml::escape_text(&translated)));
+        }
+    }
+    Ok(xml::apply_edits(source, edits))
+}
+
+fn set_language(opf: &str, language: &str) -> Result<String> {
+    let tokens = xml::tokenize(opf)?;
+    let edits = tokens
+        .windows(2)
+        .filter(|pair| pair[0].kind == XmlKind::Start && pair[0].name == "dc:language")
+        .filter(|pair| pair[1].kind == XmlKind::Text)
+        .map(|pair| (pair[1].range.clone(), language.to_string()))
+        .collect();
+    Ok(xml::apply_edits(opf, edits))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use super::translate_epub;
+    use crate::document::segments::SegmentTranslator;
+    use crate::document::testing::{FakeInference, translate_with, zip_entries, zip_package};
+
+    const PHRASES: &[(&str, &str)] = &[
+        ("Boken", "The book"),
+        ("En berättelse", "A story"),
+        ("Innehåll", "Contents"),
+        ("Kapitel ett", "Chapter one"),
+        ("Kapitel två", "Chapter two"),
+        ("Hej", "Hello"),
+    ];
+    const CONTAINER: &str = "<?xml version=\"1.0\"?><container version=\"1.0\" xmlns=\"urn:oasis:names:tc:opendocument:xmlns:container\"><rootfiles><rootfile full-path=\"OEBPS/content.opf\" media-type=\"application/oebps-package+xml\"/></rootfiles></container>";
+    const OPF: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<package xmlns=\"http://www.idpf.org/2007/opf\" version=\"3.0\" xml:lang=\"sv\" unique-identifier=\"id\"><metadata xmlns:dc=\"http://purl.org/dc/elements/1.1/\"><dc:identifier id=\"id\">urn:uuid:1</dc:identifier><dc:title>Boken</dc:title><dc:description>En berättelse &amp; mer</dc:description><dc:language>sv</dc:language><dc:creator>Anna</dc:creator></metadata><manifest><item id=\"nav\" href=\"nav.xhtml\" media-type=\"application/xhtml+xml\" properties=\"nav\"/><item id=\"c1\" href=\"text/kapitel%201.xhtml\" media-type=\"application/xhtml+xml\"/><item id=\"c2\" href=\"text/../text/kapitel2.xhtml\" media-type=\"application/xhtml+xml\"/><item id=\"ncx\" href=\"toc.ncx\" media-type=\"application/x-dtbncx+xml\"/><item id=\"css\" href=\"style.css\" media-type=\"text/css\"/></manifest><spine toc=\"ncx\"><itemref idref=\"c1\"/><itemref idref=\"c2\"/></spine></package>";
+
+    fn chapter(title: &str) -> String {
+        format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<html xmlns=\"http://www.w3.org/1999/xhtml\" xml:lang=\"sv\" lang=\"sv\"><head><title>{title}</title></head><body><h1>{title}</h1><p>Hej<br/></p></body></html>"
+        )
+    }
+
+    #[test]
+    fn translates_spine_navigation_and_metadata_with_progress() {
+        let nav = "<html xmlns=\"http://www.w3.org/1999/xhtml\" lang=\"sv\"><body><nav epub:type=\"toc\"><h1>Innehåll</h1><ol><li><a href=\"text/kapitel%201.xhtml\">Kapitel ett</a></li></ol></nav></body></html>";
+        let ncx = "<ncx><docTitle><text>Boken</text></docTitle><navMap><navPoint id=\"p1\"><navLabel><text>Kapitel ett</text></navLabel><content src=\"text/kapitel%201.xhtml\"/></navPoint></navMap></ncx>";
+        let css = b"h1 { color: red }".as_slice();
+        let package = zip_package(&[
+            ("mimetype", b"application/epub+zip"),
+            ("META-INF/container.xml", CONTAINER.as_bytes()),
+            ("OEBPS/content.opf", OPF.as_bytes()),
+            ("OEBPS/nav.xhtml", nav.as_bytes()),
+            (
+                "OEBPS/text/kapitel 1.xhtml",
+                chapter("Kapitel ett").as_bytes(),
+            ),
+            (
+                "OEBPS/text/kapitel2.xhtml",
+                chapter("Kapitel två").as_bytes(),
+            ),
+            ("OEBPS/toc.ncx", ncx.as_bytes()),
+            ("OEBPS/style.css", css),
+        ]);
+        let reports = RefCell::new(Vec::new());
+
+        let (output, _) = translate_with(PHRASES, "en", |translator| {
+            translate_epub(translator, &package, 1 << 20, &|progress| {
+                reports.borrow_mut().push((
+                    progress.completed,
+                    progress.total,
+                    progress.part.to_string(),
+                ));
+            })
+        });
+
+        let entries = zip_entries(&output);
+        let text = |index: usize| String::from_utf8(entries[index].1.clone()).unwrap();
+        assert_eq!(entries[0].1, b"application/epub+zip");
+        assert_eq!(entries[1].1, CONTAINER.as_bytes());
+        assert_eq!(
+            text(2),
+            OPF.replace("xml:lang=\"sv\"", "xml:lang=\"en\"")
+                .replace("<dc:title>Boken", "<dc:title>The book")
+                .replace("En berättelse &amp; mer", "A story &amp; mer")
+                .replace("<dc:language>sv", "<dc:language>en")
+        );
+        assert!(text(3).contains("<h1>Contents</h1>"));
+        assert!(text(3).contains(">Chapter one</a>"));
+        assert_eq!(
+            text(4),
+            chapter("Chapter one")
+                .replace("lang=\"sv\"", "lang=\"en\"")
+                .replace("Hej", "Hello")
+        );
+        assert!(text(5).contains("<h1>Chapter two</h1>"));
+        assert!(text(6).contains("<text>The book</text>"));
+        assert_eq!(entries[7].1, css);
+        assert_eq!(
+            reports.take(),
+            [
+                (1, 3, "OEBPS/text/kapitel 1.xhtml".to_string()),
+                (2, 3, "OEBPS/text/kapitel2.xhtml".to_string()),
+                (3, 3, "OEBPS/nav.xhtml".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_books_without_a_package_document() {
+        let inference = FakeInference::replacing(PHRASES);
+        let translator = SegmentTranslator::new(&inference, "sv", "en", 1024);
+        let package = zip_package(&[
+            ("mimetype", b"application/epub+zip"),
+            (
+                "META-INF/container.xml",
+                b"<container><rootfiles/></container>",
+            ),
+        ]);
+
+        let error = translate_epub(&translator, &package, 1 << 20, &|_| {})
+            .expect_err("missing package document must fail");
+
+        assert!(
+            error
+                .to_string()
+                .contains("does not name a package document")
+        );
+        assert_eq!(inference.calls(), 0);
+    }
+}
@@ -0,0 +1,369 @@
+use std::ops::Range;
+
+use anyhow::{Result, bail};
+
+use super::segments::SegmentTranslator;
+use super::xml;
+use crate::translation::Inference;
+
+/// Nesting limit for placeables and select expressions.
+const MAX_DEPTH: usize = 64;
+
+struct Parser<'a, 'b, I: Inference> {
+    source: &'a str,
+    position: usize,
+    translator: &'a SegmentTranslator<'b, I>,
+    /// Whether patterns are translated; term definitions are only parsed.
+    translate: bool,
+}
+
+/// Translates the message values and attributes of a Project Fluent `.ftl` file.
+///
+/// Message IDs, comments, terms, and layout are copied unchanged. Placeables such as
+/// `{ $name }` and `{ -brand }` are kept verbatim inside the translated text, and each variant
+/// of a select expression is translated on its own while the selector and variant keys stay.
+pub fn translate_fluent(
+    translator: &SegmentTranslator<'_, impl Inference>,
+    source: &str,
+) -> Result<String> {
+    let mut parser = Parser {
+        source,
+        position: 0,
+        translator,
+        translate: false,
+    };
+    let mut edits = Vec::new();
+    while parser.position < source.len() {
+        let rest = &source[parser.position..];
+        let line = &rest[..rest.find('\n').map_or(rest.len(), |end| end + 1)];
+        if line.trim().is_empty() || line.starts_with('#') {
+            parser.position += line.len();
+            continue;
+        }
+        let term = line.starts_with('-');
+        let name = identifier(&line[usize::from(term)..]);
+        if name.is_empty() {
+            bail!(
+                "Invalid Fluent: expected a message, term, or comment on line {}",
+                parser.line()
+            );
+        }
+        parser.position += usize::from(term) + name.len();
+        parser.translate = !term;
+        parser.value(name, &mut edits)?;
+
+        while let Some(length) = attribute_start(&source[parser.position..]) {
+            parser.position += length;
+            let name = identifier(&source[parser.position..]);
+            parser.position += name.len();
+            parser.value(name, &mut edits)?;
+        }
+        match source[parser.position..].chars().next() {
+            None => {}
+            Some('\n') => parser.position += 1,
+            Some('\r') if source[parser.position..].starts_with("\r\n") => parser.position += 2,
+            Some(character) => bail!(
+                "Invalid Fluent: unexpected {character:?} on line {}",
+                parser.line()
+            ),
+        }
+    }
+    Ok(xml::apply_edits(source, edits))
+}
+
+/// Returns the Fluent identifier at the start of `text`, or an empty string.
+fn identifier(text: &str) -> &str {
+    if !text.starts_with(|character: char| character.is_ascii_alphabetic()) {
+        return "";
+    }
+    let length = text
+        .find(|character: char| !(character.is_ascii_alphanumeric() || "_-".contains(character)))
+        .unwrap_or(text.len());
+    &text[..length]
+}
+
+/// Returns the length up to the name of an attribute, such as `\n    .title`, if one follows.
+fn attribute_start(rest: &str) -> Option<usize> {
+    let mut length = 0;
+    loop {
+        length += line_break(&rest[length..])?;
+        let line = &rest[length..];
+        let content = line.trim_start_matches([' ', '\t']);
+        if content.starts_with(['\r', '\n']) {
+            length += line.len() - content.len();
+            continue;
+        }
+        let indent = line.len() - content.len();
+        let name = content.strip_prefix('.')?;
+        return (indent > 0 && !identifier(name).is_empty()).then_some(length + indent + 1);
+    }
+}
+
+/// Returns the line breaks and indentation before the next line of a pattern, if it continues.
+///
+/// Indented lines continue a pattern unless they start with `.`, `[`, `*`, or `}`, which
+/// begin an attribute or a variant or close a select expression.
+fn continuation(rest: &str) -> Option<usize> {
+    let mut length = 0;
+    loop {
+        length += line_break(&rest[length..])?;
+        let line = &rest[length..];
+        let content = line.trim_start_matches(' ');
+        if content.starts_with(['\r', '\n']) {
+            length += line.len() - content.len();
+            continue;
+        }
+        let indent = line.len() - content.len();
+        if indent == 0 || content.is_empty() || content.starts_with(['.', '[', '*', '}']) {
+            return None;
+        }
+        return Some(length + indent);
+    }
+}
+
+fn line_break(text: &str) -> Option<usize> {
+    if text.starts_with("\r\n") {
+        Some(2)
+    } else {
+        text.starts_with('\n').then_some(1)
+    }
+}
+
+impl<I: Inference> Parser<'_, '_, I> {
+    fn line(&self) -> usize {
+        self.source[..self.position].matches('\n').count() + 1
+    }
+
+    /// Reads ` = pattern` after a message, term, or attribute name and records its translation.
+    fn value(&mut self, name: &str, edits: &mut Vec<(Range<usize>, String)>) -> Result<()> {
+        let rest = &self.source[self.position..];
+        let Some(after) = rest.trim_start_matches(' ').strip_prefix('=') else {
+            bail!(
+                "Invalid Fluent: expected = after {name} on line {}",
+                self.line()
+            );
+        };
+        self.position += rest.len() - after.trim_start_matches(' ').len();
+        let start = self.position;
+        let translated = self.pattern(0)?;
+        if translated != self.source[start..self.position] {
+            edits.push((start..self.position, translated));
+        }
+        Ok(())
+    }
+
+    /// Reads a pattern and returns it with its text translated.
+    fn pattern(&mut self, depth: usize) -> Result<String> {
+        let mut runs = vec![String::new()];
+        // Markup as shown to the model and as written to the output; a select expression is
+        // shown as its selector.
+        let mut shown: Vec<String> = Vec::new();
+        let mut actual: Vec<String> = Vec::new();
+        loop {
+            let rest = &self.source[self.position..];
+            let (display, text) = match rest.chars().next() {
+                None | Some('}') => break,
+                Some('{') => self.placeable(depth)?,
+                Some('\r' | '\n') => {
+                    let Some(length) = continuation(rest) else {
+                        break;
+                    };
+                    self.position += length;
+                    (rest[..length].to_string(), rest[..length].to_string())
+                }
+                Some(character) => {
+                    runs.last_mut().expect("runs are not empty").push(character);
+                    self.position += character.len_utf8();
+                    continue;
+                }
+            };
+            if runs.len() > 1 && runs.last().is_some_and(String::is_empty) {
+                shown
+                    .last_mut()
+                    .expect("markup precedes the run")
+                    .push_str(&display);
+                actual
+                    .last_mut()
+                    .expect("markup precedes the run")
+                    .push_str(&text);
+            } else {
+                shown.push(display);
+                actual.push(text);
+                runs.push(String::new());
+            }
+        }
+
+        let translated =
+            if self.translate && runs.iter().any(|run| run.contains(char::is_alphabetic)) {
+                let translated = self.translator.translate_marked(&runs, &shown, "text")?;
+                translated
+                    .iter()
+                    .enumerate()
+                    .map(|(index, run)| {
+                        let line_start =
+                            index > 0 && actual[index - 1].trim_end_matches(' ').ends_with('\n');
+                        encode(run, line_start)
+                    })
+                    .collect()
+            } else {
+                runs
+            };
+        let mut pattern = translated[0].clone();
+        for (markup, run) in actual.iter().zip(&translated[1..]) {
+            pattern.push_str(markup);
+            pattern.push_str(run);
+        }
+        Ok(pattern)
+    }
+
+    /// Reads a placeable, returning how it is shown to the model and its translated source.
+    fn placeable(&mut self, depth: usize) -> Result<(String, String)> {
+        if depth > MAX_DEPTH {
+            bail!("Invalid Fluent: placeables nested deeper than {MAX_DEPTH} levels");
+        }
+        let start = self.position;
+        self.position += 1;
+        loop {
+            let rest = &self.source[self.position..];
+            match rest.chars().next() {
+                None => bail!("Invalid Fluent: unclosed placeable on line {}", self.line()),
+                Some('"') => {
+                    let mut escaped = false;
+                    let Some(length) = rest[1..].find(|character: char| {
+                        let end = character == '"' && !escaped;
+                        escaped = character == '\\' && !escaped;
+                        end || character == '\n'
+                    }) else {
+                        bail!("Invalid Fluent: unclosed string on line {}", self.line());
+                    };
+                    if rest[1 + length..].starts_with('\n') {
+                        bail!("Invalid Fluent: unclosed string on line {}", self.line());
+                    }
+                    self.position += length + 2;
+                }
+                Some('{') => {
+                    self.placeable(depth + 1)?;
+                }
+                Some('}') => {
+                    self.position += 1;
+                    let raw = &self.source[start..self.position];
+                    return Ok((raw.to_string(), raw.to_string()));
+                }
+                Some('-') if rest.starts_with("->") => break,
+                Some(character) => self.position += character.len_utf8(),
+            }
+        }
+
+        let selector = self.source[start + 1..self.position].trim();
+        self.position += 2;
+        let mut select = self.source[start..self.position].to_string();
+        loop {
+            let rest = &self.source[self.position..];
+            let whitespace = rest.len() - rest.trim_start().len();
+            select.push_str(&rest[..whitespace]);
+            self.position += whitespace;
+            let rest = &self.source[self.position..];
+            if rest.starts_with('}') {
+                select.push('}');
+                self.position += 1;
+                return Ok((format!("{{ {selector} }}"), select));
+            }
+            let key = rest.strip_prefix('*').unwrap_or(rest);
+            let Some(key_end) = key
+                .strip_prefix('[')
+                .and_then(|key| key.find([']', '\n']))
+                .filter(|&end| key[1 + end..].starts_with(']'))
+            else {
+                bail!(
+                    "Invalid Fluent: expected a variant key on line {}",
+                    self.line()
+                );
+            };
+            let key_length = rest.len() - key.len() + key_end + 2;
+            let spaces =
+                rest[key_length..].len() - rest[key_length..].trim_start_matches(' ').len();
+            select.push_str(&rest[..key_length + spaces]);
+            self.position += key_length + spaces;
+            select.push_str(&self.pattern(depth + 1)?);
+        }
+    }
+}
+
+/// Escapes text that Fluent would read as syntax: braces anywhere and `[`, `*`, or `.` at the
+/// start of a line.
+fn encode(text: &str, line_start: bool) -> String {
+    let mut encoded = String::with_capacity(text.len());
+    for (index, character) in text.char_indices() {
+        let special = matches!(character, '{' | '}')
+            || (line_start && index == 0 && matches!(character, '[' | '*' | '.'));
+        match character {
+            _ if special => encoded.push_str(&format!("{{\"{character}\"}}")),
+            '\r' | '\n' => encoded.push(' '),
+            _ => encoded.push(character),
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::translate_fluent;
+    use crate::document::segments::SegmentTranslator;
+    use crate::document::testing::{FakeInference, translate_with};
+
+    const PHRASES: &[(&str, &str)] = &[
+        ("Hej", "Hello"),
+        ("Din e-post", "Your email"),
+        ("Logga in på", "Log in to"),
+        ("Du har ett mejl.", "You have one email."),
+        ("Du har", "You have"),
+        ("mejl.", "emails."),
+        ("Om appen", "About the app"),
+        ("och mer", "and more"),
+        ("Klammer", "Brace {x}"),
+    ];
+
+    fn translate(source: &str) -> (String, Vec<String>) {
+        translate_with(PHRASES, "en", |translator| {
+            translate_fluent(translator, source)
+        })
+    }
+
+    #[test]
+    fn translates_messages_attributes_and_variants() {
+        let source = "# Kommentar\n-brand = Cirkeln\n    .gender = feminine\n\nhello = Hej, { $name }!\nlogin =\n    .placeholder = Din e-post\n    .title = Logga in på { -brand }\nemails = { $count ->\n    [one] Du har ett mejl.\n   *[other] Du har { $count } mejl.\n}\nabout =\n    Om appen\n    och mer\n";
+
+        let (output, texts) = translate(source);
+
+        assert_eq!(
+            output,
+            "# Kommentar\n-brand = Cirkeln\n    .gender = feminine\n\nhello = Hello, { $name }!\nlogin =\n    .placeholder = Your email\n    .title = Log in to { -brand }\nemails = { $count ->\n    [one] You have one email.\n   *[other] You have { $count } emails.\n}\nabout =\n    About the app\n    and more\n"
+        );
+        assert_eq!(
+            texts,
+            [
+                "Hej, { $name }!",
+                "Din e-post",
+                "Logga in på { -brand }",
+                "Du har ett mejl.",
+                "Du har { $count } mejl.",
+                "Om appen\n    och mer"
+            ]
+        );
+    }
+
+    #[test]
+    fn escapes_braces_and_rejects_invalid_entries() {
+        let (output, _) = translate("brace = Klammer\n");
+        let inference = FakeInference::replacing(PHRASES);
+        let translator = SegmentTranslator::new(&inference, "sv", "en", 1024);
+        let error =
+            translate_fluent(&translator, "hello Hej\n").expect_err("a missing = must fail");
+
+        assert_eq!(output, "brace = Brace {\"{\"}x{\"}\"}\n");
+        assert_eq!(
+            error.to_string(),
+            "Invalid Fluent: expected = after hello on line 1"
+        );
+    }
+}
@@ -0,0 +1,502 @@
+use std::ops::Range;
+
+use anyhow::{Result, bail};
+
+use super::placeholders::format_specifiers;
+use super::segments::SegmentTranslator;
+use crate::languages::{PluralForms, language_tag, plural_forms};
+use crate::translation::Inference;
+
+/// Flag added to every entry filled by the model so translators review it.
+const FUZZY: &str = "fuzzy";
+
+#[derive(Clone, Copy)]
+enum Field {
+    Context,
+    Id,
+    IdPlural,
+    Str,
+}
+
+/// One PO entry, with line indices into the whole file.
+#[derive(Default)]
+struct Entry {
+    msgid: Option<String>,
+    msgid_plural: Option<String>,
+    msgstr: Vec<String>,
+    /// Lines holding the `msgstr` keywords and their continuation strings.
+    msgstr_lines: Option<Range<usize>>,
+    flags_line: Option<usize>,
+    /// Line before which a new `#,` flags line goes: after the comments, before `#|` and keywords.
+    flags_at: Option<usize>,
+    obsolete: bool,
+}
+
+/// Fills the empty `msgstr` of every entry in a gettext PO or POT catalog.
+///
+/// Entries with `msgid_plural` get one form per plural form of the target language, each
+/// translated from a sample count so the model inflects it. Filled entries are flagged
+/// `fuzzy`; translated entries, obsolete entries, contexts, and comments are copied. The header
+/// gets the target `Language` and `Plural-Forms`.
+pub fn translate_gettext(
+    translator: &SegmentTranslator<'_, impl Inference>,
+    source: &str,
+) -> Result<String> {
+    let lines: Vec<&str> = source.split_inclusive('\n').collect();
+    let newline = if source.contains("\r\n") {
+        "\r\n"
+    } else {
+        "\n"
+    };
+    let forms = plural_forms(translator.target());
+
+    let mut edits: Vec<(Range<usize>, String)> = Vec::new();
+    let mut start = 0;
+    while start < lines.len() {
+        if lines[start].trim().is_empty() {
+            start += 1;
+            continue;
+        }
+        let end = entry_end(&lines, start);
+        let entry = parse_entry(&lines, start..end)?;
+        start = end;
+
+        let (Some(msgid), Some(msgstr_lines)) = (&entry.msgid, entry.msgstr_lines.clone()) else {
+            continue;
+        };
+        if entry.obsolete {
+            continue;
+        }
+        if msgid.is_empty() {
+            let header = entry.msgstr.first().map_or("", String::as_str);
+            let updated = update_header(header, translator.target(), forms);
+            if updated != header {
+                edits.push((msgstr_lines, format_string("msgstr", &updated, newline)));
+            }
+            continue;
+        }
+        if entry.msgstr.iter().any(|value| !value.is_empty()) {
+            continue;
+        }
+
+        let translated = match &entry.msgid_plural {
+            None => {
+                vec![translator.translate_protected(msgid, &format_specifiers(msgid), "text")?]
+            }
+            Some(plural) => forms
+                .samples
+                .iter()
+                .map(|&count| {
+                    let text = if count == 1 { msgid } else { plural };
+                    translate_plural(translator, text, count, format_specifiers)
+                })
+                .collect::<Result<_>>()?,
+        };
+        match entry.flags_line {
+            Some(index) => {
+                let line = lines[index].trim_end_matches(['\r', '\n']);
+                let flags = line.trim_start();
+                if !flags[2..].split(',').any(|flag| flag.trim() == FUZZY) {
+                    let indent = &line[..line.len() - flags.len()];
+                    let rest = flags[2..].trim_start();
+                    let ending = &lines[index][line.len()..];
+                    edits.push((
+                        index..index + 1,
+                        format!("{indent}#, {FUZZY}, {rest}{ending}"),
+                    ));
+                }
+            }
+            None => {
+                let at = entry.flags_at.unwrap_or(msgstr_lines.start);
+                edits.push((at..at, format!("#, {FUZZY}{newline}")));
+            }
+        }
+        let msgstr = match entry.msgid_plural {
+            None => format_string("msgstr", &translated[0], newline),
+            Some(_) => translated
+                .iter()
+                .enumerate()
+                .map(|(index, text)| format_string(&format!("msgstr[{index}]"), text, newline))
+                .collect(),
+        };
+        edits.push((msgstr_lines, msgstr));
+    }
+
+    let mut output = String::with_capacity(source.len());
+    let mut line = 0;
+    for (range, text) in edits {
+        output.extend(lines[line..range.start].iter().copied());
+        if range.end == lines.len() && !source.ends_with('\n') {
+            output.push_str(text.strip_suffix(newline).unwrap_or(&text));
+        } else {
+            output.push_str(&text);
+        }
+        line = range.end;
+    }
+    output.extend(lines[line..].iter().copied());
+    Ok(output)
+}
+
+/// Finds the end of the entry starting at `start`: a blank line, or a comment, `msgctxt`, or
+/// `msgid` line after the entry's `msgstr`, since entries need not be separated by blank lines.
+fn entry_end(lines: &[&str], start: usize) -> usize {
+    let mut seen_msgstr = false;
+    for (index, line) in lines.iter().enumerate().skip(start) {
+        let line = line.trim();
+        let keyword = line.split(char::is_whitespace).next().unwrap_or_default();
+        if line.is_empty()
+            || seen_msgstr && (line.starts_with('#') || matches!(keyword, "msgctxt" | "msgid"))
+        {
+            return index;
+        }
+        seen_msgstr |= keyword == "msgstr" || keyword.starts_with("msgstr[");
+    }
+    lines.len()
+}
+
+fn parse_entry(lines: &[&str], block: Range<usize>) -> Result<Entry> {
+    let mut entry = Entry::default();
+    let mut field = None;
+    let mut context = String::new();
+    for index in block {
+        let line = lines[index].trim();
+        if line.starts_with("#~") {
+            entry.obsolete = true;
+            continue;
+        }
+        if entry.flags_at.is_none() && (line.starts_with("#|") || !line.starts_with('#')) {
+            entry.flags_at = Some(index);
+        }
+        if line.starts_with("#,") {
+            entry.flags_line = Some(index);
+        }
+        if line.starts_with('#') {
+            continue;
+        }
+
+        let (keyword, quoted) = if line.starts_with('"') {
+            (None, line)
+        } else {
+            let (keyword, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            (Some(keyword), rest.trim_start())
+        };
+        let Some(value) = parse_string(quoted) else {
+            bail!("Malformed PO file: invalid string on line {}", index + 1);
+        };
+        if let Some(keyword) = keyword {
+            field = Some(match keyword {
+                "msgctxt" => Field::Context,
+                "msgid" => Field::Id,
+                "msgid_plural" => Field::IdPlural,
+                _ if keyword == "msgstr" || keyword.starts_with("msgstr[") => {
+                    entry.msgstr.push(String::new());
+                    Field::Str
+                }
+                _ => bail!(
+                    "Malformed PO file: unknown keyword {keyword} on line {}",
+                    index + 1
+                ),
+            });
+        }
+        let target = match field {
+            Some(Field::Context) => &mut context,
+            Some(Field::Id) => entry.msgid.get_or_insert_default(),
+            Some(Field::IdPlural) => entry.msgid_plural.get_or_insert_default(),
+            Some(Field::Str) => {
+                let lines = entry.msgstr_lines.get_or_insert(index..index);
+                lines.end = index + 1;
+                entry.msgstr.last_mut().expect("msgstr field has a value")
+            }
+            None => bail!(
+                "Malformed PO file: string without keyword on line {}",
+                index + 1
+            ),
+        };
+        target.push_str(&value);
+    }
+    Ok(entry)
+}
+
+/// Parses one double-quoted PO string with C escapes.
+fn parse_string(quoted: &str) -> Option<String> {
+    let inner = quoted
+        .strip_prefix('"')
+        .and_then(|rest| rest.strip_suffix('"'))?;
+    let mut value = String::with_capacity(inner.len());
+    let mut characters = inner.chars();
+    while let Some(character) = characters.next() {
+        if character == '"' {
+            return None;
+        }
+        if character != '\\' {
+            value.push(character);
+            continue;
+        }
+        match characters.next()? {
+            'n' => value.push('\n'),
+            't' => value.push('\t'),
+            'r' => value.push('\r'),
+            'a' => value.push('\u{7}'),
+            'b' => value.push('\u{8}'),
+            'f' => value.push('\u{c}'),
+            'v' => value.push('\u{b}'),
+            other => value.push(other),
+        }
+    }
+    Some(value)
+}
+
+fn escape_string(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for character in value.chars() {
+        match character {
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            '\r' => escaped.push_str("\\r"),
+            other => escaped.push(other),
+        }
+    }
+    escaped
+}
+
+/// Writes a keyword and its string, one continuation line per embedded newline like `msgmerge`.
+fn format_string(keyword: &str, value: &str, newline: &str) -> String {
+    let pieces: Vec<&str> = value.split_inclusive('\n').collect();
+    if pieces.len() <= 1 {
+        return format!("{keyword} \"{}\"{newline}", escape_string(value));
+    }
+    let mut formatted = format!("{keyword} \"\"{newline}");
+    for piece in pieces {
+        formatted.push_str(&format!("\"{}\"{newline}", escape_string(piece)));
+    }
+    formatted
+}
+
+/// Sets the `Language` and `Plural-Forms` header fields and replaces a template `CHARSET`.
+fn update_header(header: &str, target: &str, forms: &PluralForms) -> String {
+    let language = language_tag(target).replace('-', "_");
+    let mut fields = vec![("Language", language), ("Plural-Forms", forms.header())];
+    let mut updated = String::with_capacity(header.len());
+    for line in header.split_inclusive('\n') {
+        let name = line.split_once(':').map_or("", |(name, _)| name.trim());
+        match fields
+            .iter()
+            .position(|(field, _)| field.eq_ignore_ascii_case(name))
+        {
+            Some(position) => {
+                let (field, value) = fields.remove(position);
+                updated.push_str(&format!("{field}: {value}\n"));
+            }
+            None if name.eq_ignore_ascii_case("Content-Type") => {
+                updated.push_str(&line.replace("charset=CHARSET", "charset=UTF-8"));
+            }
+            None => updated.push_str(line),
+        }
+    }
+    if !updated.is_empty() && !updated.ends_with('\n') {
+        updated.push('\n');
+    }
+    for (field, value) in fields {
+        updated.push_str(&format!("{field}: {value}\n"));
+    }
+    updated
+}
+
+/// Translates one plural form with its count specifier, such as `%d` or Qt's `%n`, replaced by
+/// a sample count for the form.
+///
+/// The sample is put back as the specifier when it comes back exactly once; otherwise the form
+/// is translated with the specifier in place. `placeholders` finds the ranges to protect.
+pub fn translate_plural(
+    translator: &SegmentTranslator<'_, impl Inference>,
+    text: &str,
+    count: u64,
+    placeholders: fn(&str) -> Vec<Range<usize>>,
+) -> Result<String> {
+    let counts: Vec<Range<usize>> = placeholders(text)
+        .into_iter()
+        .filter(|range| text[range.clone()].ends_with(['d', 'i', 'u', 'n']))
+        .collect();
+    if let [range] = counts.as_slice() {
+        let sample = count.to_string();
+        let example = format!("{}{sample}{}", &text[..range.start], &text[range.end..]);
+        let translated =
+            translator.translate_protected(&example, &placeholders(&example), "text")?;
+        let found: Vec<usize> = translated
+            .match_indices(&sample)
+            .map(|(index, _)| index)
+            .filter(|&index| {
+                let before = translated[..index].chars().next_back();
+                let after = translated[index + sample.len()..].chars().next();
+                !before.is_some_and(|c| c.is_ascii_digit())
+                    && !after.is_some_and(|c| c.is_ascii_digit())
+            })
+            .collect();
+        if let [index] = found.as_slice() {
+            return Ok(format!(
+                "{}{}{}",
+                &translated[..*index],
+                &text[range.clone()],
+                &translated[index + sample.len()..]
+            ));
+        }
+    }
+    translator.translate_protected(text, &placeholders(text), "text")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::translate_gettext;
+    use crate::document::testing::translate_with;
+
+    const PHRASES: &[(&str, &str)] = &[
+        ("Öppna fil", "Open file"),
+        ("Spara", "Save"),
+        ("Avbryt", "Cancel"),
+        ("Rad ett", "Line one"),
+        ("rad två", "line two"),
+        (
+            "Hej %s, du har \"nya\" meddelanden",
+            "Hi %s, you have \"new\" messages",
+        ),
+        ("1 fil", "1 файл"),
+        ("2 filer", "2 файла"),
+        ("5 filer", "5 файлов"),
+        ("Ta bort", "Удалить"),
+        ("alla filer", "все файлы"),
+    ];
+
+    fn translate(source: &str, target: &str) -> (String, Vec<String>) {
+        translate_with(PHRASES, target, |translator| {
+            translate_gettext(translator, source)
+        })
+    }
+
+    #[test]
+    fn fills_empty_entries_and_marks_them_fuzzy() {
+        let source = r#"# Swedish catalog
+msgid ""
+msgstr ""
+"Project-Id-Version: app 1.0\n"
+"Content-Type: text/plain; charset=CHARSET\n"
+"Plural-Forms: nplurals=INTEGER; plural=EXPRESSION;\n"
+
+#. Menu item
+#: src/menu.c:10
+msgctxt "menu"
+msgid "Öppna fil"
+msgstr ""
+
+#: src/menu.c:12
+#, c-format
+msgid "Hej %s, du har \"nya\" meddelanden"
+msgstr ""
+
+msgid "Spara"
+msgstr "Save it"
+
+msgid ""
+"Rad ett\n"
+"rad två"
+msgstr ""
+
+  #, c-format
+msgid "Ta bort"
+msgstr ""
+
+#~ msgid "Spara"
+#~ msgstr ""
+"#;
+
+        let (output, texts) = translate(source, "en");
+
+        assert_eq!(
+            output,
+            r#"# Swedish catalog
+msgid ""
+msgstr ""
+"Project-Id-Version: app 1.0\n"
+"Content-Type: text/plain; charset=UTF-8\n"
+"Plural-Forms: nplurals=2; plural=(n != 1);\n"
+"Language: en\n"
+
+#. Menu item
+#: src/menu.c:10
+#, fuzzy
+msgctxt "menu"
+msgid "Öppna fil"
+msgstr "Open file"
+
+#: src/menu.c:12
+#, fuzzy, c-format
+msgid "Hej %s, du har \"nya\" meddelanden"
+msgstr "Hi %s, you have \"new\" messages"
+
+msgid "Spara"
+msgstr "Save it"
+
+#, fuzzy
+msgid ""
+"Rad ett\n"
+"rad två"
+msgstr ""
+"Line one\n"
+"line two"
+
+  #, fuzzy, c-format
+msgid "Ta bort"
+msgstr "Удалить"
+
+#~ msgid "Spara"
+#~ msgstr ""
+"#
+        );
+        assert_eq!(
+            texts,
+            [
+                "Öppna fil",
+                "Hej %s, du har \"nya\" meddelanden",
+                "Rad ett\nrad två",
+                "Ta bort"
+            ]
+        );
+    }
+
+    #[test]
+    fn writes_one_form_per_target_plural_form() {
+        let source = "msgid \"\"\nmsgstr \"Language: sv\\n\"\n\n#, c-format\nmsgid \"%d fil\"\nmsgid_plural \"%d filer\"\nmsgstr[0] \"\"\nmsgstr[1] \"\"\n\nmsgid \"Ta bort\"\nmsgid_plural \"Ta bort alla filer\"\nmsgstr[0] \"\"\nmsgstr[1] \"\"\n";
+
+        let (output, texts) = translate(source, "ru");
+
+        assert_eq!(
+            output,
+            "msgid \"\"\nmsgstr \"\"\n\"Language: ru\\n\"\n\"Plural-Forms: nplurals=3; plural=(n%10==1 && n%100!=11 ? 0 : n%10>=2 && n%10<=4 && (n%100<10 || n%100>=20) ? 1 : 2);\\n\"\n\n#, fuzzy, c-format\nmsgid \"%d fil\"\nmsgid_plural \"%d filer\"\nmsgstr[0] \"%d файл\"\nmsgstr[1] \"%d файла\"\nmsgstr[2] \"%d файлов\"\n\n#, fuzzy\nmsgid \"Ta bort\"\nmsgid_plural \"Ta bort alla filer\"\nmsgstr[0] \"Удалить\"\nmsgstr[1] \"Удалить все файлы\"\nmsgstr[2] \"Удалить все файлы\"\n"
+        );
+        assert_eq!(
+            texts,
+            [
+                "1 fil",
+                "2 filer",
+                "5 filer",
+                "Ta bort",
+                "Ta bort alla filer",
+                "Ta bort alla filer"
+            ]
+        );
+    }
+
+    #[test]
+    fn splits_entries_that_are_not_separated_by_blank_lines() {
+        let source = "msgid \"Spara\"\nmsgstr \"\"\nmsgid \"Avbryt\"\nmsgstr \"\"\n#: src/menu.c:10\nmsgctxt \"menu\"\nmsgid \"Öppna fil\"\nmsgstr \"\"\nmsgctxt \"knapp\"\nmsgid \"Spara\"\nmsgstr \"Save it\"\n";
+
+        let (output, texts) = translate(source, "en");
+
+        assert_eq!(
+            output,
+            "#, fuzzy\nmsgid \"Spara\"\nmsgstr \"Save\"\n#, fuzzy\nmsgid \"Avbryt\"\nmsgstr \"Cancel\"\n#: src/menu.c:10\n#, fuzzy\nmsgctxt \"menu\"\nmsgid \"Öppna fil\"\nmsgstr \"Open file\"\nmsgctxt \"knapp\"\nmsgid \"Spara\"\nmsgstr \"Save it\"\n"
+        );
+        assert_eq!(texts, ["Spara", "Avbryt", "Öppna fil"]);
+    }
+}
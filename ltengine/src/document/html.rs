@@ -0,0 +1,636 @@
+use std::ops::Range;
+
+use anyhow::Result;
+
+use super::bilingual::Bilingual;
+use super::segments::SegmentTranslator;
+use crate::languages::language_tag;
+use crate::translation::Inference;
+
+/// Elements that flow inside a sentence; any other tag ends the current text segment.
+const INLINE_ELEMENTS: &[&str] = &[
+    "a", "abbr", "b", "bdi", "bdo", "br", "cite", "code", "data", "del", "dfn", "em", "i", "img",
+    "ins", "kbd", "label", "mark", "q", "s", "samp", "small", "span", "strong", "sub", "sup",
+    "time", "u", "var", "wbr",
+];
+const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source",
+    "track", "wbr",
+];
+const SKIPPED_ELEMENTS: &[&str] = &["code", "script", "style"];
+const RAW_TEXT_ELEMENTS: &[&str] = &["script", "style"];
+const TRANSLATABLE_ATTRIBUTES: &[&str] = &["alt", "placeholder", "title"];
+/// Elements whose text cannot hold the markup of a bilingual pair.
+const TEXT_ONLY_ELEMENTS: &[&str] = &["head", "option", "textarea", "title"];
+
+#[derive(Debug)]
+enum Token {
+    Text(Range<usize>),
+    Tag(Tag),
+    /// Comments, doctypes, processing instructions, CDATA, and script or style bodies.
+    Other(Range<usize>),
+}
+
+#[derive(Debug)]
+struct Tag {
+    range: Range<usize>,
+    name: String,
+    name_end: usize,
+    closing: bool,
+    self_closing: bool,
+    attributes: Vec<Attribute>,
+}
+
+#[derive(Debug)]
+struct Attribute {
+    name: String,
+    value: Option<Range<usize>>,
+    quoted: bool,
+}
+
+struct OpenElement {
+    name: String,
+    skip: bool,
+}
+
+enum Piece {
+    Text(String),
+    Markup(String),
+}
+
+/// Translates text nodes and the `alt`, `title`, and `placeholder` attributes of an HTML page.
+///
+/// `<script>`, `<style>`, `<code>`, and `translate="no"` content is copied unchanged, and the
+/// `<html>` language is set to the target. Inline elements inside a block are sent to the model
+/// with the text around them using the `html` prompt format. With `bilingual`, each translated
+/// segment in the page body is paired with its source and a style for the layout is added.
+pub fn translate_html(
+    translator: &SegmentTranslator<'_, impl Inference>,
+    source: &str,
+    bilingual: Option<Bilingual>,
+) -> Result<String> {
+    let mut output = String::with_capacity(source.len());
+    let mut pieces = Vec::new();
+    let mut open: Vec<OpenElement> = Vec::new();
+    // Where the bilingual style goes: after the `<head>` start tag, or after `<html>` in a new
+    // head element.
+    let mut head_start = None;
+    let mut html_start = None;
+    let pairing = |open: &[OpenElement]| {
+        bilingual.filter(|_| {
+            !open
+                .iter()
+                .any(|element| TEXT_ONLY_ELEMENTS.contains(&element.name.as_str()))
+        })
+    };
+
+    for token in tokenize(source) {
+        let skipping = open.last().is_some_and(|element| element.skip);
+        let paired = pairing(&open);
+        match token {
+            Token::Text(range) if skipping => pieces.push(Piece::Markup(source[range].to_string())),
+            Token::Text(range) => pieces.push(Piece::Text(source[range].to_string())),
+            Token::Other(range) => pieces.push(Piece::Markup(source[range].to_string())),
+            Token::Tag(tag) => {
+                let skip = if tag.closing {
+                    skipping
+                } else {
+                    element_skips(source, &tag, skipping)
+                };
+                let rendered = render_tag(translator, source, &tag, skip)?;
+                if INLINE_ELEMENTS.contains(&tag.name.as_str()) {
+                    pieces.push(Piece::Markup(rendered));
+                } else {
+                    flush(translator, &mut pieces, paired, &mut output)?;
+                    output.push_str(&rendered);
+                }
+                if !tag.closing {
+                    match tag.name.as_str() {
+                        "head" => head_start = head_start.or(Some(output.len())),
+                        "html" => html_start = html_start.or(Some(output.len())),
+                        _ => {}
+                    }
+                }
+
+                if tag.closing {
+                    if let Some(index) = open.iter().rposition(|element| element.name == tag.name) {
+                        open.truncate(index);
+                    }
+                } else if !tag.self_closing && !VOID_ELEMENTS.contains(&tag.name.as_str()) {
+                    open.push(OpenElement {
+                        name: tag.name,
+                        skip,
+                    });
+                }
+            }
+        }
+    }
+    flush(translator, &mut pieces, pairing(&open), &mut output)?;
+    if let Some(bilingual) = bilingual {
+        let style = bilingual.html_style();
+        match (head_start, html_start) {
+            (Some(position), _) => output.insert_str(position, &style),
+            (None, Some(position)) => output.insert_str(position, &format!("<head>{style}</head>")),
+            (None, None) => {
+                // A page without `<html>` may still start with a doctype, which must stay first.
+                let content = output.len() - output.trim_start().len();
+                let position = if output[content..]
+                    .to_ascii_lowercase()
+                    .starts_with("<!doctype")
+                {
+                    output[content..]
+                        .find('>')
+                        .map_or(0, |end| content + end + 1)
+                } else {
+                    0
+                };
+                output.insert_str(position, &style);
+            }
+        }
+    }
+    Ok(output)
+}
+
+fn element_skips(source: &str, tag: &Tag, parent_skips: bool) -> bool {
+    if SKIPPED_ELEMENTS.contains(&tag.name.as_str()) {
+        return true;
+    }
+    match attribute_value(source, tag, "translate").map(str::to_ascii_lowercase) {
+        Some(value) if value == "no" => true,
+        Some(value) if value == "yes" || value.is_empty() => false,
+        _ => parent_skips,
+    }
+}
+
+fn attribute_value<'a>(source: &'a str, tag: &Tag, name: &str) -> Option<&'a str> {
+    tag.attributes
+        .iter()
+        .find(|attribute| attribute.name == name)
+        .map(|attribute| attribute.value.clone().map_or("", |value| &source[value]))
+}
+
+fn render_tag(
+    translator: &SegmentTranslator<'_, impl Inference>,
+    source: &str,
+    tag: &Tag,
+    skip: bool,
+) -> Result<String> {
+    let mut replacements: Vec<(Range<usize>, String)> = Vec::new();
+    if !tag.closing && tag.name == "html" {
+        let language = language_tag(translator.target());
+        let mut has_lang = false;
+        for attribute in &tag.attributes {
+            if let ("lang" | "xml:lang", Some(value)) = (attribute.name.as_str(), &attribute.value)
+            {
+                replacements.push((value.clone(), language.to_string()));
+                has_lang |= attribute.name == "lang";
+            }
+        }
+        if !has_lang {
+            replacements.push((tag.name_end..tag.name_end, format!(" lang=\"{language}\"")));
+        }
+    }
+    if !tag.closing && !skip {
+        for attribute in &tag.attributes {
+            let Some(value) = &attribute.value else {
+                continue;
+            };
+            if TRANSLATABLE_ATTRIBUTES.contains(&attribute.name.as_str()) {
+                let translated =
+                    translator.translate(&decode_entities(&source[value.clone()]), "text")?;
+                let escaped = escape_attribute(&translated);
+                replacements.push(if attribute.quoted {
+                    (value.clone(), escaped)
+                } else {
+                    (value.clone(), format!("\"{escaped}\""))
+                });
+            }
+        }
+    }
+    replacements.sort_by_key(|(range, _)| range.start);
+
+    let mut rendered = String::new();
+    let mut position = tag.range.start;
+    for (range, replacement) in replacements {
+        rendered.push_str(&source[position..range.start]);
+        rendered.push_str(&replacement);
+        position = range.end;
+    }
+    rendered.push_str(&source[position..tag.range.end]);
+    Ok(rendered)
+}
+
+/// Translates the text collected since the last block boundary and writes it with its markup,
+/// paired with the source when `bilingual` is set.
+fn flush(
+    translator: &SegmentTranslator<'_, impl Inference>,
+    pieces: &mut Vec<Piece>,
+    bilingual: Option<Bilingual>,
+    output: &mut String,
+) -> Result<()> {
+    let is_text = |piece: &Piece| matches!(piece, Piece::Text(text) if !text.trim().is_empty());
+    let (Some(first_text), Some(last_text)) = (
+        pieces.iter().position(is_text),
+        pieces.iter().rposition(is_text),
+    ) else {
+        write_pieces(pieces.drain(..), output);
+        return Ok(());
+    };
+    let mut trailing = String::new();
+    write_pieces(pieces.split_off(last_text + 1).into_iter(), &mut trailing);
+    let segment = pieces.split_off(first_text);
+    let mut leading = String::new();
+    write_pieces(pieces.drain(..), &mut leading);
+
+    let mut runs = vec![String::new()];
+    let mut markups: Vec<String> = Vec::new();
+    for piece in segment {
+        match piece {
+            Piece::Text(text) => runs.last_mut().expect("runs are not empty").push_str(&text),
+            Piece::Markup(markup) => match markups.last_mut() {
+                Some(previous) if runs.last().is_some_and(String::is_empty) => {
+                    previous.push_str(&markup);
+                }
+                _ => {
+                    markups.push(markup);
+                    runs.push(String::new());
+                }
+            },
+        }
+    }
+    let translated = translator.translate_marked(&runs, &markups, "html")?;
+    let mut target = leading.clone();
+    for (index, run) in translated.iter().enumerate() {
+        if index > 0 {
+            target.push_str(&markups[index - 1]);
+        }
+        target.push_str(&escape_text(run));
+    }
+    target.push_str(&trailing);
+    match bilingual {
+        // Both sides get the inline markup around the text so each one is balanced.
+        Some(bilingual) => {
+            let mut source = leading + &runs[0];
+            for (markup, run) in markups.iter().zip(&runs[1..]) {
+                source.push_str(markup);
+                source.push_str(run);
+            }
+            source.push_str(&trailing);
+            let languages = (translator.source(), translator.target());
+            bilingual.push_html(&source, &target, languages, output);
+        }
+        None => output.push_str(&target),
+    }
+    Ok(())
+}
+
+fn write_pieces(pieces: impl Iterator<Item = Piece>, output: &mut String) {
+    for piece in pieces {
+        let (Piece::Text(text) | Piece::Markup(text)) = piece;
+        output.push_str(&text);
+    }
+}
+
+fn tokenize(source: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut position = 0;
+    let mut text_start = 0;
+    while let Some(found) = source[position..].find('<') {
+        let start = position + found;
+        let Some((token, end)) = markup_at(source, start) else {
+            position = start + 1;
+            continue;
+        };
+        if text_start < start {
+            tokens.push(Token::Text(text_start..start));
+        }
+
+        let raw_text = match &token {
+            Token::Tag(tag) if !tag.closing && RAW_TEXT_ELEMENTS.contains(&tag.name.as_str()) => {
+                Some(tag.name.clone())
+            }
+            _ => None,
+        };
+        tokens.push(token);
+        position = end;
+        if let Some(name) = raw_text {
+            let body_end = find_ignore_case(&source[end..], &format!("</{name}"))
+                .map_or(source.len(), |found| end + found);
+            if end < body_end {
+                tokens.push(Token::Other(end..body_end));
+            }
+            position = body_end;
+        }
+        text_start = position;
+    }
+    if text_start < source.len() {
+        tokens.push(Token::Text(text_start..source.len()));
+    }
+    tokens
+}
+
+fn markup_at(source: &str, start: usize) -> Option<(Token, usize)> {
+    let rest = &source[start..];
+    for (open, close) in [
+        ("<!--", "-->"),
+        ("<![CDATA[", "]]>"),
+        ("<!", ">"),
+        ("<?", ">"),
+    ] {
+        if let Some(body) = rest.strip_prefix(open) {
+            let end = body.find(close).map_or(source.len(), |found| {
+                start + open.len() + found + close.len()
+            });
+            return Some((Token::Other(start..end), end));
+        }
+    }
+
+    let closing = rest.starts_with("</");
+    let name_start = start + if closing { 2 } else { 1 };
+    if !source[name_start..].starts_with(|c: char| c.is_ascii_alphabetic()) {
+        return None;
+    }
+    let name_end = source[name_start..]
+        .find(|c: char| c.is_whitespace() || c == '/' || c == '>')
+        .map_or(source.len(), |found| name_start + found);
+
+    let bytes = source.as_bytes();
+    let mut attributes = Vec::new();
+    let mut position = name_end;
+    let mut self_closing = false;
+    loop {
+        while position < bytes.len()
+            && (bytes[position].is_ascii_whitespace() || bytes[position] == b'/')
+        {
+            self_closing = bytes[position] == b'/';
+            position += 1;
+        }
+        if position >= bytes.len() {
+            return None;
+        }
+        if bytes[position] == b'>' {
+            position += 1;
+            break;
+        }
+        self_closing = false;
+
+        let attribute_start = position;
+        while position < bytes.len() && !b" \t\r\n/>=".contains(&bytes[position]) {
+            position += 1;
+        }
+        let name = source[attribute_start..position].to_ascii_lowercase();
+        while position < bytes.len() && bytes[position].is_ascii_whitespace() {
+            position += 1;
+        }
+        let mut value = None;
+        let mut quoted = false;
+        if bytes.get(position) == Some(&b'=') {
+            position += 1;
+            while position < bytes.len() && bytes[position].is_ascii_whitespace() {
+                position += 1;
+            }
+            match bytes.get(position) {
+                Some(&quote) if quote == b'"' || quote == b'\'' => {
+                    let value_end = source[position + 1..].find(quote as char)? + position + 1;
+                    value = Some(position + 1..value_end);
+                    quoted = true;
+                    position = value_end + 1;
+                }
+                _ => {
+                    let value_start = position;
+                    while position < bytes.len()
+                        && !bytes[position].is_ascii_whitespace()
+                        && bytes[position] != b'>'
+                    {
+                        position += 1;
+                    }
+                    value = Some(value_start..position);
+                }
+            }
+        }
+        attributes.push(Attribute {
+            name,
+            value,
+            quoted,
+        });
+    }
+
+    let tag = Tag {
+        range: start..position,
+        name: source[name_start..name_end].to_ascii_lowercase(),
+        name_end,
+        closing,
+        self_closing,
+        attributes,
+    };
+    Some((Token::Tag(tag), position))
+}
+
+fn find_ignore_case(haystack: &str, needle: &str) -> Option<usize> {
+    haystack
+        .as_bytes()
+        .windows(needle.len())
+        .position(|window| window.eq_ignore_ascii_case(needle.as_bytes()))
+}
+
+pub(super) fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let entity = rest[1..]
+            .find(';')
+            .filter(|&end| end <= 10)
+            .map(|end| &rest[1..=end]);
+        let character = entity.and_then(|entity| match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "nbsp" => Some('\u{a0}'),
+            _ => entity
+                .strip_prefix("#x")
+                .or_else(|| entity.strip_prefix("#X"))
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                .or_else(|| {
+                    entity
+                        .strip_prefix('#')
+                        .and_then(|decimal| decimal.parse().ok())
+                })
+                .and_then(char::from_u32),
+        });
+        match (entity, character) {
+            (Some(entity), Some(character)) => {
+                decoded.push(character);
+                rest = &rest[entity.len() + 2..];
+            }
+            _ => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+/// Escapes characters the model may emit that would change the document structure.
+pub(super) fn escape_text(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for (index, character) in text.char_indices() {
+        match character {
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '&' if !starts_with_entity(&text[index..]) => escaped.push_str("&amp;"),
+            character => escaped.push(character),
+        }
+    }
+    escaped
+}
+
+fn starts_with_entity(text: &str) -> bool {
+    text[1..].find(';').is_some_and(|end| {
+        end > 0
+            && end <= 10
+            && text[1..=end]
+                .trim_start_matches('#')
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric())
+    })
+}
+
+fn escape_attribute(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+        .replace('<', "&lt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Bilingual, translate_html};
+    use crate::document::segments::SegmentTranslator;
+    use crate::document::testing::{FakeInference, translate_with};
+
+    const PHRASES: &[(&str, &str)] = &[
+        ("Rubrik", "Heading"),
+        ("Hej", "Hello"),
+        ("världen", "world"),
+        ("Kör", "Run"),
+        ("nu", "now"),
+        ("En katt", "A cat"),
+        ("Sök", "Search"),
+        ("Ost & bröd", "Cheese & bread"),
+        ("Bild", "Image"),
+    ];
+
+    fn translate(source: &str) -> (String, Vec<String>) {
+        translate_with(PHRASES, "en", |translator| {
+            translate_html(translator, source, None)
+        })
+    }
+
+    #[test]
+    fn translates_blocks_with_inline_markup_and_sets_language() {
+        let (output, texts) = translate(
+            "<!DOCTYPE html>\n<html lang=\"sv\"><head><title>Rubrik</title></head>\n<body><p>Hej <b class=\"x\">världen</b>!</p></body></html>\n",
+        );
+
+        assert_eq!(
+            output,
+            "<!DOCTYPE html>\n<html lang=\"en\"><head><title>Heading</title></head>\n<body><p>Hello <b class=\"x\">world</b>!</p></body></html>\n"
+        );
+        assert_eq!(texts, ["Rubrik", "Hej <b class=\"x\">världen</b>!"]);
+    }
+
+    #[test]
+    fn skips_script_style_code_and_translate_no() {
+        let source = "<html><style>p{content:'Hej'}</style><script>if (a<b) { alert('Hej'); }</script>\n<p>Kör <code>ls</code> nu</p><div translate=\"no\">Hej <span translate=\"yes\">världen</span></div></html>";
+
+        let (output, texts) = translate(source);
+
+        assert_eq!(
+            output,
+            "<html lang=\"en\"><style>p{content:'Hej'}</style><script>if (a<b) { alert('Hej'); }</script>\n<p>Run <code>ls</code> now</p><div translate=\"no\">Hej <span translate=\"yes\">world</span></div></html>"
+        );
+        assert_eq!(texts, ["Kör <code>ls</code> nu", "världen"]);
+    }
+
+    #[test]
+    fn translates_attributes_and_escapes_output() {
+        let source = "<p><img src=\"katt.png\" alt='En katt'><input placeholder=Sök title=\"Ost &amp; bröd\" value=\"Hej\"/></p>\n<p>Bild &amp; text</p>";
+
+        let (output, _) = translate(source);
+
+        assert_eq!(
+            output,
+            "<p><img src=\"katt.png\" alt='A cat'><input placeholder=\"Search\" title=\"Cheese &amp; bread\" value=\"Hej\"/></p>\n<p>Image &amp; text</p>"
+        );
+    }
+
+    #[test]
+    fn translates_text_runs_when_the_model_breaks_markup() {
+        let inference = FakeInference::new(|text| {
+            Ok(text
+                .replace("<i>", "")
+                .replace("Hej", "Hello")
+                .replace("du", "you")
+                .replace("&lt;", "<"))
+        });
+        let translator = SegmentTranslator::new(&inference, "sv", "en", 1024);
+
+        let output = translate_html(&translator, "<p>Hej <i>du</i> x &lt; y</p>", None)
+            .expect("translation should succeed");
+
+        assert_eq!(output, "<p>Hello <i>you</i> x &lt; y</p>");
+        assert_eq!(
+            *inference.texts.borrow(),
+            ["Hej <i>du</i> x &lt; y", "Hej", "du", "x &lt; y"]
+        );
+    }
+
+    #[test]
+    fn pairs_body_segments_with_their_source_for_bilingual_review() {
+        let inference = FakeInference::replacing(PHRASES);
+        let translator = SegmentTranslator::new(&inference, "sv", "en", 1024);
+
+        let output = translate_html(
+            &translator,
+            "<html><head><title>Rubrik</title></head><body><p>Hej <b>världen</b></p></body></html>",
+            Some(Bilingual::Columns),
+        )
+        .expect("translation should succeed");
+
+        assert_eq!(
+            output,
+            "<html lang=\"en\"><head><style>.ltengine-pair{display:grid;grid-template-columns:1fr 1fr;column-gap:2em}.ltengine-source{color:#555}</style><title>Heading</title></head><body><p><span class=\"ltengine-pair\"><span class=\"ltengine-source\" lang=\"sv\">Hej <b>världen</b></span><span class=\"ltengine-translation\" lang=\"en\">Hello <b>world</b></span></span></p></body></html>"
+        );
+    }
+
+    #[test]
+    fn keeps_the_doctype_first_when_a_page_omits_its_head_end_tag() {
+        let inference = FakeInference::replacing(PHRASES);
+        let translator = SegmentTranslator::new(&inference, "sv", "en", 1024);
+        let style = Bilingual::Interleaved.html_style();
+        let translate = |page| {
+            translate_html(&translator, page, Some(Bilingual::Interleaved))
+                .expect("translation should succeed")
+        };
+
+        let output = translate("<!DOCTYPE html>\n<html><head><title>Rubrik</title>\n<p>Hej</p>");
+        assert!(output.starts_with(&format!(
+            "<!DOCTYPE html>\n<html lang=\"en\"><head>{style}<title>Heading</title>"
+        )));
+        let output = translate("<!DOCTYPE html>\n<html><p>Hej</p></html>");
+        assert!(output.starts_with(&format!(
+            "<!DOCTYPE html>\n<html lang=\"en\"><head>{style}</head><p>"
+        )));
+        let output = translate("<!doctype html><p>Hej</p>");
+        assert!(output.starts_with(&format!("<!doctype html>{style}<p>")));
+        assert!(translate("<p>Hej</p>").starts_with(&style));
+        let output = translate("<html><head><title>Rubrik");
+        assert!(output.ends_with("<title>Heading"));
+    }
+}
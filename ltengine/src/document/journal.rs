@@ -0,0 +1,231 @@
+use std::cell::{Cell, RefCell};
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, bail};
+
+const HEADER: &str = "ltengine-journal 1";
+
+/// Completed segment translations of one document, kept next to its output until the output
+/// is written so an interrupted run can resume without translating them again.
+///
+/// After the header, each entry is a line with the byte lengths of the source segment and its
+/// translation, followed by both texts and a newline. An entry cut short by a crash is ignored.
+pub struct Journal {
+    path: PathBuf,
+    header: String,
+    entries: Vec<(String, String)>,
+    replayed: Cell<usize>,
+    /// Length of the resumed journal up to its last complete entry.
+    kept_length: u64,
+    file: RefCell<Option<File>>,
+}
+
+impl Journal {
+    /// Opens the journal for `output`, loading its entries when `resume` is set.
+    ///
+    /// `settings` describes the options that decide how the document is segmented and
+    /// translated; the journal is rejected when they or the input bytes have changed.
+    pub fn open(output: &Path, input: &[u8], settings: &str, resume: bool) -> Result<Self> {
+        let mut name = OsString::from(output.file_name().unwrap_or_default());
+        name.push(".ltengine-journal");
+        let path = output.with_file_name(name);
+        let header = format!(
+            "{HEADER}\ninput {:016x} {}\nsettings {:016x}\n",
+            fnv1a(input),
+            input.len(),
+            fnv1a(settings.as_bytes())
+        );
+        let mut journal = Self {
+            path,
+            header,
+            entries: Vec::new(),
+            replayed: Cell::new(0),
+            kept_length: 0,
+            file: RefCell::new(None),
+        };
+        if !journal.path.exists() {
+            return Ok(journal);
+        }
+        if !resume {
+            bail!(
+                "An interrupted translation left a journal at {}; pass --resume to continue it or delete it to start over",
+                journal.path.display()
+            );
+        }
+
+        let contents = fs::read(&journal.path)
+            .with_context(|| format!("Failed to read journal {}", journal.path.display()))?;
+        let Some(mut rest) = contents.strip_prefix(journal.header.as_bytes()) else {
+            let mut lines = contents.split(|&byte| byte == b'\n');
+            let mut expected = journal.header.lines().map(str::as_bytes);
+            let problem = if lines.next() != expected.next() {
+                "is not a translation journal"
+            } else if lines.next() != expected.next() {
+                "was written for a different input document"
+            } else {
+                "was written with different translation options"
+            };
+            bail!(
+                "Journal {} {problem}; delete it to start over",
+                journal.path.display()
+            );
+        };
+        while let Some((source, translation, after)) = read_entry(rest) {
+            journal.entries.push((source, translation));
+            rest = after;
+        }
+        journal.kept_length = (contents.len() - rest.len()) as u64;
+        Ok(journal)
+    }
+
+    /// Returns the journaled translation of the next segment, or translates it with `run` and
+    /// records the result.
+    pub fn translate(&self, source: &str, run: impl FnOnce() -> Result<String>) -> Result<String> {
+        let index = self.replayed.get();
+        if let Some((journaled, translation)) = self.entries.get(index) {
+            if journaled != source {
+                bail!(
+                    "Journal {} does not match the document; delete it to start over",
+                    self.path.display()
+                );
+            }
+            self.replayed.set(index + 1);
+            return Ok(translation.clone());
+        }
+
+        let translation = run()?;
+        self.append(source, &translation)
+            .with_context(|| format!("Failed to write journal {}", self.path.display()))?;
+        Ok(translation)
+    }
+
+    fn append(&self, source: &str, translation: &str) -> Result<()> {
+        let mut file = self.file.borrow_mut();
+        let file = match &mut *file {
+            Some(file) => file,
+            empty => {
+                let opened = if self.kept_length > 0 {
+                    let mut opened = OpenOptions::new().write(true).open(&self.path)?;
+                    opened.set_len(self.kept_length)?;
+                    opened.seek(SeekFrom::End(0))?;
+                    opened
+                } else {
+                    if let Some(directory) = self.path.parent() {
+                        fs::create_dir_all(directory)?;
+                    }
+                    let mut created = OpenOptions::new()
+                        .write(true)
+                        .create_new(true)
+                        .open(&self.path)?;
+                    created.write_all(self.header.as_bytes())?;
+                    created
+                };
+                empty.insert(opened)
+            }
+        };
+        write!(
+            file,
+            "{} {}\n{source}{translation}\n",
+            source.len(),
+            translation.len()
+        )?;
+        file.sync_data()?;
+        Ok(())
+    }
+
+    /// Removes the journal once the output document is written.
+    pub fn finish(self) -> Result<()> {
+        drop(self.file.into_inner());
+        match fs::remove_file(&self.path) {
+            Err(error) if error.kind() != std::io::ErrorKind::NotFound => {
+                Err(error).with_context(|| {
+                    format!(
+                        "Translated document was written but its journal {} could not be removed",
+                        self.path.display()
+                    )
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Reads one complete entry, returning its texts and the bytes after it.
+fn read_entry(bytes: &[u8]) -> Option<(String, String, &[u8])> {
+    let newline = bytes.iter().position(|&byte| byte == b'\n')?;
+    let lengths = std::str::from_utf8(&bytes[..newline]).ok()?;
+    let (source_length, translation_length) = lengths.split_once(' ')?;
+    let source_length: usize = source_length.parse().ok()?;
+    let translation_length: usize = translation_length.parse().ok()?;
+    let texts = &bytes[newline + 1..];
+    let end = source_length.checked_add(translation_length)?;
+    if texts.get(end) != Some(&b'\n') {
+        return None;
+    }
+    let source = String::from_utf8(texts[..source_length].to_vec()).ok()?;
+    let translation = String::from_utf8(texts[source_length..end].to_vec()).ok()?;
+    Some((source, translation, &texts[end + 1..]))
+}
+
+/// 64-bit FNV-1a, which stays the same across builds unlike the standard library hasher.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::Journal;
+
+    #[test]
+    fn drops_a_torn_entry_and_rejects_changed_input() {
+        let name = format!("ltengine-journal-{}.txt", std::process::id());
+        let output = std::env::temp_dir().join(&name);
+        let path = std::env::temp_dir().join(name + ".ltengine-journal");
+        let journal = Journal::open(&output, b"Ett. Tv\xc3\xa5.", "sv-en", false).unwrap();
+        journal
+            .translate("Ett.", || Ok("One.".to_string()))
+            .unwrap();
+        journal
+            .translate("Två.", || Ok("Two.".to_string()))
+            .unwrap();
+        drop(journal);
+        let mut contents = fs::read(&path).unwrap();
+        contents.truncate(contents.len() - 3);
+        fs::write(&path, &contents).unwrap();
+
+        let resumed = Journal::open(&output, b"Ett. Tv\xc3\xa5.", "sv-en", true).unwrap();
+        assert_eq!(
+            resumed.translate("Ett.", || unreachable!()).unwrap(),
+            "One."
+        );
+        assert_eq!(
+            resumed
+                .translate("Två.", || Ok("Two!".to_string()))
+                .unwrap(),
+            "Two!"
+        );
+        drop(resumed);
+        assert!(
+            fs::read_to_string(&path)
+                .unwrap()
+                .ends_with("4 4\nEtt.One.\n5 4\nTvå.Two!\n")
+        );
+
+        let error = Journal::open(&output, b"Ett. Tre.", "sv-en", true)
+            .err()
+            .unwrap();
+        assert!(error.to_string().contains("different input document"));
+        let error = Journal::open(&output, b"Ett. Tv\xc3\xa5.", "sv-de", true)
+            .err()
+            .unwrap();
+        assert!(error.to_string().contains("different translation options"));
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,453 @@
+use std::collections::HashMap;
+use std::ops::Range;
+
+use anyhow::{Result, bail};
+
+use super::placeholders::interpolations;
+use super::segments::SegmentTranslator;
+use crate::translation::Inference;
+
+/// Nesting limit that keeps the recursive parser off the end of the stack.
+const MAX_DEPTH: usize = 256;
+
+/// JSON pointers (RFC 6901) choosing which values of a structured document are translated.
+///
+/// A pointer selects the value it names and everything below it. With no includes every value
+/// is selected; excludes win over includes.
+#[derive(Clone, Copy)]
+pub struct PointerFilter<'a> {
+    include: &'a [String],
+    exclude: &'a [String],
+}
+
+impl<'a> PointerFilter<'a> {
+    pub fn new(include: &'a [String], exclude: &'a [String]) -> Result<Self> {
+        for pointer in include.iter().chain(exclude) {
+            if !pointer.is_empty() && !pointer.starts_with('/') {
+                bail!("JSON pointer must be empty or start with '/': {pointer}");
+            }
+        }
+        Ok(Self { include, exclude })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+
+    pub fn selects(&self, pointer: &str) -> bool {
+        let under = |prefix: &String| {
+            pointer
+                .strip_prefix(prefix.as_str())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+        };
+        (self.include.is_empty() || self.include.iter().any(under))
+            && !self.exclude.iter().any(under)
+    }
+}
+
+/// Appends one reference token to a JSON pointer, escaping `~` and `/`.
+pub fn push_pointer(pointer: &str, token: &str) -> String {
+    format!("{pointer}/{}", token.replace('~', "~0").replace('/', "~1"))
+}
+
+struct JsonString {
+    range: Range<usize>,
+    pointer: String,
+    value: String,
+}
+
+struct Parser<'a> {
+    source: &'a str,
+    position: usize,
+    strings: Vec<JsonString>,
+}
+
+/// Translates the string values of a JSON resource file.
+///
+/// Keys, numbers, booleans, nulls, key order, and whitespace are copied byte for byte.
+/// Interpolation placeholders such as `{{name}}`, `{count}`, and `$t(key)` are shown to the
+/// model verbatim and must come back unchanged. Repeated values are translated once.
+pub fn translate_json(
+    translator: &SegmentTranslator<'_, impl Inference>,
+    source: &str,
+    filter: PointerFilter<'_>,
+) -> Result<String> {
+    let mut parser = Parser {
+        source,
+        position: 0,
+        strings: Vec::new(),
+    };
+    parser.skip_whitespace();
+    parser.value(String::new(), 0)?;
+    parser.skip_whitespace();
+    if parser.position < source.len() {
+        bail!(
+            "Invalid JSON: unexpected content after the value at {}",
+            parser.location()
+        );
+    }
+
+    let mut translations: HashMap<String, String> = HashMap::new();
+    let mut output = String::with_capacity(source.len());
+    let mut position = 0;
+    for string in parser.strings {
+        if !filter.selects(&string.pointer) || string.value.trim().is_empty() {
+            continue;
+        }
+        let translated = match translations.get(&string.value) {
+            Some(translated) => translated.clone(),
+            None => {
+                let translated = translator.translate_protected(
+                    &string.value,
+                    &interpolations(&string.value),
+                    "text",
+                )?;
+                translations.insert(string.value, translated.clone());
+                translated
+            }
+        };
+        output.push_str(&source[position..string.range.start]);
+        output.push_str(&escape_string(&translated));
+        position = string.range.end;
+    }
+    output.push_str(&source[position..]);
+    Ok(output)
+}
+
+impl Parser<'_> {
+    fn value(&mut self, pointer: String, depth: usize) -> Result<()> {
+        if depth > MAX_DEPTH {
+            bail!("Invalid JSON: nesting deeper than {MAX_DEPTH} levels");
+        }
+        match self.peek() {
+            Some(b'{') => {
+                self.position += 1;
+                self.skip_whitespace();
+                if self.eat(b'}') {
+                    return Ok(());
+                }
+                loop {
+                    if self.peek() != Some(b'"') {
+                        bail!(
+                            "Invalid JSON: expected an object key at {}",
+                            self.location()
+                        );
+                    }
+                    let key = self.string()?;
+                    self.skip_whitespace();
+                    self.expect(b':')?;
+                    self.skip_whitespace();
+                    self.value(push_pointer(&pointer, &key), depth + 1)?;
+                    self.skip_whitespace();
+                    if self.eat(b'}') {
+                        return Ok(());
+                    }
+                    self.expect(b',')?;
+                    self.skip_whitespace();
+                }
+            }
+            Some(b'[') => {
+                self.position += 1;
+                self.skip_whitespace();
+                if self.eat(b']') {
+                    return Ok(());
+                }
+                for index in 0.. {
+                    self.value(push_pointer(&pointer, &index.to_string()), depth + 1)?;
+                    self.skip_whitespace();
+                    if self.eat(b']') {
+                        break;
+                    }
+                    self.expect(b',')?;
+                    self.skip_whitespace();
+                }
+                Ok(())
+            }
+            Some(b'"') => {
+                let start = self.position;
+                let value = self.string()?;
+                self.strings.push(JsonString {
+                    range: start..self.position,
+                    pointer,
+                    value,
+                });
+                Ok(())
+            }
+            _ => {
+                let rest = &self.source[self.position..];
+                let length = rest
+                    .find(|character: char| {
+                        !(character.is_ascii_alphanumeric() || "+-.".contains(character))
+                    })
+                    .unwrap_or(rest.len());
+                let literal = &rest[..length];
+                if !is_number(literal) && !["true", "false", "null"].contains(&literal) {
+                    bail!("Invalid JSON: unexpected value at {}", self.location());
+                }
+                self.position += length;
+                Ok(())
+            }
+        }
+    }
+
+    /// Reads a string starting at its opening quote and returns its unescaped value.
+    fn string(&mut self) -> Result<String> {
+        let start = self.position;
+        self.position += 1;
+        let mut value = String::new();
+        loop {
+            let rest = &self.source[self.position..];
+            let Some(length) = rest.find(['"', '\\']) else {
+                self.position = start;
+                bail!("Invalid JSON: unterminated string at {}", self.location());
+            };
+            if rest[..length].contains(|character: char| character < ' ') {
+                bail!(
+                    "Invalid JSON: control character in string at {}",
+                    self.location()
+                );
+            }
+            value.push_str(&rest[..length]);
+            self.position += length + 1;
+            if rest.as_bytes()[length] == b'"' {
+                return Ok(value);
+            }
+            let escaped = match self.peek() {
+                Some(b'"') => '"',
+                Some(b'\\') => '\\',
+                Some(b'/') => '/',
+                Some(b'b') => '\u{8}',
+                Some(b'f') => '\u{c}',
+                Some(b'n') => '\n',
+                Some(b'r') => '\r',
+                Some(b't') => '\t',
+                Some(b'u') => {
+                    self.position += 1;
+                    let high = self.hex()?;
+                    let code = if (0xd800..0xdc00).contains(&high)
+                        && self.source[self.position..].starts_with("\\u")
+                    {
+                        self.position += 2;
+                        let low = self.hex()?;
+                        0x10000 + ((high - 0xd800) << 10) + low.wrapping_sub(0xdc00)
+                    } else {
+                        high
+                    };
+                    value.push(char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER));
+                    continue;
+                }
+                _ => bail!("Invalid JSON: invalid escape at {}", self.location()),
+            };
+            value.push(escaped);
+            self.position += 1;
+        }
+    }
+
+    fn hex(&mut self) -> Result<u32> {
+        let digits = self.source.get(self.position..self.position + 4);
+        match digits.and_then(|digits| u32::from_str_radix(digits, 16).ok()) {
+            Some(code) => {
+                self.position += 4;
+                Ok(code)
+            }
+            None => bail!("Invalid JSON: invalid \\u escape at {}", self.location()),
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.source.as_bytes().get(self.position).copied()
+    }
+
+    fn eat(&mut self, byte: u8) -> bool {
+        let found = self.peek() == Some(byte);
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<()> {
+        if !self.eat(byte) {
+            bail!(
+                "Invalid JSON: expected '{}' at {}",
+                byte as char,
+                self.location()
+            );
+        }
+        Ok(())
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t' | b'\n' | b'\r')) {
+            self.position += 1;
+        }
+    }
+
+    fn location(&self) -> String {
+        let before = &self.source[..self.position];
+        let line = before.matches('\n').count() + 1;
+        let column = before.len() - before.rfind('\n').map_or(0, |index| index + 1) + 1;
+        format!("line {line}, column {column}")
+    }
+}
+
+/// Checks the JSON number grammar: `-?(0|[1-9][0-9]*)(.[0-9]+)?([eE][+-]?[0-9]+)?`.
+fn is_number(literal: &str) -> bool {
+    let digits =
+        |text: &str| text.len() - text.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    let rest = literal.strip_prefix('-').unwrap_or(literal);
+    let integer = digits(rest);
+    if integer == 0 || (integer > 1 && rest.starts_with('0')) {
+        return false;
+    }
+    let mut rest = &rest[integer..];
+    if let Some(fraction) = rest.strip_prefix('.') {
+        let length = digits(fraction);
+        if length == 0 {
+            return false;
+        }
+        rest = &fraction[length..];
+    }
+    if let Some(exponent) = rest.strip_prefix(['e', 'E']) {
+        let exponent = exponent.strip_prefix(['+', '-']).unwrap_or(exponent);
+        let length = digits(exponent);
+        if length == 0 {
+            return false;
+        }
+        rest = &exponent[length..];
+    }
+    rest.is_empty()
+}
+
+fn escape_string(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len() + 2);
+    escaped.push('"');
+    for character in value.chars() {
+        match character {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            character if character < ' ' => {
+                escaped.push_str(&format!("\\u{:04x}", character as u32));
+            }
+            character => escaped.push(character),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PointerFilter, translate_json};
+    use crate::document::segments::SegmentTranslator;
+    use crate::document::testing::{FakeInference, translate_with};
+
+    const PHRASES: &[(&str, &str)] = &[
+        ("Hej {{name}}", "Hello {{name}}"),
+        (
+            "Du har {count} nya meddelanden",
+            "You have {count} new messages",
+        ),
+        ("Spara", "Save"),
+        ("Avbryt", "Cancel"),
+        ("Säg \"hej\"", "Say \"hello\""),
+        ("rad ett\nrad två", "line one\nline two"),
+        ("fil i", "file in"),
+        ("filer i", "files in"),
+    ];
+
+    fn translate(source: &str, include: &[String], exclude: &[String]) -> (String, Vec<String>) {
+        let filter = PointerFilter::new(include, exclude).expect("pointers should be valid");
+        translate_with(PHRASES, "en", |translator| {
+            translate_json(translator, source, filter)
+        })
+    }
+
+    #[test]
+    fn translates_string_values_and_keeps_everything_else() {
+        let source = r#"{
+  "greeting": "Hej {{name}}",
+  "inbox": { "unread": "Du har {count} nya meddelanden", "limit": 50, "enabled": true },
+  "buttons": ["Spara", "Avbryt", null, -1.5e3],
+  "Spara": "Spara",
+  "quote": "Säg \"hej\"",
+  "lines": "rad ett\nrad två",
+  "empty": ""
+}
+"#;
+
+        let (output, texts) = translate(source, &[], &[]);
+
+        assert_eq!(
+            output,
+            r#"{
+  "greeting": "Hello {{name}}",
+  "inbox": { "unread": "You have {count} new messages", "limit": 50, "enabled": true },
+  "buttons": ["Save", "Cancel", null, -1.5e3],
+  "Spara": "Save",
+  "quote": "Say \"hello\"",
+  "lines": "line one\nline two",
+  "empty": ""
+}
+"#
+        );
+        assert_eq!(
+            texts,
+            [
+                "Hej {{name}}",
+                "Du har {count} nya meddelanden",
+                "Spara",
+                "Avbryt",
+                "Säg \"hej\"",
+                "rad ett\nrad två"
+            ]
+        );
+    }
+
+    #[test]
+    fn limits_translation_to_included_pointers() {
+        let source =
+            r#"{"ui": {"save": "Spara", "a/b": "Avbryt", "cancel": "Avbryt"}, "meta": "Spara"}"#;
+
+        let (output, _) = translate(source, &["/ui".to_string()], &["/ui/a~1b".to_string()]);
+
+        assert_eq!(
+            output,
+            r#"{"ui": {"save": "Save", "a/b": "Avbryt", "cancel": "Cancel"}, "meta": "Spara"}"#
+        );
+    }
+
+    #[test]
+    fn translates_icu_plural_leaves_with_nested_arguments() {
+        let source =
+            r#"{"files": "{count, plural, one {# fil i {folder}} other {# filer i {folder}}}"}"#;
+
+        let (output, texts) = translate(source, &[], &[]);
+
+        assert_eq!(
+            output,
+            r#"{"files": "{count, plural, one {# file in {folder}} other {# files in {folder}}}"}"#
+        );
+        assert_eq!(texts, ["1 fil i {folder}", "2 filer i {folder}"]);
+    }
+
+    #[test]
+    fn rejects_invalid_json() {
+        let inference = FakeInference::replacing(PHRASES);
+        let translator = SegmentTranslator::new(&inference, "sv", "en", 1024);
+        let filter = PointerFilter::new(&[], &[]).expect("pointers should be valid");
+
+        let error = translate_json(&translator, "{\"a\": \"Spara\",\n \"b\": }", filter)
+            .expect_err("invalid JSON must fail");
+
+        assert_eq!(
+            error.to_string(),
+            "Invalid JSON: unexpected value at line 2, column 7"
+        );
+        assert_eq!(inference.calls(), 0);
+        assert!(PointerFilter::new(&["ui".to_string()], &[]).is_err());
+    }
+}
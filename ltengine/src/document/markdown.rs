@@ -0,0 +1,431 @@
+use std::ops::Range;
+
+use anyhow::Result;
+use pulldown_cmark::{Event, LinkType, Options, Parser, Tag, TagEnd};
+
+use super::segments::SegmentTranslator;
+use crate::translation::Inference;
+
+/// Front-matter keys whose values are prose rather than identifiers.
+const FRONT_MATTER_KEYS: &[&str] = &[
+    "abstract",
+    "alt",
+    "caption",
+    "description",
+    "excerpt",
+    "subtitle",
+    "summary",
+    "title",
+];
+
+enum Segment {
+    /// Inline content of a heading, paragraph, list item, or table cell.
+    Inline(Inline),
+    /// YAML front matter; only prose values are translated.
+    FrontMatter(Range<usize>),
+}
+
+#[derive(Default)]
+struct Inline {
+    /// Translatable text in source order; everything between them is kept as markup.
+    texts: Vec<Range<usize>>,
+    hard_breaks: Vec<usize>,
+    table_cell: bool,
+}
+
+impl Segment {
+    fn range(&self) -> Range<usize> {
+        match self {
+            Self::Inline(inline) => inline.texts[0].start..inline.texts[inline.texts.len() - 1].end,
+            Self::FrontMatter(range) => range.clone(),
+        }
+    }
+}
+
+/// Translates headings, paragraphs, list items, table cells, link text, and front-matter prose.
+///
+/// Code, inline code, URLs, HTML, and all other Markdown syntax are copied from the source.
+pub fn translate_markdown(
+    translator: &SegmentTranslator<'_, impl Inference>,
+    source: &str,
+) -> Result<String> {
+    let mut output = String::with_capacity(source.len());
+    let mut position = 0;
+    for segment in find_segments(source) {
+        let range = segment.range();
+        output.push_str(&source[position..range.start]);
+        match segment {
+            Segment::Inline(inline) => {
+                output.push_str(&translate_inline(translator, source, &inline)?);
+            }
+            Segment::FrontMatter(range) => {
+                output.push_str(&translate_front_matter(translator, &source[range])?);
+            }
+        }
+        position = range.end;
+    }
+    output.push_str(&source[position..]);
+    Ok(output)
+}
+
+fn find_segments(source: &str) -> Vec<Segment> {
+    let options = Options::ENABLE_TABLES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_FOOTNOTES
+        | Options::ENABLE_YAML_STYLE_METADATA_BLOCKS;
+    let mut segments = Vec::new();
+    let mut current: Option<Inline> = None;
+    let mut in_code_block = false;
+    let mut in_metadata = false;
+    let mut in_table_cell = false;
+    let mut autolink_depth = 0;
+
+    for (event, range) in Parser::new_ext(source, options).into_offset_iter() {
+        match event {
+            Event::Text(_) if in_metadata => segments.push(Segment::FrontMatter(range)),
+            Event::Text(_) if in_code_block || autolink_depth > 0 => {}
+            Event::Text(_) => {
+                let inline = current.get_or_insert_with(|| Inline {
+                    table_cell: in_table_cell,
+                    ..Inline::default()
+                });
+                push_text(&mut inline.texts, source, range);
+            }
+            Event::HardBreak => {
+                if let Some(inline) = &mut current {
+                    inline.hard_breaks.push(range.start);
+                }
+            }
+            Event::Code(_)
+            | Event::InlineHtml(_)
+            | Event::InlineMath(_)
+            | Event::FootnoteReference(_)
+            | Event::SoftBreak
+            | Event::TaskListMarker(_) => {}
+            Event::Start(Tag::Link {
+                link_type: LinkType::Autolink | LinkType::Email,
+                ..
+            }) => autolink_depth += 1,
+            Event::End(TagEnd::Link) if autolink_depth > 0 => autolink_depth -= 1,
+            Event::Start(
+                Tag::Emphasis
+                | Tag::Strong
+                | Tag::Strikethrough
+                | Tag::Superscript
+                | Tag::Subscript
+                | Tag::Link { .. }
+                | Tag::Image { .. },
+            )
+            | Event::End(
+                TagEnd::Emphasis
+                | TagEnd::Strong
+                | TagEnd::Strikethrough
+                | TagEnd::Superscript
+                | TagEnd::Subscript
+                | TagEnd::Link
+                | TagEnd::Image,
+            ) => {}
+            event => {
+                // Every other event is block-level and ends the current inline segment.
+                if let Some(inline) = current.take().filter(|inline| !inline.texts.is_empty()) {
+                    segments.push(Segment::Inline(inline));
+                }
+                match event {
+                    Event::Start(Tag::CodeBlock(_)) => in_code_block = true,
+                    Event::End(TagEnd::CodeBlock) => in_code_block = false,
+                    Event::Start(Tag::MetadataBlock(_)) => in_metadata = true,
+                    Event::End(TagEnd::MetadataBlock(_)) => in_metadata = false,
+                    Event::Start(Tag::TableCell) => in_table_cell = true,
+                    Event::End(TagEnd::TableCell) => in_table_cell = false,
+                    _ => {}
+                }
+            }
+        }
+    }
+    if let Some(inline) = current.filter(|inline| !inline.texts.is_empty()) {
+        segments.push(Segment::Inline(inline));
+    }
+    segments
+}
+
+/// Adds a text range, leaving bare URLs out so they are kept as markup.
+fn push_text(texts: &mut Vec<Range<usize>>, source: &str, range: Range<usize>) {
+    let mut start = range.start;
+    while let Some(found) = ["https://", "http://"]
+        .iter()
+        .filter_map(|scheme| source[start..range.end].find(scheme))
+        .min()
+    {
+        let url_start = start + found;
+        let url_length = source[url_start..range.end]
+            .find(|character: char| character.is_whitespace() || "<>\"".contains(character))
+            .unwrap_or(range.end - url_start);
+        let url = source[url_start..url_start + url_length]
+            .trim_end_matches(['.', ',', ';', ':', '!', '?', ')']);
+        if url_start > start {
+            texts.push(start..url_start);
+        }
+        start = url_start + url.len();
+    }
+    if start < range.end {
+        texts.push(start..range.end);
+    }
+}
+
+fn translate_inline(
+    translator: &SegmentTranslator<'_, impl Inference>,
+    source: &str,
+    inline: &Inline,
+) -> Result<String> {
+    let mut runs = vec![String::new()];
+    let mut markups = Vec::new();
+    let mut position = inline.texts[0].start;
+    for text in &inline.texts {
+        let gap = position..text.start;
+        let is_hard_break = inline.hard_breaks.iter().any(|start| gap.contains(start));
+        let gap = &source[gap];
+        if gap.contains('\n')
+            && !is_hard_break
+            && gap.chars().all(|c| c.is_whitespace() || c == '>')
+        {
+            // Soft line breaks, including container prefixes, join lines of one paragraph.
+            runs.last_mut().expect("runs are not empty").push(' ');
+        } else if !gap.is_empty() {
+            markups.push(gap);
+            runs.push(String::new());
+        }
+        runs.last_mut()
+            .expect("runs are not empty")
+            .push_str(&source[text.clone()]);
+        position = text.end;
+    }
+
+    let translated = translator.translate_runs(&runs, "markdown")?;
+    let mut output = String::new();
+    for (index, run) in translated.iter().enumerate() {
+        if index > 0 {
+            output.push_str(markups[index - 1]);
+        }
+        // A translated line break would end the paragraph, heading, or table row early.
+        let run = run.replace("\r\n", " ").replace('\n', " ");
+        if inline.table_cell {
+            output.push_str(&escape_pipes(&run));
+        } else {
+            output.push_str(&run);
+        }
+    }
+    Ok(output)
+}
+
+fn escape_pipes(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    let mut previous = None;
+    for character in text.chars() {
+        if character == '|' && previous != Some('\\') {
+            escaped.push('\\');
+        }
+        escaped.push(character);
+        previous = Some(character);
+    }
+    escaped
+}
+
+fn translate_front_matter(
+    translator: &SegmentTranslator<'_, impl Inference>,
+    yaml: &str,
+) -> Result<String> {
+    let mut output = String::with_capacity(yaml.len());
+    for line in yaml.split_inclusive('\n') {
+        let content = line.trim_end_matches(['\r', '\n']);
+        let Some((key, value)) = content.split_once(": ") else {
+            output.push_str(line);
+            continue;
+        };
+        if !FRONT_MATTER_KEYS.contains(&key.trim()) {
+            output.push_str(line);
+            continue;
+        }
+
+        let leading = &value[..value.len() - value.trim_start().len()];
+        let value = value.trim();
+        let translated = if let Some(inner) = value
+            .strip_prefix('"')
+            .and_then(|value| value.strip_suffix('"'))
+            .filter(|inner| !inner.contains('\\'))
+        {
+            Some(double_quoted(&translator.translate(inner, "text")?))
+        } else if let Some(inner) = value
+            .strip_prefix('\'')
+            .and_then(|value| value.strip_suffix('\''))
+        {
+            let translated = translator.translate(&inner.replace("''", "'"), "text")?;
+            Some(format!("'{}'", translated.replace('\'', "''")))
+        } else if is_plain_prose(value) {
+            let translated = translator.translate(value, "text")?;
+            Some(if is_plain_prose(&translated) {
+                translated
+            } else {
+                double_quoted(&translated)
+            })
+        } else {
+            None
+        };
+
+        match translated {
+            Some(translated) => {
+                output.push_str(key);
+                output.push_str(": ");
+                output.push_str(leading);
+                output.push_str(&translated.replace('\n', " "));
+                output.push_str(&line[content.len()..]);
+            }
+            None => output.push_str(line),
+        }
+    }
+    Ok(output)
+}
+
+/// Plain YAML scalars that are prose and stay plain after translation.
+fn is_plain_prose(value: &str) -> bool {
+    value.starts_with(char::is_alphabetic)
+        && !value.contains(": ")
+        && !value.contains(" #")
+        && !value.contains("://")
+        && !matches!(
+            value.to_ascii_lowercase().as_str(),
+            "true" | "false" | "yes" | "no" | "on" | "off" | "null"
+        )
+}
+
+fn double_quoted(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::translate_markdown;
+    use crate::document::segments::SegmentTranslator;
+    use crate::document::testing::{FakeInference, translate_with};
+
+    const PHRASES: &[(&str, &str)] = &[
+        ("Rubrik", "Heading"),
+        ("Första raden andra raden", "First line second line"),
+        ("punkt ett", "item one"),
+        ("punkt två", "item two"),
+        ("inre", "inner"),
+        ("Kör", "Run"),
+        (" på ", " on "),
+        ("Läs", "Read"),
+        ("mer här", "more here"),
+        (" och ", " and "),
+        ("nu", "now"),
+        ("Namn", "Name | full"),
+        ("Ålder", "Age"),
+        ("Min sida", "My page"),
+        ("En kort text", "A short text"),
+        ("Text", "Body"),
+    ];
+
+    fn translate(source: &str) -> (String, Vec<String>) {
+        translate_with(PHRASES, "en", |translator| {
+            translate_markdown(translator, source)
+        })
+    }
+
+    #[test]
+    fn translates_headings_paragraphs_and_lists() {
+        let (output, texts) = translate(
+            "# Rubrik\n\nFörsta raden\nandra raden.\n\n- punkt ett\n- punkt två\n  1. inre\n",
+        );
+
+        assert_eq!(
+            output,
+            "# Heading\n\nFirst line second line.\n\n- item one\n- item two\n  1. inner\n"
+        );
+        assert_eq!(texts.len(), 5);
+    }
+
+    #[test]
+    fn sends_oversized_paragraphs_in_sentence_groups() {
+        let inference = FakeInference::new(|text| Ok(text.to_uppercase()));
+        let translator = SegmentTranslator::new(&inference, "sv", "en", 12);
+        let source = "# Kort\n\nDet finns mjölk i kylen. Vi köper mer bröd. Sedan åker vi hem.\n";
+
+        let output = translate_markdown(&translator, source).expect("translation should succeed");
+
+        assert_eq!(
+            output,
+            "# KORT\n\nDET FINNS MJÖLK I KYLEN. VI KÖPER MER BRÖD. SEDAN ÅKER VI HEM.\n"
+        );
+        assert_eq!(
+            inference.texts.take(),
+            [
+                "Kort",
+                "Det finns mjölk i kylen.",
+                "Vi köper mer bröd.",
+                "Sedan åker vi hem."
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_code_urls_and_html_untouched() {
+        let source = "Kör `ls -la` på https://example.com/sida.\n\n```sh\nKör nu\n```\n\n<div>\nKör nu\n</div>\n\n<https://example.com/Kör>\n";
+
+        let (output, texts) = translate(source);
+
+        assert_eq!(
+            output,
+            "Run `ls -la` on https://example.com/sida.\n\n```sh\nKör nu\n```\n\n<div>\nKör nu\n</div>\n\n<https://example.com/Kör>\n"
+        );
+        assert_eq!(texts, ["Kör ⟦1⟧ på ⟦2⟧."]);
+    }
+
+    #[test]
+    fn translates_link_text_and_keeps_destinations() {
+        let (output, texts) =
+            translate("Läs [mer här](https://example.com/nu \"nu\") och **nu**.\n");
+
+        assert_eq!(
+            output,
+            "Read [more here](https://example.com/nu \"nu\") and **now**.\n"
+        );
+        assert_eq!(texts, ["Läs ⟦1⟧mer här⟦2⟧ och ⟦3⟧nu⟦4⟧."]);
+    }
+
+    #[test]
+    fn translates_table_cells_and_escapes_pipes() {
+        let (output, _) = translate("| Namn | Ålder |\n|---|--:|\n| Kör | 30 |\n");
+
+        assert_eq!(output, "| Name \\| full | Age |\n|---|--:|\n| Run | 30 |\n");
+    }
+
+    #[test]
+    fn translates_front_matter_prose_values_only() {
+        let source = "---\ntitle: Min sida\nlayout: Text\ndescription: \"En kort text\"\ndate: 2024-01-01\n---\n\nText.\n";
+
+        let (output, texts) = translate(source);
+
+        assert_eq!(
+            output,
+            "---\ntitle: My page\nlayout: Text\ndescription: \"A short text\"\ndate: 2024-01-01\n---\n\nBody.\n"
+        );
+        assert_eq!(texts, ["Min sida", "En kort text", "Text."]);
+    }
+
+    #[test]
+    fn falls_back_to_runs_when_placeholders_are_lost() {
+        let inference = FakeInference::new(|text| Ok(text.replace("⟦1⟧", "")));
+        let translator = SegmentTranslator::new(&inference, "sv", "en", 1024);
+
+        let output = translate_markdown(&translator, "Ett *viktigt* ord.\n")
+            .expect("translation should succeed");
+
+        assert_eq!(output, "Ett *viktigt* ord.\n");
+        assert_eq!(
+            *inference.texts.borrow(),
+            ["Ett ⟦1⟧viktigt⟦2⟧ ord.", "Ett", "viktigt", "ord."]
+        );
+    }
+}
@@ -0,0 +1,79 @@
+/// The line endings of a document, in order, so translated text can get them back.
+#[derive(Debug, PartialEq)]
+pub struct LineEndings(Vec<&'static str>);
+
+impl LineEndings {
+    /// Records the CRLF, LF, and CR line endings of `text` and returns it with each one
+    /// replaced by `\n`.
+    pub fn normalize(text: &str) -> (String, Self) {
+        let mut normalized = String::with_capacity(text.len());
+        let mut endings = Vec::new();
+        let mut rest = text;
+        while let Some(index) = rest.find(['\r', '\n']) {
+            normalized.push_str(&rest[..index]);
+            normalized.push('\n');
+            let ending = match &rest[index..] {
+                after if after.starts_with("\r\n") => "\r\n",
+                after if after.starts_with('\r') => "\r",
+                _ => "\n",
+            };
+            endings.push(ending);
+            rest = &rest[index + ending.len()..];
+        }
+        normalized.push_str(rest);
+        (normalized, Self(endings))
+    }
+
+    /// Replaces the `\n` line endings of translated text with the recorded ones.
+    ///
+    /// When the translation has as many lines as the source, each line gets its own ending
+    /// back. Otherwise every line gets the ending the source used most.
+    pub fn restore(&self, text: &str) -> String {
+        if self.0.iter().all(|ending| *ending == "\n") {
+            return text.to_string();
+        }
+        let dominant = ["\r\n", "\n", "\r"]
+            .into_iter()
+            .max_by_key(|ending| self.0.iter().filter(|seen| *seen == ending).count())
+            .expect("there are candidate endings");
+        let aligned = text.matches('\n').count() == self.0.len();
+
+        let mut restored = String::with_capacity(text.len() + self.0.len());
+        for (index, line) in text.split('\n').enumerate() {
+            if index > 0 {
+                restored.push_str(if aligned { self.0[index - 1] } else { dominant });
+            }
+            restored.push_str(line);
+        }
+        restored
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LineEndings;
+
+    #[test]
+    fn restores_mixed_line_endings_line_by_line() {
+        let (text, endings) = LineEndings::normalize("Ett\r\nTvå\nTre\rFyra\r\n\r\n");
+
+        assert_eq!(text, "Ett\nTvå\nTre\nFyra\n\n");
+        assert_eq!(
+            endings.restore("One\nTwo\nThree\nFour\n\n"),
+            "One\r\nTwo\nThree\rFour\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn uses_the_dominant_ending_when_lines_change() {
+        let (_, endings) = LineEndings::normalize("Ett\r\nTvå\r\nTre\n");
+
+        assert_eq!(endings.restore("One\nTwo"), "One\r\nTwo");
+        assert_eq!(
+            LineEndings::normalize("Ett\nTvå")
+                .1
+                .restore("One\nTwo\nThree"),
+            "One\nTwo\nThree"
+        );
+    }
+}
@@ -0,0 +1,267 @@
+use std::collections::HashMap;
+use std::ops::Range;
+
+use anyhow::{Context, Result, bail};
+
+use super::package::Package;
+use super::segments::SegmentTranslator;
+use super::xml::{self, XmlKind};
+use crate::translation::Inference;
+
+const CONTENT_PART: &str = "content.xml";
+/// `content.xml` holds the body; `styles.xml` holds header and footer paragraphs.
+const TEXT_PARTS: &[&str] = &[CONTENT_PART, "styles.xml"];
+const PARAGRAPH_ELEMENTS: &[&str] = &["text:p", "text:h"];
+/// Paragraph children whose text belongs to the sentence around them.
+const INLINE_ELEMENTS: &[&str] = &[
+    "text:a",
+    "text:bookmark",
+    "text:bookmark-end",
+    "text:bookmark-start",
+    "text:line-break",
+    "text:reference-mark",
+    "text:reference-mark-end",
+    "text:reference-mark-start",
+    "text:soft-page-break",
+    "text:span",
+    "text:tab",
+];
+
+/// A stretch of text between markup, made of text nodes and `text:s` spaces.
+struct Run {
+    range: Range<usize>,
+    text: String,
+}
+
+#[derive(Default)]
+struct Paragraph {
+    runs: Vec<Run>,
+    /// Whether the last token extended the last run, so the next text continues it.
+    open_run: bool,
+    /// Depth inside fields, notes, frames, and other elements whose text is kept.
+    skipped_depth: usize,
+}
+
+/// Translates the `text:p` and `text:h` paragraphs of an OpenDocument text package.
+///
+/// Only text nodes and `text:s` spaces change; spans, links, notes, frames, fields, styles,
+/// images, and metadata are copied, and `mimetype` stays the first, uncompressed entry.
+pub fn translate_odt(
+    translator: &SegmentTranslator<'_, impl Inference>,
+    bytes: &[u8],
+    max_input_bytes: u64,
+) -> Result<Vec<u8>> {
+    let mut package = Package::open(bytes, max_input_bytes)?;
+    if !package.contains(CONTENT_PART) {
+        bail!("ODT document is missing {CONTENT_PART}");
+    }
+
+    let mut parts = HashMap::new();
+    for name in TEXT_PARTS {
+        if !package.contains(name) {
+            continue;
+        }
+        let source = package.read_text(name)?;
+        let translated = translate_part(translator, &source)
+            .with_context(|| format!("Failed to translate ODT part {name}"))?;
+        parts.insert(name.to_string(), translated);
+    }
+    package.repack(&parts)
+}
+
+fn translate_part(
+    translator: &SegmentTranslator<'_, impl Inference>,
+    source: &str,
+) -> Result<String> {
+    let mut paragraphs: Vec<Paragraph> = Vec::new();
+    let mut edits = Vec::new();
+
+    for token in xml::tokenize(source)? {
+        if PARAGRAPH_ELEMENTS.contains(&token.name) {
+            match token.kind {
+                XmlKind::Start => {
+                    if let Some(outer) = paragraphs.last_mut() {
+                        outer.open_run = false;
+                    }
+                    paragraphs.push(Paragraph::default());
+                }
+                XmlKind::End => {
+                    if let Some(paragraph) = paragraphs.pop() {
+                        edits.extend(translate_paragraph(translator, paragraph)?);
+                    }
+                }
+                _ => {}
+            }
+            continue;
+        }
+        let Some(paragraph) = paragraphs.last_mut() else {
+            continue;
+        };
+
+        let text = match (token.kind, token.name) {
+            _ if paragraph.skipped_depth > 0 => None,
+            (XmlKind::Text, _) => Some(xml::unescape(&source[token.range.clone()])),
+            (XmlKind::Empty, "text:s") => {
+                let count = xml::attribute_range(&source[token.range.clone()], "text:c")
+                    .and_then(|range| source[token.range.clone()][range].parse().ok())
+                    .unwrap_or(1);
+                Some(" ".repeat(count))
+            }
+            _ => None,
+        };
+        match (text, token.kind) {
+            (Some(text), _) => {
+                match paragraph.runs.last_mut() {
+                    Some(run) if paragraph.open_run => {
+                        run.range.end = token.range.end;
+                        run.text.push_str(&text);
+                    }
+                    _ => paragraph.runs.push(Run {
+                        range: token.range,
+                        text,
+                    }),
+                }
+                paragraph.open_run = true;
+            }
+            (None, XmlKind::Start) => {
+                paragraph.open_run = false;
+                if paragraph.skipped_depth > 0 || !INLINE_ELEMENTS.contains(&token.name) {
+                    paragraph.skipped_depth += 1;
+                }
+            }
+            (None, XmlKind::End) => {
+                paragraph.open_run = false;
+                paragraph.skipped_depth = paragraph.skipped_depth.saturating_sub(1);
+            }
+            (None, _) => paragraph.open_run = false,
+        }
+    }
+
+    Ok(xml::apply_edits(source, edits))
+}
+
+fn translate_paragraph(
+    translator: &SegmentTranslator<'_, impl Inference>,
+    paragraph: Paragraph,
+) -> Result<Vec<(Range<usize>, String)>> {
+    if paragraph.runs.iter().all(|run| run.text.trim().is_empty()) {
+        return Ok(Vec::new());
+    }
+    let texts: Vec<&str> = paragraph.runs.iter().map(|run| run.text.as_str()).collect();
+    let translated = translator.translate_runs(&texts, "text")?;
+    Ok(paragraph
+        .runs
+        .into_iter()
+        .zip(translated)
+        .map(|(run, text)| (run.range, encode_text(&text)))
+        .collect())
+}
+
+/// Escapes text for a paragraph, writing repeated spaces as `text:s` because ODF collapses them.
+fn encode_text(text: &str) -> String {
+    let mut encoded = String::with_capacity(text.len());
+    let mut spaces = 0;
+    for character in xml::escape_text(text).chars().chain(['\0']) {
+        if character.is_whitespace() {
+            spaces += 1;
+            continue;
+        }
+        match spaces {
+            0 => {}
+            1 => encoded.push(' '),
+            2 => encoded.push_str(" <text:s/>"),
+            _ => encoded.push_str(&format!(" <text:s text:c=\"{}\"/>", spaces - 1)),
+        }
+        spaces = 0;
+        if character != '\0' {
+            encoded.push(character);
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::translate_odt;
+    use crate::document::testing::{translate_with, zip_entries, zip_package};
+
+    const MIMETYPE: &[u8] = b"application/vnd.oasis.opendocument.text";
+    const PHRASES: &[(&str, &str)] = &[
+        ("Rubrik", "Heading"),
+        ("Hej", "Hello"),
+        ("världen", "world"),
+        ("länken", "the link"),
+        ("Fotnot", "Footnote"),
+        ("Sidhuvud", "Page header"),
+        ("Ost & bröd", "Cheese & bread"),
+    ];
+
+    fn content(body: &str) -> String {
+        format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<office:document-content xmlns:office=\"urn:oasis:names:tc:opendocument:xmlns:office:1.0\" xmlns:text=\"urn:oasis:names:tc:opendocument:xmlns:text:1.0\"><office:body><office:text>{body}</office:text></office:body></office:document-content>"
+        )
+    }
+
+    #[test]
+    fn translates_paragraphs_across_spans_and_keeps_other_markup() {
+        let source = content(
+            "<text:h text:outline-level=\"1\">Rubrik</text:h><text:p text:style-name=\"P1\">Hej <text:span text:style-name=\"T1\">världen</text:span><text:s text:c=\"2\"/>och <text:a xlink:href=\"https://example.se\">länken</text:a><text:note text:id=\"n1\"><text:note-citation>1</text:note-citation><text:note-body><text:p>Fotnot</text:p></text:note-body></text:note><draw:frame><draw:image xlink:href=\"Pictures/a.png\"/></draw:frame></text:p><text:p>Ost &amp; bröd<text:tab/><text:date>2024-01-01</text:date></text:p>",
+        );
+        let styles = "<office:document-styles><style:master-page><style:header><text:p>Sidhuvud</text:p></style:header></style:master-page></office:document-styles>";
+        let image = [0x89, b'P', b'N', b'G'];
+        let package = zip_package(&[
+            ("mimetype", MIMETYPE),
+            ("content.xml", source.as_bytes()),
+            ("styles.xml", styles.as_bytes()),
+            (
+                "meta.xml",
+                b"<office:meta><dc:title>Hej</dc:title></office:meta>",
+            ),
+            ("Pictures/a.png", &image),
+        ]);
+
+        let (output, texts) = translate_with(PHRASES, "en", |translator| {
+            translate_odt(translator, &package, 1 << 20)
+        });
+
+        let entries = zip_entries(&output);
+        assert_eq!(
+            String::from_utf8(entries[1].1.clone()).unwrap(),
+            content(
+                "<text:h text:outline-level=\"1\">Heading</text:h><text:p text:style-name=\"P1\">Hello <text:span text:style-name=\"T1\">world</text:span> <text:s/>och <text:a xlink:href=\"https://example.se\">the link</text:a><text:note text:id=\"n1\"><text:note-citation>1</text:note-citation><text:note-body><text:p>Footnote</text:p></text:note-body></text:note><draw:frame><draw:image xlink:href=\"Pictures/a.png\"/></draw:frame></text:p><text:p>Cheese &amp; bread<text:tab/><text:date>2024-01-01</text:date></text:p>"
+            )
+        );
+        assert!(String::from_utf8_lossy(&entries[2].1).contains("<text:p>Page header</text:p>"));
+        assert_eq!(
+            entries[3].1,
+            b"<office:meta><dc:title>Hej</dc:title></office:meta>"
+        );
+        assert_eq!(entries[4].1, image);
+        assert_eq!(
+            texts,
+            [
+                "Rubrik",
+                "Fotnot",
+                "Hej ⟦1⟧världen⟦2⟧  och ⟦3⟧länken",
+                "Ost & bröd",
+                "Sidhuvud"
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_mimetype_as_the_first_stored_entry() {
+        let content = content("<text:p>Hej</text:p>");
+        let package = zip_package(&[("mimetype", MIMETYPE), ("content.xml", content.as_bytes())]);
+
+        let (output, _) = translate_with(PHRASES, "en", |translator| {
+            translate_odt(translator, &package, 1 << 20)
+        });
+
+        // Local file header: stored method, no extra field, then the name and the data.
+        assert_eq!(&output[8..10], [0, 0]);
+        assert_eq!(&output[28..30], [0, 0]);
+        assert_eq!(&output[30..38], b"mimetype");
+        assert_eq!(&output[38..38 + MIMETYPE.len()], MIMETYPE);
+    }
+}
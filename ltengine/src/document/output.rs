@@ -0,0 +1,271 @@
+use std::ffi::OsString;
+use std::fs::{self, File, Metadata, OpenOptions};
+use std::io::{self, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use anyhow::{Context, Result, bail};
+
+/// Output file name used with `--output-dir` when no `--name-template` is given.
+pub const DEFAULT_NAME_TEMPLATE: &str = "{stem}.{target}.{ext}";
+
+/// Builds an output file name from a template with `{stem}`, `{ext}`, `{source}`, and
+/// `{target}` placeholders; `{{` and `}}` stand for literal braces.
+pub fn template_name(template: &str, input: &Path, source: &str, target: &str) -> Result<OsString> {
+    let mut name = OsString::new();
+    let mut rest = template;
+    while let Some(index) = rest.find(['{', '}']) {
+        name.push(&rest[..index]);
+        let (brace, after) = rest[index..].split_at(1);
+        if let Some(after) = after.strip_prefix(brace) {
+            name.push(brace);
+            rest = after;
+            continue;
+        }
+        let Some((placeholder, after)) = after.split_once('}').filter(|_| brace == "{") else {
+            bail!(
+                "Unbalanced brace in name template {template:?}; write {{{{ or }}}} for a literal brace"
+            );
+        };
+        match placeholder {
+            "stem" => name.push(input.file_stem().unwrap_or_default()),
+            "ext" => name.push(input.extension().unwrap_or_default()),
+            "source" => name.push(source),
+            "target" => name.push(target),
+            _ => bail!(
+                "Unknown placeholder {{{placeholder}}} in name template; use {{stem}}, {{ext}}, {{source}}, or {{target}}"
+            ),
+        }
+        rest = after;
+    }
+    name.push(rest);
+    let mut components = Path::new(&name).components();
+    if !matches!(
+        (components.next(), components.next()),
+        (Some(std::path::Component::Normal(_)), None)
+    ) {
+        bail!("Name template {template:?} must produce a file name, not {name:?}");
+    }
+    Ok(name)
+}
+
+/// Whether two files are the same, including hard links and other names for one file.
+pub fn same_file(first: &Metadata, second: &Metadata) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        first.dev() == second.dev() && first.ino() == second.ino()
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = (first, second);
+        false
+    }
+}
+
+/// Whether standard output is redirected into `path`.
+pub fn is_stdout(path: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::fd::AsFd;
+        let Ok(stdout) = std::io::stdout().as_fd().try_clone_to_owned() else {
+            return false;
+        };
+        match (File::from(stdout).metadata(), fs::metadata(path)) {
+            (Ok(stdout), Ok(file)) => same_file(&stdout, &file),
+            _ => false,
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = path;
+        false
+    }
+}
+
+/// A temporary file next to the output, removed unless it is moved into place.
+struct Temporary(PathBuf);
+
+impl Drop for Temporary {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+/// Writes an output document so that it appears complete or not at all.
+///
+/// The bytes go to a temporary file in the output directory, which is synced and then moved to
+/// `output` with a rename that fails when `output` exists, even if another process created it
+/// after the caller checked. Where the platform or filesystem has no such rename, the file is
+/// hard linked instead, which fails the same way; with neither, nothing is written. With
+/// `replace`, the temporary file is renamed over `output`.
+pub fn write(output: &Path, bytes: &[u8], replace: bool) -> Result<()> {
+    static NEXT_TEMPORARY: AtomicU64 = AtomicU64::new(0);
+    let mut name = std::ffi::OsString::from(".");
+    name.push(output.file_name().unwrap_or_default());
+    name.push(format!(
+        ".{}-{}.ltengine-tmp",
+        std::process::id(),
+        NEXT_TEMPORARY.fetch_add(1, Ordering::Relaxed)
+    ));
+    let temporary = Temporary(output.with_file_name(name));
+
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&temporary.0)
+        .with_context(|| format!("Failed to create output document {}", output.display()))?;
+    file.write_all(bytes)
+        .and_then(|()| file.sync_all())
+        .with_context(|| format!("Failed to write output document {}", output.display()))?;
+    drop(file);
+
+    let moved = if replace {
+        fs::rename(&temporary.0, output)
+    } else {
+        rename_no_replace(&temporary.0, output).or_else(|error| match error.kind() {
+            ErrorKind::Unsupported => fs::hard_link(&temporary.0, output),
+            _ => Err(error),
+        })
+    };
+    match moved {
+        Ok(()) => {}
+        Err(error) if error.kind() == ErrorKind::AlreadyExists => {
+            bail!(
+                "Output path already exists: {}; pass --force to replace it",
+                output.display()
+            )
+        }
+        Err(error) if error.kind() == ErrorKind::Unsupported => {
+            bail!(
+                "Cannot create {} without the risk of replacing a file created meanwhile: the filesystem supports neither exclusive renames nor hard links; pass --force to replace any file at that path",
+                output.display()
+            )
+        }
+        Err(error) => {
+            return Err(error).with_context(|| {
+                format!(
+                    "Failed to move the translated document to {}",
+                    output.display()
+                )
+            });
+        }
+    }
+    // Make the new directory entry durable; directories cannot be opened as files everywhere.
+    if let Some(directory) = output.parent()
+        && cfg!(unix)
+    {
+        let directory = if directory.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            directory
+        };
+        let _ = File::open(directory).and_then(|directory| directory.sync_all());
+    }
+    Ok(())
+}
+
+/// Renames `from` to `to`, failing with [`ErrorKind::AlreadyExists`] rather than replacing an
+/// existing `to`, or with [`ErrorKind::Unsupported`] where that cannot be done atomically.
+fn rename_no_replace(from: &Path, to: &Path) -> io::Result<()> {
+    #[cfg(any(target_os = "linux", target_os = "macos", target_os = "ios"))]
+    {
+        use std::ffi::CString;
+        use std::os::unix::ffi::OsStrExt;
+
+        let from = CString::new(from.as_os_str().as_bytes())?;
+        let to = CString::new(to.as_os_str().as_bytes())?;
+        // SAFETY: both paths are NUL-terminated strings that outlive the call.
+        #[cfg(target_os = "linux")]
+        let result = unsafe {
+            libc::renameat2(
+                libc::AT_FDCWD,
+                from.as_ptr(),
+                libc::AT_FDCWD,
+                to.as_ptr(),
+                libc::RENAME_NOREPLACE,
+            )
+        };
+        // SAFETY: as above.
+        #[cfg(not(target_os = "linux"))]
+        let result = unsafe { libc::renamex_np(from.as_ptr(), to.as_ptr(), libc::RENAME_EXCL) };
+        if result == 0 {
+            return Ok(());
+        }
+        let error = io::Error::last_os_error();
+        // Older kernels and some filesystems do not support the flag.
+        match error.raw_os_error() {
+            Some(libc::EINVAL | libc::ENOSYS | libc::ENOTSUP) => Err(ErrorKind::Unsupported.into()),
+            _ => Err(error),
+        }
+    }
+    #[cfg(windows)]
+    {
+        use std::os::windows::ffi::OsStrExt;
+
+        let wide = |path: &Path| {
+            let mut wide: Vec<u16> = path.as_os_str().encode_wide().collect();
+            wide.push(0);
+            wide
+        };
+        let (from, to) = (wide(from), wide(to));
+        // SAFETY: both paths are NUL-terminated wide strings that outlive the call. Without
+        // MOVEFILE_REPLACE_EXISTING the move fails when `to` exists.
+        if unsafe {
+            windows_sys::Win32::Storage::FileSystem::MoveFileExW(from.as_ptr(), to.as_ptr(), 0)
+        } == 0
+        {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+    #[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "ios", windows)))]
+    {
+        let _ = (from, to);
+        Err(ErrorKind::Unsupported.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use std::path::Path;
+
+    use super::{template_name, write};
+
+    #[test]
+    fn never_replaces_an_existing_file_and_leaves_no_temporary_files() {
+        let directory =
+            std::env::temp_dir().join(format!("ltengine-output-{}", std::process::id()));
+        fs::create_dir(&directory).unwrap();
+        let output = directory.join("translated.txt");
+
+        write(&output, b"Hello.", false).expect("a new output should be written");
+        let error = write(&output, b"Overwritten.", false).expect_err("the output exists");
+
+        assert!(error.to_string().starts_with("Output path already exists"));
+        assert_eq!(fs::read(&output).unwrap(), b"Hello.");
+        write(&output, b"Replaced.", true).expect("an output can be replaced");
+        assert_eq!(fs::read(&output).unwrap(), b"Replaced.");
+        assert_eq!(fs::read_dir(&directory).unwrap().count(), 1);
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn names_outputs_from_templates() {
+        let input = Path::new("docs/guide.sv.md");
+        let name = |template| template_name(template, input, "sv", "en");
+
+        assert_eq!(name("{stem}.{target}.{ext}").unwrap(), "guide.sv.en.md");
+        assert_eq!(name("{{{source}}}-{target}.txt").unwrap(), "{sv}-en.txt");
+        assert!(
+            name("{lang}.md")
+                .unwrap_err()
+                .to_string()
+                .contains("Unknown placeholder {lang}")
+        );
+        assert!(name("{stem").is_err());
+        assert!(name("{target}/{stem}.{ext}").is_err());
+    }
+}
@@ -0,0 +1,82 @@
+use std::collections::HashMap;
+use std::io::{Cursor, Read, Write};
+
+use anyhow::{Context, Result, bail};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+/// Unpacked parts may be this many times larger than the input limit before reading stops.
+const MAX_EXPANSION: u64 = 16;
+
+/// ZIP-based document package such as DOCX, ODT, or EPUB.
+pub struct Package<'a> {
+    archive: ZipArchive<Cursor<&'a [u8]>>,
+    remaining: u64,
+}
+
+impl<'a> Package<'a> {
+    pub fn open(bytes: &'a [u8], max_input_bytes: u64) -> Result<Self> {
+        let archive = ZipArchive::new(Cursor::new(bytes))
+            .context("Input document is not a valid ZIP package")?;
+        Ok(Self {
+            archive,
+            remaining: max_input_bytes.saturating_mul(MAX_EXPANSION),
+        })
+    }
+
+    /// Entry names in archive order.
+    pub fn names(&self) -> Vec<String> {
+        self.archive.file_names().map(str::to_string).collect()
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.archive.index_for_name(name).is_some()
+    }
+
+    /// Reads a UTF-8 part, counting it against the unpacked size budget.
+    pub fn read_text(&mut self, name: &str) -> Result<String> {
+        let file = self
+            .archive
+            .by_name(name)
+            .with_context(|| format!("Document package is missing {name}"))?;
+        let mut bytes = Vec::new();
+        file.take(self.remaining.saturating_add(1))
+            .read_to_end(&mut bytes)
+            .with_context(|| format!("Failed to unpack {name}"))?;
+        if bytes.len() as u64 > self.remaining {
+            bail!("Document package unpacks beyond the size limit at {name}");
+        }
+        self.remaining -= bytes.len() as u64;
+        String::from_utf8(bytes).with_context(|| format!("{name} must contain valid UTF-8"))
+    }
+
+    /// Writes a new package with the same entries in the same order, replacing the given parts.
+    ///
+    /// Untouched entries are copied without recompression, so their bytes, compression method,
+    /// and timestamps are kept.
+    pub fn repack(mut self, parts: &HashMap<String, String>) -> Result<Vec<u8>> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for index in 0..self.archive.len() {
+            let file = self.archive.by_index_raw(index)?;
+            let Some(text) = parts.get(file.name()) else {
+                writer.raw_copy_file(file)?;
+                continue;
+            };
+
+            let method = match file.compression() {
+                CompressionMethod::Stored => CompressionMethod::Stored,
+                _ => CompressionMethod::Deflated,
+            };
+            let mut options = SimpleFileOptions::default().compression_method(method);
+            if let Some(modified) = file.last_modified() {
+                options = options.last_modified_time(modified);
+            }
+            let name = file.name().to_string();
+            writer.start_file(name.as_str(), options)?;
+            writer
+                .write_all(text.as_bytes())
+                .with_context(|| format!("Failed to pack {name}"))?;
+        }
+        Ok(writer.finish()?.into_inner())
+    }
+}
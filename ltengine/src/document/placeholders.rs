@@ -0,0 +1,221 @@
+use std::ops::Range;
+
+const PRINTF_LENGTHS: &[&str] = &["hh", "ll", "h", "l", "L", "q", "j", "z", "t"];
+const PRINTF_CONVERSIONS: &str = "diouxXeEfFgGaAcspn@";
+
+/// Finds printf (`%s`, `%1$d`, `%(name)s`, `%%`, Apple's `%@` and `%#@name@`) and brace (`{0}`,
+/// `{name}`) format specifiers.
+pub fn format_specifiers(text: &str) -> Vec<Range<usize>> {
+    let mut specifiers = Vec::new();
+    let mut index = 0;
+    while index < text.len() {
+        let end = match text.as_bytes()[index] {
+            b'%' => variable_end(text, index).or_else(|| printf_end(text, index + 1)),
+            b'{' => text[index + 1..].find('}').and_then(|length| {
+                let name = &text[index + 1..index + 1 + length];
+                (!name.is_empty()
+                    && name
+                        .chars()
+                        .all(|character| character.is_ascii_alphanumeric() || character == '_'))
+                .then_some(index + length + 2)
+            }),
+            _ => None,
+        };
+        match end {
+            Some(end) => {
+                specifiers.push(index..end);
+                index = end;
+            }
+            None => index += 1,
+        }
+    }
+    specifiers
+}
+
+/// Returns the end of an Apple `.stringsdict` variable such as `%#@files@`.
+fn variable_end(text: &str, index: usize) -> Option<usize> {
+    let name = text[index..].strip_prefix("%#@")?;
+    let length = name.find('@')?;
+    (length > 0
+        && name[..length]
+            .chars()
+            .all(|character| character.is_ascii_alphanumeric() || character == '_'))
+    .then_some(index + length + 4)
+}
+
+fn printf_end(text: &str, mut index: usize) -> Option<usize> {
+    let bytes = text.as_bytes();
+    let digits = |from: usize| {
+        bytes[from.min(bytes.len())..]
+            .iter()
+            .take_while(|byte| byte.is_ascii_digit())
+            .count()
+    };
+    match bytes.get(index) {
+        Some(b'%') => return Some(index + 1),
+        Some(b'(') => index += text[index..].find(')')? + 1,
+        _ => {
+            let position = digits(index);
+            if position > 0 && bytes.get(index + position) == Some(&b'$') {
+                index += position + 1;
+            }
+        }
+    }
+    while matches!(bytes.get(index), Some(b'-' | b'+' | b'#' | b'0' | b'\'')) {
+        index += 1;
+    }
+    index += if bytes.get(index) == Some(&b'*') {
+        1
+    } else {
+        digits(index)
+    };
+    if bytes.get(index) == Some(&b'.') {
+        index += 1;
+        index += if bytes.get(index) == Some(&b'*') {
+            1
+        } else {
+            digits(index)
+        };
+    }
+    if let Some(length) = PRINTF_LENGTHS
+        .iter()
+        .find(|length| text[index.min(text.len())..].starts_with(**length))
+    {
+        index += length.len();
+    }
+    let conversion = *bytes.get(index)?;
+    PRINTF_CONVERSIONS
+        .contains(conversion as char)
+        .then_some(index + 1)
+}
+
+/// Finds `{{ name }}`, `{name}`, `%{name}`, and `$t(key)` interpolation placeholders.
+pub fn interpolations(text: &str) -> Vec<Range<usize>> {
+    let is_name = |name: &str| {
+        !name.is_empty()
+            && name
+                .chars()
+                .all(|character| character.is_alphanumeric() || "_.-".contains(character))
+    };
+    let mut found = Vec::new();
+    let mut index = 0;
+    while index < text.len() {
+        let rest = &text[index..];
+        let length = if let Some(inner) = rest.strip_prefix("{{") {
+            inner
+                .find("}}")
+                .filter(|end| !inner[..*end].contains(['{', '}']))
+                .map(|end| end + 4)
+        } else if let Some(inner) = rest.strip_prefix("%{") {
+            inner
+                .find('}')
+                .filter(|end| is_name(&inner[..*end]))
+                .map(|end| end + 3)
+        } else if let Some(inner) = rest.strip_prefix('{') {
+            inner
+                .find('}')
+                .filter(|end| is_name(inner[..*end].trim()))
+                .map(|end| end + 2)
+        } else if let Some(inner) = rest.strip_prefix("$t(") {
+            inner
+                .find(')')
+                .filter(|end| !inner[..*end].contains('('))
+                .map(|end| end + 4)
+        } else {
+            None
+        };
+        match length {
+            Some(length) => {
+                found.push(index..index + length);
+                index += length;
+            }
+            None => index += rest.chars().next().map_or(1, char::len_utf8),
+        }
+    }
+    found
+}
+
+/// Whether a whole value is a URL or an e-mail address, which is copied instead of translated.
+pub fn is_address(text: &str) -> bool {
+    let text = text.trim();
+    if text.is_empty() || text.contains(char::is_whitespace) {
+        return false;
+    }
+    if let Some((scheme, rest)) = text.split_once("://") {
+        return !rest.is_empty()
+            && scheme.starts_with(|character: char| character.is_ascii_alphabetic())
+            && scheme
+                .chars()
+                .all(|character| character.is_ascii_alphanumeric() || "+.-".contains(character));
+    }
+    let text = text.strip_prefix("mailto:").unwrap_or(text);
+    text.starts_with("www.")
+        || text.split_once('@').is_some_and(|(local, domain)| {
+            !local.is_empty()
+                && !domain.contains('@')
+                && domain.contains('.')
+                && !domain.split('.').any(str::is_empty)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Range;
+
+    use super::{format_specifiers, interpolations, is_address};
+
+    fn found(text: &str, ranges: Vec<Range<usize>>) -> Vec<&str> {
+        ranges.into_iter().map(|range| &text[range]).collect()
+    }
+
+    #[test]
+    fn finds_format_specifiers() {
+        let text = "%s har %1$d filer (%.2f%%), %(name)s, {0}, %@ %#@files@ och 100% klart";
+
+        assert_eq!(
+            found(text, format_specifiers(text)),
+            [
+                "%s",
+                "%1$d",
+                "%.2f",
+                "%%",
+                "%(name)s",
+                "{0}",
+                "%@",
+                "%#@files@"
+            ]
+        );
+    }
+
+    #[test]
+    fn finds_interpolations() {
+        let text = "Hej {{ .Name }}, {count} nya, %{user} och $t(common.ok) {inte ett namn}";
+
+        assert_eq!(
+            found(text, interpolations(text)),
+            ["{{ .Name }}", "{count}", "%{user}", "$t(common.ok)"]
+        );
+    }
+
+    #[test]
+    fn recognizes_urls_and_email_addresses() {
+        for address in [
+            "https://example.se/om-oss?sida=2",
+            "ftp://filer.example.se",
+            "www.example.se",
+            "info@example.se",
+            "mailto:info@example.se",
+        ] {
+            assert!(is_address(address), "{address}");
+        }
+        for text in [
+            "Besök https://example.se",
+            "Hej@alla",
+            "@namn",
+            "info@example.",
+            "Läs mer",
+        ] {
+            assert!(!is_address(text), "{text}");
+        }
+    }
+}
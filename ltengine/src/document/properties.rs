@@ -0,0 +1,296 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::ops::Range;
+
+use anyhow::{Result, bail};
+
+use super::placeholders::format_specifiers;
+use super::segments::SegmentTranslator;
+use super::xml;
+use crate::translation::Inference;
+
+/// Translates the values of a Java `.properties` file.
+///
+/// The file is read as UTF-8 when it is valid UTF-8 and as ISO-8859-1 otherwise, and written
+/// back in the same encoding with non-ASCII characters in translations escaped as `\uXXXX`.
+/// Keys, comments, separators, and line continuations are copied unchanged. MessageFormat
+/// arguments such as `{0}` or `{1,number}`, printf specifiers, and `\n`-style escapes are
+/// protected, and values with MessageFormat arguments keep apostrophes doubled.
+pub fn translate_properties(
+    translator: &SegmentTranslator<'_, impl Inference>,
+    bytes: &[u8],
+) -> Result<Vec<u8>> {
+    let (source, latin1) = match std::str::from_utf8(bytes) {
+        Ok(source) => (source.to_string(), false),
+        Err(_) => (bytes.iter().map(|&byte| char::from(byte)).collect(), true),
+    };
+    if source.trim().is_empty() {
+        bail!("Properties document is empty");
+    }
+
+    let mut translations: HashMap<&str, String> = HashMap::new();
+    let mut edits = Vec::new();
+    for value in values(&source) {
+        let raw = &source[value.clone()];
+        let translated = match translations.get(raw) {
+            Some(translated) => translated.clone(),
+            None => {
+                let translated = translate_value(translator, raw)?;
+                translations.insert(raw, translated.clone());
+                translated
+            }
+        };
+        if translated != raw {
+            edits.push((value, translated));
+        }
+    }
+    let output = xml::apply_edits(&source, edits);
+    Ok(if latin1 {
+        output
+            .chars()
+            .map(|character| u8::try_from(character).expect("translations are ASCII-escaped"))
+            .collect()
+    } else {
+        output.into_bytes()
+    })
+}
+
+/// Returns the raw value range of every key-value entry, continuation lines included.
+fn values(source: &str) -> Vec<Range<usize>> {
+    let line_end = |from: usize| {
+        source[from..]
+            .find('\n')
+            .map_or(source.len(), |end| from + end)
+    };
+    let mut values = Vec::new();
+    let mut position = 0;
+    while position < source.len() {
+        let end = line_end(position);
+        let line = &source[position..end];
+        let content = line.trim_start_matches([' ', '\t', '\x0c']);
+        let start = position + line.len() - content.len();
+        let next = (end + 1).min(source.len());
+        if content.trim().is_empty() || content.starts_with(['#', '!']) {
+            position = next;
+            continue;
+        }
+
+        let mut end = end;
+        while continues(source[start..end].trim_end_matches('\r')) && end < source.len() {
+            end = line_end(end + 1);
+        }
+        let logical = &source[start..end];
+        let logical = logical.strip_suffix('\r').unwrap_or(logical);
+        let mut key_end = logical.len();
+        let mut escaped = false;
+        for (index, character) in logical.char_indices() {
+            match character {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '=' | ':' | ' ' | '\t' | '\x0c' => {
+                    key_end = index;
+                    break;
+                }
+                _ => {}
+            }
+        }
+        let separator = &logical[key_end..];
+        let separator = separator.trim_start_matches([' ', '\t', '\x0c']);
+        let separator = separator.strip_prefix(['=', ':']).unwrap_or(separator);
+        let value = separator.trim_start_matches([' ', '\t', '\x0c']);
+        if !value.is_empty() {
+            values.push(start + logical.len() - value.len()..start + logical.len());
+        }
+        position = (end + 1).min(source.len());
+    }
+    values
+}
+
+/// Whether a natural line ends with an odd number of backslashes and so continues.
+fn continues(line: &str) -> bool {
+    (line.len() - line.trim_end_matches('\\').len()) % 2 == 1
+}
+
+/// Finds MessageFormat arguments such as `{0}`, `{1,number}`, and `{2,date,short}`.
+fn message_arguments(text: &str) -> Vec<Range<usize>> {
+    let mut arguments = Vec::new();
+    for (index, _) in text.match_indices('{') {
+        let rest = &text[index + 1..];
+        let digits = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        if digits == 0 || !rest[digits..].starts_with(['}', ',']) {
+            continue;
+        }
+        if let Some(end) = rest.find('}').filter(|&end| !rest[..end].contains('{')) {
+            arguments.push(index..index + end + 2);
+        }
+    }
+    arguments
+}
+
+fn translate_value(
+    translator: &SegmentTranslator<'_, impl Inference>,
+    raw: &str,
+) -> Result<String> {
+    let bytes = raw.as_bytes();
+    let arguments = message_arguments(raw);
+    let message_format = !arguments.is_empty();
+    let mut protected = format_specifiers(raw);
+    protected.extend(arguments);
+    let mut index = 0;
+    while index < bytes.len() {
+        if bytes[index] != b'\\' {
+            index += 1;
+            continue;
+        }
+        let rest = &raw[index + 1..];
+        let length = match bytes.get(index + 1) {
+            Some(b't' | b'n' | b'r' | b'f') => 2,
+            Some(b'\r' | b'\n') => {
+                let line_break = if rest.starts_with("\r\n") { 2 } else { 1 };
+                let next = &rest[line_break..];
+                1 + line_break + next.len() - next.trim_start_matches([' ', '\t', '\x0c']).len()
+            }
+            _ => {
+                index += 2;
+                continue;
+            }
+        };
+        protected.push(index..index + length);
+        index += length;
+    }
+    protected.sort_by_key(|range| (range.start, Reverse(range.end)));
+
+    let mut runs = Vec::new();
+    let mut markups = Vec::new();
+    let mut position = 0;
+    for range in protected {
+        if range.start < position {
+            continue;
+        }
+        runs.push(decode(&raw[position..range.start], message_format));
+        markups.push(&raw[range.clone()]);
+        position = range.end;
+    }
+    runs.push(decode(&raw[position..], message_format));
+    if !runs.iter().any(|run| run.contains(char::is_alphabetic)) {
+        return Ok(raw.to_string());
+    }
+
+    let translated = translator.translate_marked(&runs, &markups, "text")?;
+    let mut value = String::with_capacity(raw.len());
+    for (index, run) in translated.iter().enumerate() {
+        if index > 0 {
+            value.push_str(markups[index - 1]);
+        }
+        for (offset, character) in run.char_indices() {
+            match character {
+                ' ' if index == 0 && offset == 0 => value.push_str("\\ "),
+                '\\' => value.push_str("\\\\"),
+                '\n' => value.push_str("\\n"),
+                '\t' => value.push_str("\\t"),
+                '\'' if message_format => value.push_str("''"),
+                _ if character.is_ascii() => value.push(character),
+                _ => {
+                    for unit in character.encode_utf16(&mut [0; 2]) {
+                        value.push_str(&format!("\\u{unit:04X}"));
+                    }
+                }
+            }
+        }
+    }
+    Ok(value)
+}
+
+fn decode(raw: &str, message_format: bool) -> String {
+    let mut text = String::with_capacity(raw.len());
+    // `\uXXXX` escapes are UTF-16 code units; surrogate pairs span two escapes.
+    let mut units = Vec::new();
+    let mut rest = raw;
+    while let Some(character) = rest.chars().next() {
+        if let Some(hex) = rest
+            .strip_prefix("\\u")
+            .and_then(|hex| hex.get(..4))
+            .filter(|hex| hex.chars().all(|c| c.is_ascii_hexdigit()))
+        {
+            units.push(u16::from_str_radix(hex, 16).expect("hex digits were checked"));
+            rest = &rest[6..];
+            continue;
+        }
+        text.extend(
+            char::decode_utf16(units.drain(..))
+                .map(|unit| unit.unwrap_or(char::REPLACEMENT_CHARACTER)),
+        );
+        let length = match character {
+            '\\' => rest[1..].chars().next().map_or(1, |escaped| {
+                text.push(escaped);
+                1 + escaped.len_utf8()
+            }),
+            '\'' if message_format && rest.starts_with("''") => {
+                text.push('\'');
+                2
+            }
+            _ => {
+                text.push(character);
+                character.len_utf8()
+            }
+        };
+        rest = &rest[length..];
+    }
+    text.extend(char::decode_utf16(units).map(|unit| unit.unwrap_or(char::REPLACEMENT_CHARACTER)));
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::translate_properties;
+    use crate::document::testing::translate_with;
+
+    const PHRASES: &[(&str, &str)] = &[
+        ("Öppna", "Ouvrir"),
+        (
+            "Hej {0}, du har {1,number} filer",
+            "Salut {0}, tu as {1,number} fichiers",
+        ),
+        ("Det är {0}s fil", "C'est le fichier de {0}"),
+        ("Rad ett", "Ligne un"),
+        ("rad två", "ligne deux"),
+        ("Spara", "Enregistrer"),
+    ];
+
+    #[test]
+    fn translates_values_with_escapes_and_message_arguments() {
+        let source = "# Meny\nmenu.open = \\u00D6ppna\ngreeting: Hej {0}, du har {1,number} filer\nowner=Det \\u00e4r {0}s fil\nlines = Rad ett\\nrad tv\\u00e5\nlong = Spara \\\n    Spara\nempty =\n";
+
+        let (output, texts) = translate_with(PHRASES, "fr", |translator| {
+            translate_properties(translator, source.as_bytes())
+        });
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "# Meny\nmenu.open = Ouvrir\ngreeting: Salut {0}, tu as {1,number} fichiers\nowner=C''est le fichier de {0}\nlines = Ligne un\\nligne deux\nlong = Enregistrer \\\n    Enregistrer\nempty =\n"
+        );
+        assert_eq!(
+            texts,
+            [
+                "Öppna",
+                "Hej {0}, du har {1,number} filer",
+                "Det är {0}s fil",
+                "Rad ett\\nrad två",
+                "Spara \\\n    Spara"
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_iso_8859_1_and_escapes_translations() {
+        let mut source = b"title = ".to_vec();
+        source.extend([0xD6, b'p', b'p', b'n', b'a', b'\n']);
+
+        let (output, texts) = translate_with(&[("Öppna", "Öffnen")], "de", |translator| {
+            translate_properties(translator, &source)
+        });
+
+        assert_eq!(output, b"title = \\u00D6ffnen\n");
+        assert_eq!(texts, ["Öppna"]);
+    }
+}
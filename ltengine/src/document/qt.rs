@@ -0,0 +1,234 @@
+use std::collections::HashMap;
+use std::ops::Range;
+
+use anyhow::{Result, bail};
+
+use super::gettext::translate_plural;
+use super::segments::SegmentTranslator;
+use super::xml::{self, XmlKind};
+use crate::languages::{language_tag, plural_forms};
+use crate::translation::Inference;
+
+#[derive(Default)]
+struct Message {
+    numerus: bool,
+    source: Option<Range<usize>>,
+    translation: Option<Range<usize>>,
+    /// Text before the `<translation>` tag on its line.
+    indent: String,
+}
+
+/// Fills the empty translations of a Qt Linguist `.ts` file.
+///
+/// Sources are translated with Qt arguments such as `%1` and `%n` protected, and the filled
+/// `<translation>` keeps or gets `type="unfinished"` so it is reviewed in Linguist. Numerus
+/// messages get one `<numerusform>` per target plural form, each translated from a sample
+/// count. Existing, obsolete, and vanished translations are left alone, and the `TS` element's
+/// `language` is set to the target.
+pub fn translate_ts(
+    translator: &SegmentTranslator<'_, impl Inference>,
+    source: &str,
+) -> Result<String> {
+    let tokens = xml::tokenize(source)?;
+    if !tokens
+        .iter()
+        .any(|token| token.kind == XmlKind::Start && token.name == "TS")
+    {
+        bail!("Qt Linguist documents must have a TS root element");
+    }
+    let forms = plural_forms(translator.target());
+
+    let mut translations: HashMap<(bool, String), String> = HashMap::new();
+    let mut edits = Vec::new();
+    let mut message: Option<Message> = None;
+    let mut index = 0;
+    while index < tokens.len() {
+        let token = &tokens[index];
+        index += 1;
+        let tag = &source[token.range.clone()];
+        match (token.kind, token.name) {
+            (XmlKind::Start, "TS") => {
+                let language = language_tag(translator.target()).replace('-', "_");
+                edits.push((
+                    token.range.clone(),
+                    xml::set_attribute(tag, "language", &language),
+                ));
+            }
+            (XmlKind::Start, "message") => {
+                message = Some(Message {
+                    numerus: xml::attribute(tag, "numerus").as_deref() == Some("yes"),
+                    ..Message::default()
+                });
+            }
+            (XmlKind::Start | XmlKind::Empty, name @ ("source" | "translation")) => {
+                let (content, element) = match token.kind {
+                    XmlKind::Empty => (token.range.end..token.range.end, token.range.clone()),
+                    _ => match tokens[index..]
+                        .iter()
+                        .position(|token| token.kind == XmlKind::End && token.name == name)
+                    {
+                        Some(offset) => {
+                            let end = &tokens[index + offset];
+                            index += offset + 1;
+                            (
+                                token.range.end..end.range.start,
+                                token.range.start..end.range.end,
+                            )
+                        }
+                        None => bail!("Malformed Qt Linguist document: unclosed {name} element"),
+                    },
+                };
+                if let Some(message) = &mut message {
+                    if name == "source" {
+                        message.source = Some(content);
+                    } else {
+                        message.translation = Some(element);
+                        message.indent = source[..token.range.start]
+                            .rsplit_once('\n')
+                            .map_or("", |(_, indent)| indent)
+                            .to_string();
+                    }
+                }
+            }
+            (XmlKind::End, "message") => {
+                let Some(Message {
+                    numerus,
+                    source: Some(source_text),
+                    translation: Some(translation),
+                    indent,
+                }) = message.take()
+                else {
+                    continue;
+                };
+                let element = &source[translation.clone()];
+                let start_tag = &element[..element.find('>').expect("the tag is closed") + 1];
+                let filled = xml::tokenize(element)?.iter().any(|token| {
+                    token.kind == XmlKind::Text && !element[token.range.clone()].trim().is_empty()
+                });
+                let kind = xml::attribute(start_tag, "type");
+                if filled || matches!(kind.as_deref(), Some("obsolete" | "vanished")) {
+                    continue;
+                }
+
+                let text = xml::unescape(&source[source_text]);
+                if !text.contains(char::is_alphabetic) {
+                    continue;
+                }
+                let content = match translations.get(&(numerus, text.clone())) {
+                    Some(content) => content.clone(),
+                    None => {
+                        let content = if numerus {
+                            let mut content = String::new();
+                            for &count in forms.samples {
+                                let form =
+                                    translate_plural(translator, &text, count, qt_arguments)?;
+                                content.push_str(&format!(
+                                    "\n{indent}    <numerusform>{}</numerusform>",
+                                    xml::escape_text(&form)
+                                ));
+                            }
+                            content + "\n" + &indent
+                        } else {
+                            xml::escape_text(&translator.translate_protected(
+                                &text,
+                                &qt_arguments(&text),
+                                "text",
+                            )?)
+                        };
+                        translations.insert((numerus, text), content.clone());
+                        content
+                    }
+                };
+                let open = start_tag
+                    .strip_suffix("/>")
+                    .map_or(start_tag.to_string(), |open| {
+                        format!("{}>", open.trim_end())
+                    });
+                let open = xml::set_attribute(&open, "type", "unfinished");
+                edits.push((translation, format!("{open}{content}</translation>")));
+            }
+            _ => {}
+        }
+    }
+    Ok(xml::apply_edits(source, edits))
+}
+
+/// Finds Qt string arguments: `%1` to `%99`, `%n`, and their localized `%L1` and `%Ln` forms.
+fn qt_arguments(text: &str) -> Vec<Range<usize>> {
+    text.match_indices('%')
+        .filter_map(|(index, _)| {
+            let rest = &text[index + 1..];
+            let localized = usize::from(rest.starts_with('L'));
+            let rest = &rest[localized..];
+            let digits = rest.bytes().take_while(u8::is_ascii_digit).count().min(2);
+            if rest.starts_with('n') {
+                Some(index..index + localized + 2)
+            } else {
+                (digits > 0).then_some(index..index + localized + digits + 1)
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::translate_ts;
+    use crate::document::testing::translate_with;
+
+    const PHRASES: &[(&str, &str)] = &[
+        ("Öppna %1", "Открыть %1"),
+        ("Spara", "Сохранить"),
+        ("1 filer", "1 файл"),
+        ("2 filer", "2 файла"),
+        ("5 filer", "5 файлов"),
+    ];
+
+    #[test]
+    fn fills_unfinished_translations_and_numerus_forms() {
+        let source = r#"<?xml version="1.0" encoding="utf-8"?>
+<!DOCTYPE TS>
+<TS version="2.1" language="en_US">
+<context>
+    <name>MainWindow</name>
+    <message>
+        <location filename="../mainwindow.cpp" line="12"/>
+        <source>Öppna %1</source>
+        <translation type="unfinished"></translation>
+    </message>
+    <message>
+        <source>Spara</source>
+        <translation>Записать</translation>
+    </message>
+    <message>
+        <source>Spara</source>
+        <translation type="vanished"></translation>
+    </message>
+    <message numerus="yes">
+        <source>%n filer</source>
+        <translation type="unfinished">
+            <numerusform></numerusform>
+        </translation>
+    </message>
+</context>
+</TS>
+"#;
+
+        let (output, texts) =
+            translate_with(PHRASES, "ru", |translator| translate_ts(translator, source));
+
+        assert_eq!(
+            output,
+            source
+                .replace("language=\"en_US\"", "language=\"ru\"")
+                .replace(
+                    "<translation type=\"unfinished\"></translation>",
+                    "<translation type=\"unfinished\">Открыть %1</translation>"
+                )
+                .replace(
+                    "<translation type=\"unfinished\">\n            <numerusform></numerusform>\n",
+                    "<translation type=\"unfinished\">\n            <numerusform>%n файл</numerusform>\n            <numerusform>%n файла</numerusform>\n            <numerusform>%n файлов</numerusform>\n"
+                )
+        );
+        assert_eq!(texts, ["Öppna %1", "1 filer", "2 filer", "5 filer"]);
+    }
+}
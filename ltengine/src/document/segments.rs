@@ -0,0 +1,222 @@
+use std::ops::Range;
+
+use anyhow::Result;
+
+use super::journal::Journal;
+use crate::icu;
+use crate::segmentation::split_sentences;
+use crate::translation::{Inference, TranslationRequest, translate};
+
+pub const DEFAULT_MAX_SLICE_TOKENS: usize = 1024;
+
+pub struct SegmentTranslator<'a, I: Inference> {
+    inference: &'a I,
+    source: &'a str,
+    target: &'a str,
+    max_slice_tokens: usize,
+    journal: Option<&'a Journal>,
+}
+
+impl<'a, I: Inference> SegmentTranslator<'a, I> {
+    pub fn new(
+        inference: &'a I,
+        source: &'a str,
+        target: &'a str,
+        max_slice_tokens: usize,
+    ) -> Self {
+        Self {
+            inference,
+            source,
+            target,
+            max_slice_tokens: max_slice_tokens.max(1),
+            journal: None,
+        }
+    }
+
+    /// Records each translated segment in `journal` and replays the ones it already holds.
+    pub fn with_journal(self, journal: &'a Journal) -> Self {
+        Self {
+            journal: Some(journal),
+            ..self
+        }
+    }
+
+    pub fn source(&self) -> &str {
+        self.source
+    }
+
+    pub fn target(&self) -> &str {
+        self.target
+    }
+
+    pub fn max_slice_tokens(&self) -> usize {
+        self.max_slice_tokens
+    }
+
+    /// Translates one segment, keeping its leading and trailing whitespace out of the prompt.
+    ///
+    /// A segment over the token budget is sent in groups of whole sentences; ICU messages are
+    /// always sent whole.
+    pub fn translate(&self, text: &str, format: &str) -> Result<String> {
+        let content_start = text.len() - text.trim_start_matches(char::is_whitespace).len();
+        let content_end = text.trim_end_matches(char::is_whitespace).len();
+        if content_start >= content_end {
+            return Ok(text.to_string());
+        }
+
+        let content = &text[content_start..content_end];
+        let translated =
+            if estimate_tokens(content) > self.max_slice_tokens && icu::parse(content).is_none() {
+                self.translate_sentences(content, format)?
+            } else {
+                self.send(content, format)?
+            };
+        Ok(text[..content_start].to_string() + &translated + &text[content_end..])
+    }
+
+    /// Translates an oversized segment in groups of whole sentences that fit the token budget,
+    /// keeping the whitespace between the groups.
+    ///
+    /// A single sentence larger than the budget is sent on its own rather than cut in half.
+    fn translate_sentences(&self, content: &str, format: &str) -> Result<String> {
+        let mut translated = String::new();
+        let mut position = 0;
+        let mut chunk: Option<Range<usize>> = None;
+        for sentence in split_sentences(content, self.source) {
+            if let Some(current) = &mut chunk {
+                if estimate_tokens(&content[current.start..sentence.end]) <= self.max_slice_tokens {
+                    current.end = sentence.end;
+                    continue;
+                }
+                translated.push_str(&content[position..current.start]);
+                translated.push_str(&self.send(&content[current.clone()], format)?);
+                position = current.end;
+            }
+            chunk = Some(sentence);
+        }
+        if let Some(current) = chunk {
+            translated.push_str(&content[position..current.start]);
+            translated.push_str(&self.send(&content[current.clone()], format)?);
+            position = current.end;
+        }
+        translated.push_str(&content[position..]);
+        Ok(translated)
+    }
+
+    /// Sends one trimmed segment to the model, or replays its translation from the journal.
+    fn send(&self, content: &str, format: &str) -> Result<String> {
+        let run = || {
+            let request = TranslationRequest {
+                text: content,
+                source: self.source,
+                target: self.target,
+                format,
+            };
+            Ok(translate(self.inference, request)?.text)
+        };
+        match self.journal {
+            Some(journal) => journal.translate(content, run),
+            None => run(),
+        }
+    }
+
+    /// Translates text runs separated by markup that the model must not change.
+    ///
+    /// The runs are sent together with numbered placeholders standing in for the markup so the
+    /// model sees whole sentences. Returns one translation per run.
+    pub fn translate_runs<S: AsRef<str>>(&self, runs: &[S], format: &str) -> Result<Vec<String>> {
+        let placeholders: Vec<String> = (1..runs.len()).map(placeholder).collect();
+        self.translate_marked(runs, &placeholders, format)
+    }
+
+    /// Translates text whose placeholder ranges, such as format specifiers, must come back
+    /// unchanged. The placeholders are shown to the model verbatim. ICU plural and select
+    /// messages are passed whole to the translation core, which keeps their arguments.
+    pub fn translate_protected(
+        &self,
+        text: &str,
+        placeholders: &[Range<usize>],
+        format: &str,
+    ) -> Result<String> {
+        if icu::parse(text).is_some() {
+            return self.translate(text, format);
+        }
+        let mut runs = Vec::with_capacity(placeholders.len() + 1);
+        let mut markups = Vec::with_capacity(placeholders.len());
+        let mut position = 0;
+        for range in placeholders {
+            runs.push(&text[position..range.start]);
+            markups.push(&text[range.clone()]);
+            position = range.end;
+        }
+        runs.push(&text[position..]);
+
+        let translated = self.translate_marked(&runs, &markups, format)?;
+        let mut joined = translated[0].clone();
+        for (markup, run) in markups.iter().zip(&translated[1..]) {
+            joined.push_str(markup);
+            joined.push_str(run);
+        }
+        Ok(joined)
+    }
+
+    /// Translates text runs with the given markup between them shown to the model verbatim.
+    ///
+    /// When the markup does not come back exactly once and in order, or new markup appears,
+    /// each run is translated on its own instead. Returns one translation per run.
+    pub fn translate_marked<S: AsRef<str>, M: AsRef<str>>(
+        &self,
+        runs: &[S],
+        markups: &[M],
+        format: &str,
+    ) -> Result<Vec<String>> {
+        debug_assert_eq!(runs.len(), markups.len() + 1);
+        if let [run] = runs {
+            return Ok(vec![self.translate(run.as_ref(), format)?]);
+        }
+
+        let markers: Vec<char> = markups
+            .iter()
+            .filter_map(|markup| markup.as_ref().chars().next())
+            .collect();
+        let has_marker = |run: &str| run.contains(markers.as_slice());
+        if !runs.iter().any(|run| has_marker(run.as_ref())) {
+            let mut joined = runs[0].as_ref().to_string();
+            for (markup, run) in markups.iter().zip(&runs[1..]) {
+                joined.push_str(markup.as_ref());
+                joined.push_str(run.as_ref());
+            }
+            let translated = self.translate(&joined, format)?;
+            if let Some(split) = split_markup(&translated, markups)
+                .filter(|split| !split.iter().any(|run| has_marker(run)))
+            {
+                return Ok(split);
+            }
+        }
+
+        runs.iter()
+            .map(|run| self.translate(run.as_ref(), format))
+            .collect()
+    }
+}
+
+fn placeholder(index: usize) -> String {
+    format!("⟦{index}⟧")
+}
+
+fn split_markup<M: AsRef<str>>(text: &str, markups: &[M]) -> Option<Vec<String>> {
+    let mut split = Vec::with_capacity(markups.len() + 1);
+    let mut rest = text;
+    for markup in markups {
+        let (run, after) = rest.split_once(markup.as_ref())?;
+        split.push(run.to_string());
+        rest = after;
+    }
+    split.push(rest.to_string());
+    Some(split)
+}
+
+/// Conservative token estimate; the inference boundary does not expose the model tokenizer.
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(3)
+}
@@ -0,0 +1,403 @@
+use std::ops::Range;
+
+use anyhow::{Result, bail};
+
+use super::html::{decode_entities, escape_text};
+use super::segments::{SegmentTranslator, estimate_tokens};
+use crate::translation::Inference;
+
+/// Upper bound on cues per context window so placeholders stay easy for the model to keep.
+const MAX_WINDOW_CUES: usize = 20;
+const WEBVTT_BLOCKS: &[&str] = &["WEBVTT", "NOTE", "STYLE", "REGION"];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SubtitleFormat {
+    Srt,
+    WebVtt,
+}
+
+struct Cue<'a> {
+    /// Source range of the cue text, from the first text line to the end of the last one.
+    text: Range<usize>,
+    lines: usize,
+    parts: Vec<Part<'a>>,
+}
+
+enum Part<'a> {
+    Text(String),
+    Tag(&'a str),
+}
+
+/// Translates the cue text of an SRT or WebVTT document.
+///
+/// Indices, timestamps, cue settings, comments, styles, and styling tags are copied unchanged.
+/// Consecutive cues are sent together in context windows, and each translated cue is wrapped to
+/// its original number of lines.
+pub fn translate_subtitles(
+    translator: &SegmentTranslator<'_, impl Inference>,
+    source: &str,
+    format: SubtitleFormat,
+) -> Result<String> {
+    if format == SubtitleFormat::WebVtt
+        && !source.trim_start_matches('\u{feff}').starts_with("WEBVTT")
+    {
+        bail!("WebVTT document must start with a WEBVTT header");
+    }
+    let cues = parse_cues(source, format);
+    if cues.is_empty() {
+        bail!("Subtitle document contains no cues");
+    }
+
+    let mut output = String::with_capacity(source.len());
+    let mut position = 0;
+    for window in windows(&cues, translator.max_slice_tokens()) {
+        let translated = translate_window(translator, &cues[window.clone()], format)?;
+        for (cue, text) in cues[window].iter().zip(translated) {
+            output.push_str(&source[position..cue.text.start]);
+            output.push_str(&text);
+            position = cue.text.end;
+        }
+    }
+    output.push_str(&source[position..]);
+    Ok(output)
+}
+
+fn parse_cues(source: &str, format: SubtitleFormat) -> Vec<Cue<'_>> {
+    let mut cues = Vec::new();
+    let mut block: Vec<Range<usize>> = Vec::new();
+    let mut offset = 0;
+    for line in source.split_inclusive('\n') {
+        let range = offset..offset + line.len();
+        offset = range.end;
+        if line.trim().is_empty() {
+            cues.extend(parse_cue(source, &block, format));
+            block.clear();
+        } else {
+            block.push(range);
+        }
+    }
+    cues.extend(parse_cue(source, &block, format));
+    cues
+}
+
+fn parse_cue<'a>(
+    source: &'a str,
+    block: &[Range<usize>],
+    format: SubtitleFormat,
+) -> Option<Cue<'a>> {
+    let first = source[block.first()?.clone()].trim_start_matches('\u{feff}');
+    if format == SubtitleFormat::WebVtt
+        && WEBVTT_BLOCKS
+            .iter()
+            .any(|keyword| first.starts_with(keyword))
+    {
+        return None;
+    }
+    // The timing line follows an optional index (SRT) or identifier (WebVTT).
+    let timing = block
+        .iter()
+        .take(2)
+        .position(|line| source[line.clone()].contains("-->"))?;
+    let lines = &block[timing + 1..];
+    let (first, last) = (lines.first()?, lines.last()?);
+
+    let text =
+        first.start..first.start + source[first.start..last.end].trim_end_matches('\n').len();
+    Some(Cue {
+        parts: split_tags(&source[text.clone()]),
+        text,
+        lines: lines.len(),
+    })
+}
+
+/// Splits cue text into styling tags such as `<i>`, `<v Name>`, or `{\an8}` and the text between
+/// them, with line breaks replaced by spaces.
+fn split_tags(text: &str) -> Vec<Part<'_>> {
+    let mut parts = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find(['<', '{']) {
+        let tag_end = match rest[start..].split_at(1) {
+            ("<", after) if after.starts_with(|c: char| c.is_alphanumeric() || c == '/') => {
+                after.find('>')
+            }
+            ("{", after) if after.starts_with('\\') => after.find('}'),
+            _ => None,
+        };
+        let Some(tag_end) = tag_end else {
+            push_text(&mut parts, &rest[..start + 1]);
+            rest = &rest[start + 1..];
+            continue;
+        };
+        push_text(&mut parts, &rest[..start]);
+        let end = start + tag_end + 2;
+        parts.push(Part::Tag(&rest[start..end]));
+        rest = &rest[end..];
+    }
+    push_text(&mut parts, rest);
+    parts
+}
+
+fn push_text(parts: &mut Vec<Part<'_>>, text: &str) {
+    if text.is_empty() {
+        return;
+    }
+    let text = text.replace('\n', " ");
+    match parts.last_mut() {
+        Some(Part::Text(previous)) => previous.push_str(&text),
+        _ => parts.push(Part::Text(text)),
+    }
+}
+
+/// Groups consecutive cues within the token budget, ending a window after a cue that closes a
+/// sentence when one is available.
+fn windows(cues: &[Cue<'_>], max_tokens: usize) -> Vec<Range<usize>> {
+    let tokens: Vec<usize> = cues
+        .iter()
+        .map(|cue| estimate_tokens(&visible_text(cue)))
+        .collect();
+    let mut windows = Vec::new();
+    let mut start = 0;
+    while start < cues.len() {
+        let mut end = start + 1;
+        let mut total = tokens[start];
+        while end < cues.len() && end - start < MAX_WINDOW_CUES && total + tokens[end] <= max_tokens
+        {
+            total += tokens[end];
+            end += 1;
+        }
+        if end < cues.len()
+            && let Some(last) = (start..end)
+                .rev()
+                .find(|&index| ends_sentence(&cues[index]))
+        {
+            end = last + 1;
+        }
+        windows.push(start..end);
+        start = end;
+    }
+    windows
+}
+
+fn visible_text(cue: &Cue<'_>) -> String {
+    cue.parts
+        .iter()
+        .filter_map(|part| match part {
+            Part::Text(text) => Some(text.as_str()),
+            Part::Tag(_) => None,
+        })
+        .collect()
+}
+
+fn ends_sentence(cue: &Cue<'_>) -> bool {
+    visible_text(cue)
+        .trim_end()
+        .trim_end_matches(['"', '\'', '”', '’', '»', ')'])
+        .ends_with(['.', '!', '?', '…', '。', '！', '？'])
+}
+
+/// Translates a window of cues in one prompt, with cue boundaries and tags as placeholders.
+fn translate_window(
+    translator: &SegmentTranslator<'_, impl Inference>,
+    cues: &[Cue<'_>],
+    format: SubtitleFormat,
+) -> Result<Vec<String>> {
+    let mut runs = Vec::new();
+    for cue in cues {
+        runs.push(String::new());
+        for part in &cue.parts {
+            match part {
+                Part::Text(text) => runs.last_mut().expect("cue run exists").push_str(text),
+                Part::Tag(_) => runs.push(String::new()),
+            }
+        }
+    }
+
+    if format == SubtitleFormat::WebVtt {
+        runs = runs.iter().map(|run| decode_entities(run)).collect();
+    }
+    let mut translated = translator.translate_runs(&runs, "text")?.into_iter();
+    let mut texts = Vec::with_capacity(cues.len());
+    for cue in cues {
+        let mut pieces = vec![Piece::Text(translated.next().expect("run per cue"))];
+        for part in &cue.parts {
+            if let Part::Tag(tag) = part {
+                pieces.push(Piece::Tag(tag));
+                pieces.push(Piece::Text(translated.next().expect("run per tag")));
+            }
+        }
+        if format == SubtitleFormat::WebVtt {
+            for piece in &mut pieces {
+                if let Piece::Text(text) = piece {
+                    *text = escape_text(text);
+                }
+            }
+        }
+        texts.push(layout(&pieces, cue.lines));
+    }
+    Ok(texts)
+}
+
+enum Piece<'a> {
+    Text(String),
+    Tag(&'a str),
+}
+
+/// Joins translated text and tags, collapsing whitespace and breaking the text into `lines` lines
+/// of similar visible length.
+fn layout(pieces: &[Piece<'_>], lines: usize) -> String {
+    let mut output = String::new();
+    // Byte offset and visible characters before each single space outside a tag.
+    let mut spaces: Vec<(usize, usize)> = Vec::new();
+    let mut visible = 0;
+    let mut after_space = true;
+    for piece in pieces {
+        match piece {
+            Piece::Tag(tag) => output.push_str(tag),
+            Piece::Text(text) => {
+                for character in text.chars() {
+                    if character.is_whitespace() {
+                        if !after_space {
+                            spaces.push((output.len(), visible));
+                            output.push(' ');
+                            visible += 1;
+                            after_space = true;
+                        }
+                    } else {
+                        output.push(character);
+                        visible += 1;
+                        after_space = false;
+                    }
+                }
+            }
+        }
+    }
+    if after_space && let Some((offset, _)) = spaces.pop() {
+        output.remove(offset);
+        visible -= 1;
+    }
+
+    let mut breaks = Vec::new();
+    let mut candidates = spaces.as_slice();
+    for line in 1..lines {
+        let target = visible * line / lines;
+        let Some(best) =
+            (0..candidates.len()).min_by_key(|&index| candidates[index].1.abs_diff(target))
+        else {
+            break;
+        };
+        breaks.push(candidates[best].0);
+        candidates = &candidates[best + 1..];
+    }
+    for offset in breaks.into_iter().rev() {
+        output.replace_range(offset..offset + 1, "\n");
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SubtitleFormat, translate_subtitles};
+    use crate::document::segments::SegmentTranslator;
+    use crate::document::testing::{FakeInference, translate_with};
+
+    const PHRASES: &[(&str, &str)] = &[
+        ("Vi ses", "See you"),
+        ("i morgon", "tomorrow"),
+        ("Hej", "Hello"),
+        ("allihop", "everyone"),
+        ("Kom in", "Come in"),
+        ("och stäng dörren", "and close the door"),
+        ("Tack", "Thanks"),
+        ("Ost & bröd", "Cheese & bread"),
+    ];
+
+    fn translate(source: &str, format: SubtitleFormat) -> (String, Vec<String>) {
+        translate_with(PHRASES, "en", |translator| {
+            translate_subtitles(translator, source, format)
+        })
+    }
+
+    #[test]
+    fn keeps_srt_indices_timing_tags_and_line_counts() {
+        let source = "1\n00:00:01,000 --> 00:00:02,500\n{\\an8}<i>Hej</i>\nallihop!\n\n2\n00:00:03,000 --> 00:00:04,000 X1:10 X2:20\nTack.\n";
+
+        let (output, texts) = translate(source, SubtitleFormat::Srt);
+
+        assert_eq!(
+            output,
+            "1\n00:00:01,000 --> 00:00:02,500\n{\\an8}<i>Hello</i>\neveryone!\n\n2\n00:00:03,000 --> 00:00:04,000 X1:10 X2:20\nThanks.\n"
+        );
+        assert_eq!(texts, ["⟦1⟧⟦2⟧Hej⟦3⟧ allihop!⟦4⟧Tack."]);
+    }
+
+    #[test]
+    fn sends_sentences_across_cues_in_one_window() {
+        let source = "1\n00:00:01,000 --> 00:00:02,000\nKom in\n\n2\n00:00:02,000 --> 00:00:03,000\noch stäng dörren.\n\n3\n00:00:04,000 --> 00:00:05,000\nVi ses\ni morgon.\n";
+
+        let inference = FakeInference::replacing(PHRASES);
+        let translator = SegmentTranslator::new(&inference, "sv", "en", 8);
+
+        let output = translate_subtitles(&translator, source, SubtitleFormat::Srt)
+            .expect("translation should succeed");
+
+        assert_eq!(
+            output,
+            "1\n00:00:01,000 --> 00:00:02,000\nCome in\n\n2\n00:00:02,000 --> 00:00:03,000\nand close the door.\n\n3\n00:00:04,000 --> 00:00:05,000\nSee you\ntomorrow.\n"
+        );
+        assert_eq!(
+            inference.texts.take(),
+            ["Kom in⟦1⟧och stäng dörren.", "Vi ses i morgon."]
+        );
+    }
+
+    #[test]
+    fn keeps_webvtt_header_notes_styles_and_cue_settings() {
+        let source = "WEBVTT - Demo\n\nSTYLE\n::cue(.x) { color: red }\n\nNOTE Hej\n\nintro\n00:01.000 --> 00:02.000 align:start line:0\n<v Anna>Ost &amp; bröd</v>\n";
+
+        let (output, texts) = translate(source, SubtitleFormat::WebVtt);
+
+        assert_eq!(
+            output,
+            "WEBVTT - Demo\n\nSTYLE\n::cue(.x) { color: red }\n\nNOTE Hej\n\nintro\n00:01.000 --> 00:02.000 align:start line:0\n<v Anna>Cheese &amp; bread</v>\n"
+        );
+        assert_eq!(texts, ["⟦1⟧Ost & bröd⟦2⟧"]);
+    }
+
+    #[test]
+    fn translates_cues_separately_when_the_model_drops_a_boundary() {
+        let inference = FakeInference::new(|text| {
+            Ok(text
+                .replace("⟦1⟧", " ")
+                .replace("Kom in", "Come in")
+                .replace("Tack", "Thanks"))
+        });
+        let translator = SegmentTranslator::new(&inference, "sv", "en", 1024);
+        let source =
+            "1\n00:00:01,000 --> 00:00:02,000\nKom in\n\n2\n00:00:03,000 --> 00:00:04,000\nTack\n";
+
+        let output = translate_subtitles(&translator, source, SubtitleFormat::Srt)
+            .expect("translation should succeed");
+
+        assert_eq!(
+            output,
+            "1\n00:00:01,000 --> 00:00:02,000\nCome in\n\n2\n00:00:03,000 --> 00:00:04,000\nThanks\n"
+        );
+        assert_eq!(inference.calls(), 3);
+    }
+
+    #[test]
+    fn rejects_webvtt_without_header() {
+        let inference = FakeInference::replacing(PHRASES);
+        let translator = SegmentTranslator::new(&inference, "sv", "en", 1024);
+
+        let error = translate_subtitles(
+            &translator,
+            "00:01.000 --> 00:02.000\nHej\n",
+            SubtitleFormat::WebVtt,
+        )
+        .expect_err("missing header must fail");
+
+        assert!(error.to_string().contains("WEBVTT"));
+        assert_eq!(inference.calls(), 0);
+    }
+}
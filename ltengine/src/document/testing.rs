@@ -0,0 +1,107 @@
+use std::cell::RefCell;
+use std::io::{Cursor, Read, Write};
+
+use anyhow::Result;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::document::segments::SegmentTranslator;
+use crate::translation::Inference;
+
+type Respond = Box<dyn Fn(&str) -> Result<String>>;
+
+/// Inference double that answers with a function of the source text embedded in the prompt.
+pub struct FakeInference {
+    respond: Respond,
+    pub texts: RefCell<Vec<String>>,
+}
+
+impl FakeInference {
+    pub fn new(respond: impl Fn(&str) -> Result<String> + 'static) -> Self {
+        Self {
+            respond: Box::new(respond),
+            texts: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Translates by replacing each source phrase with its target phrase.
+    pub fn replacing(phrases: &'static [(&'static str, &'static str)]) -> Self {
+        Self::new(|text| {
+            Ok(phrases
+                .iter()
+                .fold(text.to_string(), |text, (source, target)| {
+                    text.replace(source, target)
+                }))
+        })
+    }
+
+    pub fn calls(&self) -> usize {
+        self.texts.borrow().len()
+    }
+}
+
+impl Inference for FakeInference {
+    fn run_prompt(&self, _system: String, user: String) -> Result<String> {
+        let text = prompt_text(&user);
+        self.texts.borrow_mut().push(text.clone());
+        (self.respond)(&text)
+    }
+}
+
+/// Runs `translate` with a Swedish to `target` translator that replaces `phrases`, returning
+/// its output and the texts sent to the model.
+pub fn translate_with<T>(
+    phrases: &'static [(&'static str, &'static str)],
+    target: &str,
+    translate: impl FnOnce(&SegmentTranslator<FakeInference>) -> Result<T>,
+) -> (T, Vec<String>) {
+    let inference = FakeInference::replacing(phrases);
+    let translator = SegmentTranslator::new(&inference, "sv", target, 1024);
+    let output = translate(&translator).expect("translation should succeed");
+    (output, inference.texts.take())
+}
+
+fn prompt_text(user: &str) -> String {
+    let (_, body) = user
+        .split_once("\n\n")
+        .expect("prompt must contain an instruction");
+    let (_, text) = body.split_once(": ").expect("prompt must label the text");
+    let (text, _) = text
+        .rsplit_once("\n\n")
+        .expect("prompt must end with a target label");
+    text.to_string()
+}
+
+/// Builds a ZIP package; the first entry is stored and the rest are deflated, as in ODT and EPUB.
+pub fn zip_package(files: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    for (index, (name, contents)) in files.iter().enumerate() {
+        let method = if index == 0 {
+            CompressionMethod::Stored
+        } else {
+            CompressionMethod::Deflated
+        };
+        writer
+            .start_file(
+                *name,
+                SimpleFileOptions::default().compression_method(method),
+            )
+            .expect("entry should start");
+        writer.write_all(contents).expect("entry should be written");
+    }
+    writer.finish().expect("package should finish").into_inner()
+}
+
+/// Returns the entry names and contents of a ZIP package in archive order.
+pub fn zip_entries(bytes: &[u8]) -> Vec<(String, Vec<u8>)> {
+    let mut archive = ZipArchive::new(Cursor::new(bytes)).expect("package should open");
+    (0..archive.len())
+        .map(|index| {
+            let mut file = archive.by_index(index).expect("entry should open");
+            let mut contents = Vec::new();
+            file.read_to_end(&mut contents)
+                .expect("entry should be read");
+            (file.name().to_string(), contents)
+        })
+        .collect()
+}
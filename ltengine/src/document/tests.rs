@@ -0,0 +1,554 @@
+use std::cell::{Cell, RefCell};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use anyhow::{Result, anyhow};
+
+use super::batch::{BatchOutcome, BatchSummary};
+use super::testing::{FakeInference, zip_entries, zip_package};
+use super::{
+    DEFAULT_NAME_TEMPLATE, DocumentRequest, translate_directory, translate_document,
+    translate_document_to_stdout,
+};
+use crate::translation::Inference;
+
+struct TestDirectory(PathBuf);
+
+impl TestDirectory {
+    fn new() -> Self {
+        static NEXT_DIRECTORY: AtomicU64 = AtomicU64::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "ltengine-document-{}-{}",
+            std::process::id(),
+            NEXT_DIRECTORY.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::create_dir(&path).expect("test directory should be created");
+        Self(path)
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.0.join(name)
+    }
+}
+
+impl Drop for TestDirectory {
+    fn drop(&mut self) {
+        fs::remove_dir_all(&self.0).expect("test directory should be removed");
+    }
+}
+
+struct ControlledInference {
+    calls: Cell<usize>,
+    response: RefCell<Option<Result<String>>>,
+}
+
+impl ControlledInference {
+    fn returning(response: &str) -> Self {
+        Self {
+            calls: Cell::new(0),
+            response: RefCell::new(Some(Ok(response.to_string()))),
+        }
+    }
+
+    fn unused() -> Self {
+        Self {
+            calls: Cell::new(0),
+            response: RefCell::new(None),
+        }
+    }
+}
+
+impl Inference for ControlledInference {
+    fn run_prompt(&self, _system: String, _user: String) -> Result<String> {
+        self.calls.set(self.calls.get() + 1);
+        self.response
+            .borrow_mut()
+            .take()
+            .expect("controlled response must be configured")
+    }
+}
+
+fn request<'a>(input: &'a Path, output: &'a Path) -> DocumentRequest<'a> {
+    DocumentRequest {
+        input,
+        output,
+        max_input_bytes: 100,
+        max_slice_tokens: 1024,
+        source: "sv",
+        target: "en",
+        include: &[],
+        exclude: &[],
+        columns: &[],
+        replace_columns: false,
+        rename_language_key: false,
+        input_encoding: None,
+        output_encoding: None,
+        resume: false,
+        output_template: None,
+        force: false,
+        bilingual: None,
+        progress: &|_| {},
+    }
+}
+
+fn translate(
+    input: &Path,
+    output: &Path,
+    max_input_bytes: u64,
+    inference: &ControlledInference,
+) -> Result<()> {
+    translate_document(
+        inference,
+        &DocumentRequest {
+            max_input_bytes,
+            ..request(input, output)
+        },
+    )
+}
+
+#[test]
+fn translates_swedish_multiline_document_to_selected_path() {
+    let directory = TestDirectory::new();
+    let input = directory.path("source.txt");
+    let output = directory.path("translated.txt");
+    fs::write(&input, "Första stycket.\n\nAndra stycket.\n").expect("fixture should be written");
+    let inference = ControlledInference::returning("First paragraph.\n\nSecond paragraph.\n");
+
+    translate(&input, &output, 10 * 1024 * 1024, &inference)
+        .expect("document translation should succeed");
+
+    assert_eq!(
+        fs::read_to_string(output).expect("output should be readable"),
+        "First paragraph.\n\nSecond paragraph.\n"
+    );
+    assert_eq!(inference.calls.get(), 1);
+}
+
+#[test]
+fn identity_translation_preserves_document_boundary_whitespace() {
+    let directory = TestDirectory::new();
+    let input = directory.path("source.txt");
+    let output = directory.path("translated.txt");
+    let source = "  Rubrik\n\nText  \n";
+    fs::write(&input, source).expect("fixture should be written");
+    let inference = ControlledInference::unused();
+
+    translate_document(
+        &inference,
+        &DocumentRequest {
+            target: "sv",
+            ..request(&input, &output)
+        },
+    )
+    .expect("identity document translation should succeed");
+
+    assert_eq!(fs::read_to_string(output).unwrap(), source);
+    assert_eq!(inference.calls.get(), 0);
+}
+
+#[test]
+fn rejects_document_over_configured_byte_limit() {
+    let directory = TestDirectory::new();
+    let input = directory.path("source.txt");
+    let output = directory.path("translated.txt");
+    fs::write(&input, "123456").expect("fixture should be written");
+    let inference = ControlledInference::unused();
+
+    let error =
+        translate(&input, &output, 5, &inference).expect_err("oversized document must fail");
+
+    assert!(error.to_string().contains("exceeds the 5-byte limit"));
+    assert!(!output.exists());
+    assert_eq!(inference.calls.get(), 0);
+}
+
+#[test]
+fn rejects_invalid_utf8_without_creating_output() {
+    let directory = TestDirectory::new();
+    let input = directory.path("source.txt");
+    let output = directory.path("translated.txt");
+    fs::write(&input, [0xff, 0xfe]).expect("fixture should be written");
+    let inference = ControlledInference::unused();
+
+    let error = translate_document(
+        &inference,
+        &DocumentRequest {
+            max_input_bytes: 10,
+            input_encoding: Some("utf-8"),
+            ..request(&input, &output)
+        },
+    )
+    .expect_err("invalid UTF-8 must fail");
+
+    assert!(format!("{error:#}").contains("valid UTF-8"));
+    assert!(!output.exists());
+    assert_eq!(inference.calls.get(), 0);
+}
+
+#[test]
+fn decodes_legacy_encodings_and_writes_the_same_encoding() {
+    let directory = TestDirectory::new();
+    let input = directory.path("source.txt");
+    let output = directory.path("translated.txt");
+    fs::write(&input, b"K\xF6p \x93sm\xF6r\x94.").expect("fixture should be written");
+    let inference = ControlledInference::returning("Buy “butter” à la carte.");
+
+    translate_document(
+        &inference,
+        &DocumentRequest {
+            output_encoding: Some("same"),
+            ..request(&input, &output)
+        },
+    )
+    .expect("windows-1252 translation should succeed");
+
+    assert_eq!(
+        fs::read(&output).unwrap(),
+        b"Buy \x93butter\x94 \xE0 la carte."
+    );
+}
+
+#[test]
+fn restores_byte_order_mark_and_mixed_line_endings() {
+    let directory = TestDirectory::new();
+    let input = directory.path("source.txt");
+    let output = directory.path("translated.txt");
+    fs::write(&input, "\u{feff}Hej.\r\nRad två.\n\r\nTack.\r\n")
+        .expect("fixture should be written");
+    let inference = ControlledInference::returning("Hello.\nLine two.\n\nThanks.");
+
+    translate(&input, &output, 100, &inference).expect("translation should succeed");
+
+    assert_eq!(
+        fs::read_to_string(&output).unwrap(),
+        "\u{feff}Hello.\r\nLine two.\n\r\nThanks.\r\n"
+    );
+}
+
+#[test]
+fn refuses_to_overwrite_existing_output() {
+    let directory = TestDirectory::new();
+    let input = directory.path("source.txt");
+    let output = directory.path("translated.txt");
+    fs::write(&input, "Hej.").expect("fixture should be written");
+    fs::write(&output, "keep me").expect("existing output should be written");
+    let inference = ControlledInference::unused();
+
+    let error = translate(&input, &output, 10, &inference)
+        .expect_err("existing output must not be overwritten");
+
+    assert!(error.to_string().contains("already exists"));
+    assert_eq!(fs::read_to_string(output).unwrap(), "keep me");
+    assert_eq!(inference.calls.get(), 0);
+}
+
+#[test]
+fn rejects_same_input_and_output_path() {
+    let directory = TestDirectory::new();
+    let input = directory.path("source.txt");
+    fs::write(&input, "Hej.").expect("fixture should be written");
+    let inference = ControlledInference::unused();
+
+    let error = translate(&input, &input, 10, &inference)
+        .expect_err("input path must not be used as output");
+
+    assert!(error.to_string().contains("same file"));
+    assert_eq!(fs::read_to_string(input).unwrap(), "Hej.");
+    assert_eq!(inference.calls.get(), 0);
+}
+
+#[test]
+fn rejects_unsupported_input() {
+    let directory = TestDirectory::new();
+    let input = directory.path("source.pdf");
+    let output = directory.path("translated.txt");
+    fs::write(&input, "Hej.").expect("fixture should be written");
+    let inference = ControlledInference::unused();
+
+    let error =
+        translate(&input, &output, 10, &inference).expect_err("unsupported input must fail");
+
+    assert!(error.to_string().contains(
+        "Accepted extensions: .txt, .md, .html, .htm, .srt, .vtt, .docx, .odt, .epub, .xlf, .xliff, .po, .pot, .json, .csv, .tsv, .yml, .yaml, .toml, .xml, .strings, .stringsdict, .ftl, .properties, .ts"
+    ));
+    assert!(!output.exists());
+    assert_eq!(inference.calls.get(), 0);
+}
+
+#[test]
+fn rejects_output_in_another_format() {
+    let directory = TestDirectory::new();
+    let input = directory.path("source.md");
+    let output = directory.path("translated.txt");
+    fs::write(&input, "Hej.").expect("fixture should be written");
+    let inference = ControlledInference::unused();
+
+    let error =
+        translate(&input, &output, 10, &inference).expect_err("format conversion must fail");
+
+    assert!(error.to_string().contains("same format"));
+    assert!(!output.exists());
+    assert_eq!(inference.calls.get(), 0);
+}
+
+#[test]
+fn translates_markdown_document_structure() {
+    let directory = TestDirectory::new();
+    let input = directory.path("source.md");
+    let output = directory.path("translated.MD");
+    fs::write(&input, "# Hej\n\n```\nkod\n```\n").expect("fixture should be written");
+    let inference = ControlledInference::returning("Hello");
+
+    translate(&input, &output, 100, &inference).expect("markdown translation should succeed");
+
+    assert_eq!(
+        fs::read_to_string(output).unwrap(),
+        "# Hello\n\n```\nkod\n```\n"
+    );
+    assert_eq!(inference.calls.get(), 1);
+}
+
+#[test]
+fn leaves_no_output_when_translation_fails() {
+    let directory = TestDirectory::new();
+    let input = directory.path("source.txt");
+    let output = directory.path("translated.txt");
+    fs::write(&input, "Hej.").expect("fixture should be written");
+    let inference = ControlledInference {
+        calls: Cell::new(0),
+        response: RefCell::new(Some(Err(anyhow!("controlled failure")))),
+    };
+
+    let error = translate(&input, &output, 10, &inference)
+        .expect_err("translation failure must be returned");
+
+    assert!(error.to_string().contains("controlled failure"));
+    assert!(!output.exists());
+}
+
+#[test]
+fn writes_mobile_resources_into_target_locale_directories() {
+    let directory = TestDirectory::new();
+    let input = directory.path("strings.xml");
+    let resources = directory.path("res");
+    fs::write(
+        &input,
+        "<resources><string name=\"a\">Hej.</string></resources>",
+    )
+    .expect("fixture should be written");
+    fs::create_dir(&resources).expect("resource directory should be created");
+    let inference = ControlledInference::returning("Hello.");
+
+    translate(&input, &resources, 100, &inference).expect("android translation should succeed");
+
+    assert_eq!(
+        fs::read_to_string(resources.join("values-en/strings.xml")).unwrap(),
+        "<resources><string name=\"a\">Hello.</string></resources>"
+    );
+}
+
+#[test]
+fn translates_docx_packages_and_rejects_invalid_ones_without_output() {
+    let directory = TestDirectory::new();
+    let input = directory.path("source.docx");
+    let output = directory.path("translated.docx");
+    let document = "<w:document><w:body><w:p><w:r><w:t>Hej</w:t></w:r></w:p></w:body></w:document>";
+    fs::write(
+        &input,
+        zip_package(&[("word/document.xml", document.as_bytes())]),
+    )
+    .expect("fixture should be written");
+    let inference = ControlledInference::returning("Hello");
+
+    translate(&input, &output, 10_000, &inference).expect("docx translation should succeed");
+
+    let entries = zip_entries(&fs::read(&output).unwrap());
+    assert!(String::from_utf8_lossy(&entries[0].1).contains(">Hello</w:t>"));
+
+    let invalid = directory.path("invalid.docx");
+    let invalid_output = directory.path("invalid-translated.docx");
+    fs::write(&invalid, "not a package").expect("fixture should be written");
+    let error = translate(&invalid, &invalid_output, 10_000, &inference)
+        .expect_err("invalid package must fail");
+
+    assert!(error.to_string().contains("not a valid ZIP package"));
+    assert!(!invalid_output.exists());
+}
+
+#[test]
+fn mirrors_a_directory_tree_and_skips_existing_outputs() {
+    let directory = TestDirectory::new();
+    let input = directory.path("source");
+    let output = directory.path("translated");
+    fs::create_dir_all(input.join("guide/img")).expect("fixture should be written");
+    fs::create_dir_all(output.join("guide")).expect("fixture should be written");
+    fs::write(input.join("a.txt"), "Hej.").expect("fixture should be written");
+    fs::write(input.join("guide/b.md"), "# Hej").expect("fixture should be written");
+    fs::write(input.join("guide/c.md"), "Tack.").expect("fixture should be written");
+    fs::write(input.join("guide/img/logo.png"), "png").expect("fixture should be written");
+    fs::write(input.join("table.csv"), "namn\nHej\n").expect("fixture should be written");
+    fs::write(
+        input.join("guide/layout.xml"),
+        "<?xml version=\"1.0\"?>\n<!-- Hej -->\n<LinearLayout/>",
+    )
+    .expect("fixture should be written");
+    fs::write(input.join("main.ts"), "const hej = 1;").expect("fixture should be written");
+    fs::write(output.join("guide/c.md"), "keep me").expect("fixture should be written");
+    let inference = FakeInference::replacing(&[("Hej", "Hello")]);
+    let reports = RefCell::new(Vec::new());
+
+    let summary = translate_directory(
+        &inference,
+        &DocumentRequest {
+            columns: &["namn".to_string()],
+            ..request(&input, &output)
+        },
+        &|file| {
+            let outcome = match file.outcome {
+                BatchOutcome::Translated => "translated".to_string(),
+                BatchOutcome::Skipped => "skipped".to_string(),
+                BatchOutcome::Unsupported(reason) => reason.to_string(),
+                BatchOutcome::Failed(error) => error.to_string(),
+            };
+            reports
+                .borrow_mut()
+                .push(format!("{}: {outcome}", file.path.display()));
+        },
+    )
+    .expect("the batch should run");
+
+    assert_eq!(
+        summary,
+        BatchSummary {
+            translated: 3,
+            skipped: 3,
+            failed: 0
+        }
+    );
+    assert_eq!(
+        reports.into_inner(),
+        [
+            "a.txt: translated",
+            "guide/b.md: translated",
+            "guide/c.md: skipped",
+            "guide/layout.xml: not Android string resources (no <resources> root element)",
+            "main.ts: not a Qt Linguist file (no <TS> root element)",
+            "table.csv: translated"
+        ]
+    );
+    assert_eq!(fs::read_to_string(output.join("a.txt")).unwrap(), "Hello.");
+    assert_eq!(
+        fs::read_to_string(output.join("guide/b.md")).unwrap(),
+        "# Hello"
+    );
+    assert_eq!(
+        fs::read_to_string(output.join("guide/c.md")).unwrap(),
+        "keep me"
+    );
+    assert_eq!(
+        fs::read_to_string(output.join("table.csv")).unwrap(),
+        "namn,namn (en)\nHej,Hello\n"
+    );
+    assert!(!output.join("guide/img").exists());
+}
+
+#[test]
+fn resumes_an_interrupted_document_with_identical_output() {
+    let directory = TestDirectory::new();
+    let input = directory.path("source.txt");
+    let complete = directory.path("complete.txt");
+    let output = directory.path("translated.txt");
+    let journal = directory.path("translated.txt.ltengine-journal");
+    fs::write(&input, "Ett.\n\nTvå.\n\nTre.\n").expect("fixture should be written");
+    let phrases = &[("Ett", "One"), ("Två", "Two"), ("Tre", "Three")];
+    let small_slices = |output| DocumentRequest {
+        max_slice_tokens: 2,
+        ..request(&input, output)
+    };
+    translate_document(&FakeInference::replacing(phrases), &small_slices(&complete))
+        .expect("uninterrupted translation should succeed");
+
+    let calls = Cell::new(0);
+    let interrupted = FakeInference::new(move |text| {
+        calls.set(calls.get() + 1);
+        match calls.get() {
+            3 => Err(anyhow!("power lost")),
+            _ => Ok(text.replace("Ett", "One").replace("Två", "Two")),
+        }
+    });
+    translate_document(&interrupted, &small_slices(&output)).expect_err("the run is cut short");
+    assert!(!output.exists());
+    assert!(journal.exists());
+
+    let error = translate_document(&FakeInference::replacing(phrases), &small_slices(&output))
+        .expect_err("an existing journal needs --resume");
+    assert!(error.to_string().contains("pass --resume"));
+
+    let resumed = FakeInference::replacing(phrases);
+    translate_document(
+        &resumed,
+        &DocumentRequest {
+            resume: true,
+            ..small_slices(&output)
+        },
+    )
+    .expect("the resumed run should finish");
+
+    assert_eq!(*resumed.texts.borrow(), ["Tre."]);
+    assert_eq!(fs::read(&output).unwrap(), fs::read(&complete).unwrap());
+    assert!(!journal.exists());
+}
+
+#[test]
+fn names_outputs_from_templates_writes_stdout_and_replaces_only_with_force() {
+    let directory = TestDirectory::new();
+    let input = directory.path("source.txt");
+    let output_directory = directory.path("out");
+    let output = output_directory.join("source.en.txt");
+    fs::write(&input, "Hej.").expect("fixture should be written");
+    let inference = FakeInference::replacing(&[("Hej", "Hello")]);
+    let named = DocumentRequest {
+        output_template: Some(DEFAULT_NAME_TEMPLATE),
+        ..request(&input, &output_directory)
+    };
+
+    translate_document(&inference, &named).expect("the named output should be written");
+    assert_eq!(fs::read_to_string(&output).unwrap(), "Hello.");
+    let error = translate_document(&inference, &named).expect_err("the output exists");
+    assert!(error.to_string().contains("pass --force"));
+
+    fs::write(&input, "Hej då.").expect("fixture should be changed");
+    let forced = DocumentRequest {
+        force: true,
+        ..named
+    };
+    translate_document(&inference, &forced).expect("--force should replace the output");
+    assert_eq!(fs::read_to_string(&output).unwrap(), "Hello då.");
+
+    let error = translate_document(
+        &inference,
+        &DocumentRequest {
+            force: true,
+            ..request(&input, &input)
+        },
+    )
+    .expect_err("--force must not overwrite the input");
+    assert!(error.to_string().contains("same file"));
+    fs::create_dir(directory.path("folder.txt")).expect("fixture should be written");
+    let error = translate_document(
+        &inference,
+        &DocumentRequest {
+            force: true,
+            ..request(&input, &directory.path("folder.txt"))
+        },
+    )
+    .expect_err("--force must not replace a directory");
+    assert!(error.to_string().contains("not a regular file"));
+
+    let mut stdout = Vec::new();
+    translate_document_to_stdout(&inference, &request(&input, Path::new("-")), &mut stdout)
+        .expect("the translation should go to stdout");
+    assert_eq!(stdout, "Hello då.".as_bytes());
+    assert_eq!(fs::read_to_string(&input).unwrap(), "Hej då.");
+}
@@ -0,0 +1,325 @@
+use std::borrow::Cow;
+use std::ops::Range;
+
+use anyhow::Result;
+
+use super::bilingual::Bilingual;
+use super::segments::{SegmentTranslator, estimate_tokens};
+use super::wrap::{Wrapped, unwrap};
+use crate::translation::Inference;
+
+/// A paragraph's content and the line breaks and blank lines that follow it.
+#[derive(Debug, PartialEq)]
+struct Paragraph {
+    content: Range<usize>,
+    separator: Range<usize>,
+}
+
+/// A paragraph as sent to the model, hard-wrapped ones joined into one line.
+struct Block<'a> {
+    /// The paragraph as written.
+    source: &'a str,
+    text: Cow<'a, str>,
+    wrapped: Option<Wrapped>,
+    /// The line breaks and blank lines after the paragraph.
+    separator: &'a str,
+    bilingual: Option<Bilingual>,
+}
+
+impl Block<'_> {
+    /// Appends a translation of the block, re-wrapped like the source, and its separator.
+    fn push(&self, translated: &str, output: &mut String) {
+        match (self.bilingual, &self.wrapped) {
+            (Some(bilingual), wrapped) => {
+                bilingual.push_text(self.source, translated, wrapped.as_ref(), output);
+            }
+            (None, Some(wrapped)) => output.push_str(&wrapped.rewrap(translated)),
+            (None, None) => output.push_str(translated),
+        }
+        output.push_str(self.separator);
+    }
+}
+
+/// Translates plain text paragraph by paragraph, keeping blank lines and hard wrapping.
+///
+/// With `bilingual`, each source paragraph is kept next to its translation.
+pub fn translate_text(
+    translator: &SegmentTranslator<'_, impl Inference>,
+    text: &str,
+    bilingual: Option<Bilingual>,
+) -> Result<String> {
+    let paragraphs = split_paragraphs(text);
+    let Some(first) = paragraphs.first() else {
+        return Ok(text.to_string());
+    };
+    let blocks: Vec<Block<'_>> = paragraphs
+        .iter()
+        .map(|paragraph| {
+            let content = &text[paragraph.content.clone()];
+            let wrapped = unwrap(content);
+            Block {
+                source: content,
+                text: wrapped.as_ref().map_or(Cow::Borrowed(content), |wrapped| {
+                    Cow::Owned(wrapped.text.clone())
+                }),
+                wrapped,
+                separator: &text[paragraph.separator.clone()],
+                bilingual,
+            }
+        })
+        .collect();
+
+    let mut output = text[..first.content.start].to_string();
+    for slice in slice_blocks(&blocks, translator.max_slice_tokens()) {
+        match &blocks[slice] {
+            // An oversized paragraph is split between sentences by the translator.
+            [block] => block.push(&translator.translate(&block.text, "text")?, &mut output),
+            blocks => translate_slice(translator, blocks, &mut output)?,
+        }
+    }
+    Ok(output)
+}
+
+/// Joins blocks with the separators between them.
+fn joined(blocks: &[Block<'_>]) -> String {
+    let mut text = String::new();
+    for (index, block) in blocks.iter().enumerate() {
+        if index > 0 {
+            text.push_str(blocks[index - 1].separator);
+        }
+        text.push_str(&block.text);
+    }
+    text
+}
+
+fn translate_slice(
+    translator: &SegmentTranslator<'_, impl Inference>,
+    blocks: &[Block<'_>],
+    output: &mut String,
+) -> Result<()> {
+    let translated = translator.translate(&joined(blocks), "text")?;
+
+    // The model may merge or split paragraphs; only trust its layout when the count matches.
+    let translated_paragraphs = split_paragraphs(&translated);
+    if translated_paragraphs.len() == blocks.len() {
+        for (block, translated_paragraph) in blocks.iter().zip(&translated_paragraphs) {
+            block.push(&translated[translated_paragraph.content.clone()], output);
+        }
+        return Ok(());
+    }
+
+    for block in blocks {
+        block.push(&translator.translate(&block.text, "text")?, output);
+    }
+    Ok(())
+}
+
+/// Groups consecutive blocks into slices that fit the token budget.
+///
+/// A block that exceeds the budget on its own forms a single slice.
+fn slice_blocks(blocks: &[Block<'_>], max_tokens: usize) -> Vec<Range<usize>> {
+    let mut slices = Vec::new();
+    let mut start = 0;
+    for end in 1..blocks.len() {
+        if estimate_tokens(&joined(&blocks[start..=end])) > max_tokens {
+            slices.push(start..end);
+            start = end;
+        }
+    }
+    if start < blocks.len() {
+        slices.push(start..blocks.len());
+    }
+    slices
+}
+
+/// Splits text into paragraphs separated by at least one blank line.
+fn split_paragraphs(text: &str) -> Vec<Paragraph> {
+    let mut paragraphs: Vec<Paragraph> = Vec::new();
+    let mut current: Option<Range<usize>> = None;
+    let mut offset = 0;
+
+    for line in text.split_inclusive('\n') {
+        let line_start = offset;
+        offset += line.len();
+        if line.trim().is_empty() {
+            if let Some(content) = current.take() {
+                paragraphs.push(Paragraph {
+                    separator: content.end..content.end,
+                    content,
+                });
+            }
+            continue;
+        }
+
+        let line_end = line_start + line.trim_end_matches(['\r', '\n']).len();
+        match &mut current {
+            Some(content) => content.end = line_end,
+            None => {
+                if let Some(previous) = paragraphs.last_mut() {
+                    previous.separator.end = line_start;
+                }
+                current = Some(line_start..line_end);
+            }
+        }
+    }
+
+    if let Some(content) = current {
+        paragraphs.push(Paragraph {
+            separator: content.end..content.end,
+            content,
+        });
+    }
+    if let Some(last) = paragraphs.last_mut() {
+        last.separator.end = text.len();
+    }
+    paragraphs
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::anyhow;
+
+    use super::{Bilingual, Paragraph, split_paragraphs, translate_text};
+    use crate::document::segments::SegmentTranslator;
+    use crate::document::testing::FakeInference;
+
+    #[test]
+    fn splits_paragraphs_on_blank_lines() {
+        let text = "Ett\nTvå\n\n  \nTre\r\n\r\nFyra\n";
+
+        assert_eq!(
+            split_paragraphs(text),
+            vec![
+                Paragraph {
+                    content: 0..8,
+                    separator: 8..13,
+                },
+                Paragraph {
+                    content: 13..16,
+                    separator: 16..20,
+                },
+                Paragraph {
+                    content: 20..24,
+                    separator: 24..25,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn translates_paragraphs_within_budget_in_one_slice() {
+        let inference = FakeInference::new(|text| Ok(text.replace("Stycke", "Paragraph")));
+        let translator = SegmentTranslator::new(&inference, "sv", "en", 1024);
+
+        let output = translate_text(&translator, "\nStycke ett.\n\n\nStycke två.\n", None)
+            .expect("translation should succeed");
+
+        assert_eq!(output, "\nParagraph ett.\n\n\nParagraph två.\n");
+        assert_eq!(inference.calls(), 1);
+    }
+
+    #[test]
+    fn splits_slices_at_the_token_budget_and_keeps_order() {
+        let inference = FakeInference::new(|text| Ok(text.to_uppercase()));
+        let translator = SegmentTranslator::new(&inference, "sv", "en", 4);
+
+        let output = translate_text(
+            &translator,
+            "Första stycket.\n\nAndra.\n\r\nTredje stycket.",
+            None,
+        )
+        .expect("translation should succeed");
+
+        assert_eq!(output, "FÖRSTA STYCKET.\n\nANDRA.\n\r\nTREDJE STYCKET.");
+        assert_eq!(
+            *inference.texts.borrow(),
+            ["Första stycket.", "Andra.", "Tredje stycket."]
+        );
+    }
+
+    #[test]
+    fn retranslates_paragraphs_when_the_model_merges_them() {
+        let inference = FakeInference::new(|text| Ok(text.replace("\n\n", " ")));
+        let translator = SegmentTranslator::new(&inference, "sv", "en", 1024);
+        let output =
+            translate_text(&translator, "Ett.\n\nTvå.", None).expect("translation should succeed");
+
+        assert_eq!(output, "Ett.\n\nTvå.");
+        assert_eq!(*inference.texts.borrow(), ["Ett.\n\nTvå.", "Ett.", "Två."]);
+    }
+
+    #[test]
+    fn splits_oversized_paragraphs_between_sentences() {
+        let inference = FakeInference::new(|text| Ok(text.to_uppercase()));
+        let translator = SegmentTranslator::new(&inference, "sv", "en", 12);
+        let text =
+            "Kort.\n\nDet finns t.ex. mjölk i kylen. Vi köper mer bröd imorgon.  Sedan åker vi.\n";
+
+        let output = translate_text(&translator, text, None).expect("translation should succeed");
+
+        assert_eq!(
+            output,
+            "KORT.\n\nDET FINNS T.EX. MJÖLK I KYLEN. VI KÖPER MER BRÖD IMORGON.  SEDAN ÅKER VI.\n"
+        );
+        assert_eq!(
+            *inference.texts.borrow(),
+            [
+                "Kort.",
+                "Det finns t.ex. mjölk i kylen.",
+                "Vi köper mer bröd imorgon.",
+                "Sedan åker vi."
+            ]
+        );
+    }
+
+    #[test]
+    fn joins_hard_wrapped_paragraphs_and_rewraps_the_translation() {
+        let inference = FakeInference::new(|text| {
+            Ok(text.replace("Hej!", "Hi!").replace(
+                "Vi har flyttat mötet till torsdag eftermiddag eftersom flera av oss är på resande fot i början av veckan.",
+                "We moved the meeting to Thursday afternoon because several of us travel early in the week.",
+            ))
+        });
+        let translator = SegmentTranslator::new(&inference, "sv", "en", 1024);
+        let text = "Hej!\n\n> Vi har flyttat mötet till torsdag eftermiddag eftersom\n> flera av oss är på resande fot i början av veckan.\n";
+
+        let output = translate_text(&translator, text, None).expect("translation should succeed");
+
+        assert_eq!(
+            output,
+            "Hi!\n\n> We moved the meeting to Thursday afternoon because\n> several of us travel early in the week.\n"
+        );
+        assert_eq!(
+            *inference.texts.borrow(),
+            [
+                "Hej!\n\nVi har flyttat mötet till torsdag eftermiddag eftersom flera av oss är på resande fot i början av veckan."
+            ]
+        );
+
+        let interleaved = translate_text(&translator, text, Some(Bilingual::Interleaved))
+            .expect("translation should succeed");
+
+        assert_eq!(
+            interleaved,
+            "Hej!\n\nHi!\n\n> Vi har flyttat mötet till torsdag eftermiddag eftersom\n> flera av oss är på resande fot i början av veckan.\n\n> We moved the meeting to Thursday afternoon because\n> several of us travel early in the week.\n"
+        );
+    }
+
+    #[test]
+    fn stops_at_the_first_failed_slice() {
+        let inference = FakeInference::new(|text| {
+            if text.starts_with("Andra") {
+                Err(anyhow!("controlled failure"))
+            } else {
+                Ok(text.to_string())
+            }
+        });
+        let translator = SegmentTranslator::new(&inference, "sv", "en", 2);
+
+        let error = translate_text(&translator, "Första.\n\nAndra.\n\nTredje.", None)
+            .expect_err("slice failure must be returned");
+
+        assert!(error.to_string().contains("controlled failure"));
+        assert_eq!(inference.calls(), 2);
+    }
+}
@@ -0,0 +1,534 @@
+use std::collections::HashMap;
+use std::ops::Range;
+
+use anyhow::{Result, bail};
+
+use super::placeholders::{interpolations, is_address};
+use super::segments::SegmentTranslator;
+use super::xml;
+use super::yaml::rename_language_keys;
+use crate::translation::Inference;
+
+/// Nesting limit for arrays and inline tables.
+const MAX_DEPTH: usize = 128;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Kind {
+    Basic,
+    Literal,
+    MultilineBasic,
+    MultilineLiteral,
+}
+
+struct TomlString {
+    range: Range<usize>,
+    kind: Kind,
+    /// Whether a multi-line string starts with the newline that TOML trims.
+    leading_newline: bool,
+    value: String,
+}
+
+struct Parser<'a> {
+    source: &'a str,
+    position: usize,
+    strings: Vec<TomlString>,
+    /// First segments of root keys and table headers: the key range and the unquoted key.
+    top_keys: Vec<(Range<usize>, String)>,
+}
+
+/// Translates the string values of a TOML locale file.
+///
+/// Keys, table headers, comments, numbers, dates, booleans, and key order are copied byte for
+/// byte; strings keep their quoting kind where the translation allows it and interpolations
+/// such as `{{ .Count }}` are protected. With `rename_language_key`, a root key or table named
+/// after the source language, such as `[sv]`, becomes the target tag.
+pub fn translate_toml(
+    translator: &SegmentTranslator<'_, impl Inference>,
+    source: &str,
+    rename_language_key: bool,
+) -> Result<String> {
+    let mut parser = Parser {
+        source,
+        position: 0,
+        strings: Vec::new(),
+        top_keys: Vec::new(),
+    };
+    parser.document()?;
+
+    let mut translations: HashMap<String, String> = HashMap::new();
+    let mut edits = Vec::new();
+    for string in parser.strings {
+        if !string.value.contains(char::is_alphabetic) || is_address(&string.value) {
+            continue;
+        }
+        let translated = match translations.get(&string.value) {
+            Some(translated) => translated.clone(),
+            None => {
+                let translated = translator.translate_protected(
+                    &string.value,
+                    &interpolations(&string.value),
+                    "text",
+                )?;
+                translations.insert(string.value, translated.clone());
+                translated
+            }
+        };
+        edits.push((
+            string.range,
+            encode_string(&translated, string.kind, string.leading_newline),
+        ));
+    }
+    if rename_language_key {
+        edits.extend(rename_language_keys(
+            source,
+            parser.top_keys,
+            translator.source(),
+            translator.target(),
+        ));
+    }
+    Ok(xml::apply_edits(source, edits))
+}
+
+impl Parser<'_> {
+    fn document(&mut self) -> Result<()> {
+        let mut root = true;
+        loop {
+            self.skip_trivia(true);
+            let Some(next) = self.peek() else {
+                return Ok(());
+            };
+            if next == '[' {
+                let array = self.rest().starts_with("[[");
+                self.position += if array { 2 } else { 1 };
+                let keys = self.key()?;
+                self.top_keys.push(keys[0].clone());
+                self.expect(if array { "]]" } else { "]" })?;
+                root = false;
+            } else {
+                let keys = self.key()?;
+                if root {
+                    self.top_keys.push(keys[0].clone());
+                }
+                self.expect("=")?;
+                self.value(0)?;
+            }
+            self.skip_trivia(false);
+            match self.peek() {
+                None => return Ok(()),
+                Some('\n') => self.position += 1,
+                Some('\r') if self.rest().starts_with("\r\n") => self.position += 2,
+                Some(_) => bail!("Invalid TOML: expected a new line at {}", self.location()),
+            }
+        }
+    }
+
+    /// Reads a dotted key, returning each segment's range and unquoted name.
+    fn key(&mut self) -> Result<Vec<(Range<usize>, String)>> {
+        let mut keys = Vec::new();
+        loop {
+            self.skip_spaces();
+            let start = self.position;
+            let name = match self.peek() {
+                Some('"') => self.basic_string(false)?,
+                Some('\'') => self.literal_string(false)?,
+                _ => {
+                    let length = self
+                        .rest()
+                        .find(|character: char| {
+                            !(character.is_ascii_alphanumeric() || "_-".contains(character))
+                        })
+                        .unwrap_or(self.rest().len());
+                    if length == 0 {
+                        bail!("Invalid TOML: expected a key at {}", self.location());
+                    }
+                    self.position += length;
+                    self.source[start..self.position].to_string()
+                }
+            };
+            keys.push((start..self.position, name));
+            self.skip_spaces();
+            if self.peek() != Some('.') {
+                return Ok(keys);
+            }
+            self.position += 1;
+        }
+    }
+
+    fn value(&mut self, depth: usize) -> Result<()> {
+        if depth > MAX_DEPTH {
+            bail!("Invalid TOML: nesting deeper than {MAX_DEPTH} levels");
+        }
+        self.skip_spaces();
+        let start = self.position;
+        let rest = self.rest();
+        let (kind, value) = match self.peek() {
+            Some('"') if rest.starts_with("\"\"\"") => {
+                (Kind::MultilineBasic, self.basic_string(true)?)
+            }
+            Some('"') => (Kind::Basic, self.basic_string(false)?),
+            Some('\'') if rest.starts_with("'''") => {
+                (Kind::MultilineLiteral, self.literal_string(true)?)
+            }
+            Some('\'') => (Kind::Literal, self.literal_string(false)?),
+            Some('[') => {
+                self.position += 1;
+                loop {
+                    self.skip_trivia(true);
+                    if self.peek() == Some(']') {
+                        break;
+                    }
+                    self.value(depth + 1)?;
+                    self.skip_trivia(true);
+                    if self.peek() != Some(',') {
+                        break;
+                    }
+                    self.position += 1;
+                }
+                return self.expect("]");
+            }
+            Some('{') => {
+                self.position += 1;
+                loop {
+                    self.skip_trivia(true);
+                    if self.peek() == Some('}') {
+                        break;
+                    }
+                    self.key()?;
+                    self.expect("=")?;
+                    self.value(depth + 1)?;
+                    self.skip_trivia(true);
+                    if self.peek() != Some(',') {
+                        break;
+                    }
+                    self.position += 1;
+                }
+                return self.expect("}");
+            }
+            _ => {
+                let length = rest
+                    .find([',', ']', '}', '#', '\r', '\n'])
+                    .unwrap_or(rest.len());
+                if rest[..length].trim().is_empty() {
+                    bail!("Invalid TOML: expected a value at {}", self.location());
+                }
+                self.position += rest[..length].trim_end().len();
+                return Ok(());
+            }
+        };
+        let leading_newline = matches!(kind, Kind::MultilineBasic | Kind::MultilineLiteral)
+            && ["\n", "\r\n"]
+                .iter()
+                .any(|newline| self.source[start + 3..].starts_with(newline));
+        self.strings.push(TomlString {
+            range: start..self.position,
+            kind,
+            leading_newline,
+            value,
+        });
+        Ok(())
+    }
+
+    fn basic_string(&mut self, multiline: bool) -> Result<String> {
+        let start = self.position;
+        self.position += if multiline { 3 } else { 1 };
+        if multiline {
+            self.skip_newline();
+        }
+        let mut value = String::new();
+        loop {
+            let Some(character) = self.peek() else {
+                self.position = start;
+                bail!("Invalid TOML: unterminated string at {}", self.location());
+            };
+            match character {
+                '"' if !multiline => {
+                    self.position += 1;
+                    return Ok(value);
+                }
+                '"' if self.rest().starts_with("\"\"\"") => {
+                    return Ok(value + &self.close_multiline('"'));
+                }
+                '\\' => {
+                    self.position += 1;
+                    self.escape(&mut value, multiline)?;
+                    continue;
+                }
+                '\n' if !multiline => {
+                    bail!(
+                        "Invalid TOML: line break in a string at {}",
+                        self.location()
+                    )
+                }
+                character => value.push(character),
+            }
+            self.position += character.len_utf8();
+        }
+    }
+
+    fn literal_string(&mut self, multiline: bool) -> Result<String> {
+        let start = self.position;
+        self.position += if multiline { 3 } else { 1 };
+        if multiline {
+            self.skip_newline();
+        }
+        let content = self.position;
+        loop {
+            match self.peek() {
+                None => {
+                    self.position = start;
+                    bail!("Invalid TOML: unterminated string at {}", self.location());
+                }
+                Some('\'') if !multiline => {
+                    self.position += 1;
+                    return Ok(self.source[content..self.position - 1].to_string());
+                }
+                Some('\'') if self.rest().starts_with("'''") => {
+                    let value = self.source[content..self.position].to_string();
+                    return Ok(value + &self.close_multiline('\''));
+                }
+                Some('\n') if !multiline => {
+                    bail!(
+                        "Invalid TOML: line break in a string at {}",
+                        self.location()
+                    )
+                }
+                Some(character) => self.position += character.len_utf8(),
+            }
+        }
+    }
+
+    /// Consumes the closing delimiter of a multi-line string, returning up to two quotes that
+    /// precede it and belong to the content.
+    fn close_multiline(&mut self, quote: char) -> String {
+        let run = self
+            .rest()
+            .find(|character| character != quote)
+            .unwrap_or(self.rest().len())
+            .min(5);
+        self.position += run;
+        quote.to_string().repeat(run - 3)
+    }
+
+    fn escape(&mut self, value: &mut String, multiline: bool) -> Result<()> {
+        let Some(escaped) = self.peek() else {
+            bail!("Invalid TOML: unterminated string at {}", self.location());
+        };
+        if multiline && escaped.is_whitespace() {
+            let rest = self.rest();
+            let trimmed = rest.trim_start_matches([' ', '\t']);
+            if trimmed.starts_with(['\n', '\r']) {
+                self.position += rest.len() - rest.trim_start().len();
+                return Ok(());
+            }
+        }
+        let hex_length = match escaped {
+            'x' => 2,
+            'u' => 4,
+            'U' => 8,
+            _ => 0,
+        };
+        self.position += 1;
+        if hex_length > 0 {
+            let decoded = self
+                .source
+                .get(self.position..self.position + hex_length)
+                .and_then(|digits| u32::from_str_radix(digits, 16).ok())
+                .and_then(char::from_u32);
+            let Some(decoded) = decoded else {
+                bail!(
+                    "Invalid TOML: invalid unicode escape at {}",
+                    self.location()
+                );
+            };
+            value.push(decoded);
+            self.position += hex_length;
+            return Ok(());
+        }
+        value.push(match escaped {
+            'b' => '\u{8}',
+            't' => '\t',
+            'n' => '\n',
+            'f' => '\u{c}',
+            'r' => '\r',
+            'e' => '\u{1b}',
+            '"' => '"',
+            '\\' => '\\',
+            _ => {
+                self.position -= 1;
+                bail!("Invalid TOML: invalid escape at {}", self.location())
+            }
+        });
+        Ok(())
+    }
+
+    fn rest(&self) -> &str {
+        &self.source[self.position..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn expect(&mut self, token: &str) -> Result<()> {
+        self.skip_spaces();
+        if !self.rest().starts_with(token) {
+            bail!("Invalid TOML: expected '{token}' at {}", self.location());
+        }
+        self.position += token.len();
+        Ok(())
+    }
+
+    fn skip_spaces(&mut self) {
+        let rest = self.rest();
+        self.position += rest.len() - rest.trim_start_matches([' ', '\t']).len();
+    }
+
+    fn skip_newline(&mut self) {
+        for newline in ["\n", "\r\n"] {
+            if self.rest().starts_with(newline) {
+                self.position += newline.len();
+            }
+        }
+    }
+
+    /// Skips spaces and a comment, and line breaks too when `newlines` is set.
+    fn skip_trivia(&mut self, newlines: bool) {
+        loop {
+            self.skip_spaces();
+            match self.peek() {
+                Some('#') => {
+                    self.position += self.rest().find(['\r', '\n']).unwrap_or(self.rest().len());
+                }
+                Some('\n' | '\r') if newlines => self.position += 1,
+                _ => return,
+            }
+        }
+    }
+
+    fn location(&self) -> String {
+        let before = &self.source[..self.position];
+        let line = before.matches('\n').count() + 1;
+        let column = before.len() - before.rfind('\n').map_or(0, |index| index + 1) + 1;
+        format!("line {line}, column {column}")
+    }
+}
+
+fn encode_string(text: &str, kind: Kind, leading_newline: bool) -> String {
+    let has_newline = text.contains(['\n', '\r']);
+    match kind {
+        Kind::Literal if !has_newline && !text.contains('\'') => format!("'{text}'"),
+        Kind::MultilineLiteral if !text.contains("'''") && !text.ends_with('\'') => {
+            let newline = if leading_newline { "\n" } else { "" };
+            format!("'''{newline}{text}'''")
+        }
+        Kind::MultilineBasic | Kind::MultilineLiteral => {
+            let newline = if leading_newline { "\n" } else { "" };
+            let escaped = escape_basic(text, true).replace("\"\"\"", "\"\"\\\"");
+            let escaped = match escaped.strip_suffix('"') {
+                Some(body) => format!("{body}\\\""),
+                None => escaped,
+            };
+            format!("\"\"\"{newline}{escaped}\"\"\"")
+        }
+        Kind::Basic | Kind::Literal => format!("\"{}\"", escape_basic(text, false)),
+    }
+}
+
+fn escape_basic(text: &str, multiline: bool) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for character in text.chars() {
+        match character {
+            '\\' => escaped.push_str("\\\\"),
+            '"' if !multiline => escaped.push_str("\\\""),
+            '\n' if multiline => escaped.push('\n'),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            character if character.is_control() => {
+                escaped.push_str(&format!("\\u{:04X}", character as u32));
+            }
+            character => escaped.push(character),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::translate_toml;
+    use crate::document::segments::SegmentTranslator;
+    use crate::document::testing::{FakeInference, translate_with};
+
+    const PHRASES: &[(&str, &str)] = &[
+        ("Hem", "Home"),
+        ("{{ .Count }} ord", "{{ .Count }} words"),
+        ("Läs mer", "Read more"),
+        ("Säg \"hej\"", "Say \"hello\""),
+        ("Rad ett\nrad två", "Line one\nline two"),
+        ("Det är", "It's"),
+    ];
+
+    #[test]
+    fn translates_string_values_and_keeps_structure() {
+        let source = r#"# Hugo strings
+title = "Hem" # site title
+[sv.home]
+other = 'Hem'
+[sv.wordCount]
+other = "{{ .Count }} ord"
+count = 2
+published = 1979-05-27 07:32:00Z
+links = ["Läs mer", 'Det är', { label = "Säg \"hej\"", url = "https://example.se" }]
+body = """
+Rad ett
+rad två"""
+"#;
+
+        let (output, texts) = translate_with(PHRASES, "en", |translator| {
+            translate_toml(translator, source, true)
+        });
+
+        assert_eq!(
+            output,
+            r#"# Hugo strings
+title = "Home" # site title
+[en.home]
+other = 'Home'
+[en.wordCount]
+other = "{{ .Count }} words"
+count = 2
+published = 1979-05-27 07:32:00Z
+links = ["Read more", "It's", { label = "Say \"hello\"", url = "https://example.se" }]
+body = """
+Line one
+line two"""
+"#
+        );
+        assert_eq!(
+            texts,
+            [
+                "Hem",
+                "{{ .Count }} ord",
+                "Läs mer",
+                "Det är",
+                "Säg \"hej\"",
+                "Rad ett\nrad två"
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_toml() {
+        let inference = FakeInference::replacing(PHRASES);
+        let translator = SegmentTranslator::new(&inference, "sv", "en", 1024);
+
+        let error = translate_toml(&translator, "title = \"Hem\"\nbody = \n", false)
+            .expect_err("a missing value must fail");
+
+        assert_eq!(
+            error.to_string(),
+            "Invalid TOML: expected a value at line 2, column 8"
+        );
+        assert_eq!(inference.calls(), 0);
+    }
+}
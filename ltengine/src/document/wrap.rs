@@ -0,0 +1,196 @@
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// Narrowest line width treated as hard wrapping; shorter lines are usually deliberate breaks,
+/// as in addresses or verse.
+const MIN_WRAP_WIDTH: usize = 40;
+
+/// A hard-wrapped paragraph joined into one line, with the layout to wrap a translation in.
+#[derive(Debug, PartialEq)]
+pub struct Wrapped {
+    pub text: String,
+    /// Indentation, `> ` quote markers, and list marker of the first line.
+    first_prefix: String,
+    /// Prefix of every following line, such as a list item's hanging indent.
+    prefix: String,
+    width: usize,
+}
+
+/// Joins a paragraph whose lines were broken only because the next word did not fit.
+///
+/// Returns `None` for paragraphs of one line, lines narrower than [`MIN_WRAP_WIDTH`], lines
+/// ending early on purpose, and lines that start new list items.
+pub fn unwrap(paragraph: &str) -> Option<Wrapped> {
+    let lines: Vec<&str> = paragraph.lines().collect();
+    let width = lines.iter().map(|line| display_width(line)).max()?;
+    if lines.len() < 2 || width < MIN_WRAP_WIDTH {
+        return None;
+    }
+
+    let quote = prefix_length(lines[0]);
+    let first_prefix = &lines[0][..quote + marker_length(&lines[0][quote..])];
+    let prefix = &lines[1][..prefix_length(lines[1])];
+    let mut parts = vec![&lines[0][first_prefix.len()..]];
+    for line in &lines[1..] {
+        let (line_prefix, content) = line.split_at(prefix_length(line));
+        if line_prefix != prefix || content.trim().is_empty() || marker_length(content) > 0 {
+            return None;
+        }
+        parts.push(content);
+    }
+    let same_block = if first_prefix.len() > quote {
+        prefix.trim_end() == lines[0][..quote].trim_end()
+    } else {
+        prefix.trim_end() == first_prefix.trim_end()
+    };
+    if !same_block {
+        return None;
+    }
+    for (line, next) in lines.iter().zip(&parts[1..]) {
+        let next_word = next.split_whitespace().next().unwrap_or_default();
+        if display_width(line.trim_end()) + 1 + display_width(next_word) <= width {
+            return None;
+        }
+    }
+
+    let mut text = String::new();
+    for part in parts {
+        let part = part.trim();
+        if !text.is_empty() && !text.ends_with('-') {
+            text.push(' ');
+        }
+        text.push_str(part);
+    }
+    Some(Wrapped {
+        text,
+        first_prefix: first_prefix.to_string(),
+        prefix: prefix.to_string(),
+        width,
+    })
+}
+
+impl Wrapped {
+    /// Wraps a translation of the joined text at the original width and prefixes.
+    pub fn rewrap(&self, translated: &str) -> String {
+        let mut output = self.first_prefix.clone();
+        let mut line_width = display_width(&output);
+        let mut line_has_word = false;
+        for word in translated.split_whitespace() {
+            let word_width = display_width(word);
+            if line_has_word {
+                if line_width + 1 + word_width > self.width {
+                    output.push('\n');
+                    output.push_str(&self.prefix);
+                    line_width = display_width(&self.prefix);
+                } else {
+                    output.push(' ');
+                    line_width += 1;
+                }
+            }
+            output.push_str(word);
+            line_width += word_width;
+            line_has_word = true;
+        }
+        output
+    }
+
+    /// `text` on one line behind the first line's prefix.
+    pub fn one_line(&self, text: &str) -> String {
+        format!("{}{text}", self.first_prefix)
+    }
+}
+
+/// Columns a terminal or monospaced font gives `text`, counting wide CJK characters as two.
+pub fn display_width(text: &str) -> usize {
+    text.width()
+}
+
+/// Wraps one line at `width` columns, continuing under its text after any indentation, quote
+/// markers, and list marker. Words wider than a line are broken between characters.
+pub fn wrap_line(line: &str, width: usize) -> String {
+    let quote = prefix_length(line);
+    let text_start = quote + marker_length(&line[quote..]);
+    let layout = Wrapped {
+        text: String::new(),
+        first_prefix: line[..text_start].to_string(),
+        prefix: line[..quote].to_string() + &" ".repeat(display_width(&line[quote..text_start])),
+        width,
+    };
+    let room = width.saturating_sub(display_width(&layout.prefix)).max(1);
+    let mut words = String::new();
+    for word in line[text_start..].split_whitespace() {
+        if !words.is_empty() {
+            words.push(' ');
+        }
+        let mut piece_width = 0;
+        for character in word.chars() {
+            let character_width = character.width().unwrap_or(0);
+            if piece_width > 0 && piece_width + character_width > room {
+                words.push(' ');
+                piece_width = 0;
+            }
+            words.push(character);
+            piece_width += character_width;
+        }
+    }
+    layout.rewrap(&words)
+}
+
+/// Length of a line's leading whitespace and `>` quote markers.
+fn prefix_length(line: &str) -> usize {
+    line.len() - line.trim_start_matches([' ', '\t', '>']).len()
+}
+
+/// Length of a list marker such as `- `, `* `, or `12. ` and the spaces after it.
+fn marker_length(content: &str) -> usize {
+    let digits = content.len()
+        - content
+            .trim_start_matches(|c: char| c.is_ascii_digit())
+            .len();
+    let marker = match content[digits..].chars().next() {
+        Some('.' | ')') if (1..=3).contains(&digits) => digits + 1,
+        Some('-' | '*' | '+' | '•') if digits == 0 => {
+            content.chars().next().map_or(0, char::len_utf8)
+        }
+        _ => return 0,
+    };
+    let spaces = content[marker..].len() - content[marker..].trim_start_matches(' ').len();
+    if spaces == 0 { 0 } else { marker + spaces }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::unwrap;
+
+    #[test]
+    fn unwraps_quoted_and_list_paragraphs_and_rewraps_translations() {
+        let quoted = "> Vi har flyttat mötet till torsdag eftermiddag eftersom\n> flera av oss är på resande fot i början av veckan.";
+        let wrapped = unwrap(quoted).expect("the quote is hard-wrapped");
+        assert_eq!(
+            wrapped.text,
+            "Vi har flyttat mötet till torsdag eftermiddag eftersom flera av oss är på resande fot i början av veckan."
+        );
+        assert_eq!(
+            wrapped.rewrap("We moved the meeting to Thursday afternoon because several of us travel early in the week."),
+            "> We moved the meeting to Thursday afternoon because\n> several of us travel early in the week."
+        );
+
+        let item =
+            "  - Kontrollera att alla kablar sitter ordentligt innan du\n    slår på strömmen.";
+        let wrapped = unwrap(item).expect("the item is hard-wrapped");
+        assert_eq!(
+            wrapped.rewrap(
+                "Check that all cables are firmly connected before you switch on the power."
+            ),
+            "  - Check that all cables are firmly connected before you\n    switch on the power."
+        );
+    }
+
+    #[test]
+    fn leaves_unwrapped_paragraphs_alone() {
+        // Short lines, deliberate early breaks, and lists are not hard wrapping.
+        assert!(unwrap("Anna Andersson\nStorgatan 1\n111 22 Stockholm").is_none());
+        assert!(unwrap("Det här är en mening som slutar tidigt.\nSedan kommer en helt ny rad med mer text i sig.").is_none());
+        assert!(unwrap("- Första punkten i listan som är ganska lång och\n- andra punkten i listan som också är lång").is_none());
+        assert!(unwrap("En enda lång rad som inte är radbruten på något sätt alls.").is_none());
+    }
+}
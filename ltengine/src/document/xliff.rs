@@ -0,0 +1,394 @@
+use std::ops::Range;
+
+use anyhow::{Result, bail};
+
+use super::segments::SegmentTranslator;
+use super::xml::{self, XmlKind, XmlToken};
+use crate::languages::language_tag;
+use crate::translation::Inference;
+
+/// Elements whose `translate` attribute applies to the units inside them.
+const SCOPE_ELEMENTS: &[&str] = &["file", "group", "unit", "trans-unit"];
+/// Elements holding one `source`/`target` pair: `trans-unit` in 1.2 and `segment` in 2.0.
+const PAIR_ELEMENTS: &[&str] = &["trans-unit", "segment"];
+/// XLIFF 1.2 inline elements whose content is native code rather than text.
+const CODE_ELEMENTS: &[&str] = &["bpt", "ept", "it", "ph"];
+
+#[derive(Clone, Copy, PartialEq)]
+enum Version {
+    V1,
+    V2,
+}
+
+#[derive(Default)]
+struct Pair {
+    start_tag: Range<usize>,
+    /// Elements open inside the pair; only its direct `<source>` and `<target>` count.
+    depth: usize,
+    source: Option<Source>,
+    target: Option<Range<usize>>,
+}
+
+struct Source {
+    element: Range<usize>,
+    content: Range<usize>,
+    indent: String,
+}
+
+/// Fills the `<target>` of every translatable XLIFF 1.2 or 2.0 unit with a machine translation.
+///
+/// Inline elements such as `<g>`, `<x/>`, `<ph>`, and `<pc>` are sent as numbered placeholders
+/// and copied into the target unchanged. Units with `translate="no"` and targets that already
+/// hold text are left alone. New targets are marked for review in 1.2 and their segments as
+/// `translated` in 2.0, and the target language is set from the languages table.
+pub fn translate_xliff(
+    translator: &SegmentTranslator<'_, impl Inference>,
+    source: &str,
+) -> Result<String> {
+    let tokens = xml::tokenize(source)?;
+    let version = tokens
+        .iter()
+        .find(|token| token.kind == XmlKind::Start && token.name == "xliff")
+        .and_then(|token| xml::attribute(&source[token.range.clone()], "version"));
+    let version = match version.as_deref() {
+        Some(version) if version.starts_with("1.") => Version::V1,
+        Some(version) if version.starts_with("2.") => Version::V2,
+        Some(version) => bail!("Unsupported XLIFF version {version}"),
+        None => bail!("XLIFF document has no xliff element with a version"),
+    };
+    let language = language_tag(translator.target());
+
+    let mut edits = Vec::new();
+    let mut translatable = Vec::new();
+    let mut pair: Option<Pair> = None;
+    let mut index = 0;
+    while index < tokens.len() {
+        let token = &tokens[index];
+        index += 1;
+        let tag = &source[token.range.clone()];
+        match (token.kind, token.name) {
+            (XmlKind::Start, "xliff") if version == Version::V2 => {
+                edits.push((
+                    token.range.clone(),
+                    xml::set_attribute(tag, "trgLang", language),
+                ));
+            }
+            (XmlKind::Start, "file") if version == Version::V1 => {
+                edits.push((
+                    token.range.clone(),
+                    xml::set_attribute(tag, "target-language", language),
+                ));
+            }
+            _ => {}
+        }
+
+        let scope = SCOPE_ELEMENTS.contains(&token.name);
+        if scope && token.kind == XmlKind::Start {
+            let inherited = *translatable.last().unwrap_or(&true);
+            translatable.push(match xml::attribute(tag, "translate").as_deref() {
+                Some("no") => false,
+                Some("yes") => true,
+                _ => inherited,
+            });
+        }
+        let active = *translatable.last().unwrap_or(&true);
+        if scope && token.kind == XmlKind::End {
+            translatable.pop();
+        }
+        if !active {
+            continue;
+        }
+
+        match (token.kind, token.name) {
+            (XmlKind::Start, name) if PAIR_ELEMENTS.contains(&name) => {
+                pair = Some(Pair {
+                    start_tag: token.range.clone(),
+                    ..Pair::default()
+                });
+            }
+            (XmlKind::Start, "source") => {
+                let end = closing_index(&tokens, index, "source")?;
+                let indent = tokens[..index - 1]
+                    .last()
+                    .filter(|token| token.kind == XmlKind::Text)
+                    .map(|token| {
+                        let text = &source[token.range.clone()];
+                        text.rsplit_once('\n').map_or("", |(_, indent)| indent)
+                    })
+                    .unwrap_or_default();
+                if let Some(pair) = pair.as_mut().filter(|pair| pair.depth == 0) {
+                    pair.source = Some(Source {
+                        element: token.range.start..tokens[end].range.end,
+                        content: token.range.end..tokens[end].range.start,
+                        indent: indent.to_string(),
+                    });
+                }
+                index = end + 1;
+            }
+            (XmlKind::Start, "target") => {
+                let end = closing_index(&tokens, index, "target")?;
+                if let Some(pair) = pair.as_mut().filter(|pair| pair.depth == 0) {
+                    pair.target = Some(token.range.start..tokens[end].range.end);
+                }
+                index = end + 1;
+            }
+            (XmlKind::Empty, "target") => {
+                if let Some(pair) = pair.as_mut().filter(|pair| pair.depth == 0) {
+                    pair.target = Some(token.range.clone());
+                }
+            }
+            // Translation proposals and segmented copies of the source hold their own
+            // `<source>` and `<target>` elements.
+            (XmlKind::Start, name @ ("alt-trans" | "seg-source")) => {
+                index = closing_index(&tokens, index, name)? + 1;
+            }
+            (XmlKind::End, name) if PAIR_ELEMENTS.contains(&name) => {
+                if let Some(pair) = pair.take() {
+                    edits.extend(translate_pair(translator, source, &tokens, pair, version)?);
+                }
+            }
+            (XmlKind::Start, _) => {
+                if let Some(pair) = &mut pair {
+                    pair.depth += 1;
+                }
+            }
+            (XmlKind::End, _) => {
+                if let Some(pair) = &mut pair {
+                    pair.depth = pair.depth.saturating_sub(1);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(xml::apply_edits(source, edits))
+}
+
+fn closing_index(tokens: &[XmlToken<'_>], start: usize, name: &str) -> Result<usize> {
+    match tokens[start..]
+        .iter()
+        .position(|token| token.kind == XmlKind::End && token.name == name)
+    {
+        Some(offset) => Ok(start + offset),
+        None => bail!("Malformed XLIFF: unclosed {name} element"),
+    }
+}
+
+fn translate_pair(
+    translator: &SegmentTranslator<'_, impl Inference>,
+    source: &str,
+    tokens: &[XmlToken<'_>],
+    pair: Pair,
+    version: Version,
+) -> Result<Vec<(Range<usize>, String)>> {
+    let Some(source_element) = pair.source else {
+        return Ok(Vec::new());
+    };
+    if let Some(target) = &pair.target {
+        let target_text = xml::tokenize(&source[target.clone()])?.iter().any(|token| {
+            token.kind == XmlKind::Text
+                && !source[target.clone()][token.range.clone()]
+                    .trim()
+                    .is_empty()
+        });
+        if target_text {
+            return Ok(Vec::new());
+        }
+    }
+
+    let (runs, markups) = split_inline(source, tokens, source_element.content.clone());
+    if runs.iter().all(|run| run.trim().is_empty()) {
+        return Ok(Vec::new());
+    }
+    let translated = translator.translate_runs(&runs, "text")?;
+    let mut content = String::new();
+    for (index, run) in translated.iter().enumerate() {
+        if index > 0 {
+            content.push_str(&markups[index - 1]);
+        }
+        content.push_str(&xml::escape_text(run));
+    }
+
+    let mut edits = Vec::new();
+    let target_tag = match &pair.target {
+        Some(target) => {
+            let tag = &source[target.start..];
+            let tag = &tag[..tag.find('>').expect("target tag is closed") + 1];
+            tag.strip_suffix("/>")
+                .map_or(tag.to_string(), |open| format!("{}>", open.trim_end()))
+        }
+        None => "<target>".to_string(),
+    };
+    let target_tag = match version {
+        Version::V1 => xml::set_attribute(&target_tag, "state", "needs-review-translation"),
+        Version::V2 => target_tag,
+    };
+    let target = format!("{target_tag}{content}</target>");
+    match pair.target {
+        Some(range) => edits.push((range, target)),
+        None => {
+            let end = source_element.element.end;
+            edits.push((end..end, format!("\n{}{target}", source_element.indent)));
+        }
+    }
+    if version == Version::V2 {
+        let start_tag = &source[pair.start_tag.clone()];
+        edits.push((
+            pair.start_tag,
+            xml::set_attribute(start_tag, "state", "translated"),
+        ));
+    }
+    Ok(edits)
+}
+
+/// Splits `<source>` content into text runs and the inline markup between them.
+fn split_inline(
+    source: &str,
+    tokens: &[XmlToken<'_>],
+    content: Range<usize>,
+) -> (Vec<String>, Vec<String>) {
+    let mut runs = vec![String::new()];
+    let mut markups: Vec<String> = Vec::new();
+    let mut code_depth = 0;
+    for token in tokens
+        .iter()
+        .filter(|token| content.start <= token.range.start && token.range.end <= content.end)
+    {
+        let text = &source[token.range.clone()];
+        if token.kind == XmlKind::Text && code_depth == 0 {
+            runs.last_mut()
+                .expect("runs are not empty")
+                .push_str(&xml::unescape(text));
+            continue;
+        }
+        if CODE_ELEMENTS.contains(&token.name) {
+            match token.kind {
+                XmlKind::Start => code_depth += 1,
+                XmlKind::End => code_depth -= 1,
+                _ => {}
+            }
+        }
+        match markups.last_mut() {
+            Some(markup) if runs.last().is_some_and(String::is_empty) => markup.push_str(text),
+            _ => {
+                markups.push(text.to_string());
+                runs.push(String::new());
+            }
+        }
+    }
+    (runs, markups)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::translate_xliff;
+    use crate::document::segments::SegmentTranslator;
+    use crate::document::testing::{FakeInference, translate_with};
+
+    const PHRASES: &[(&str, &str)] = &[
+        ("Hej", "Hello"),
+        ("världen", "world"),
+        ("Spara", "Save"),
+        ("Avbryt", "Cancel"),
+        ("Öppna", "Open"),
+    ];
+
+    fn translate(source: &str) -> (String, Vec<String>) {
+        translate_with(PHRASES, "en", |translator| {
+            translate_xliff(translator, source)
+        })
+    }
+
+    #[test]
+    fn fills_xliff_1_2_targets_with_protected_inline_elements() {
+        let source = r#"<?xml version="1.0" encoding="UTF-8"?>
+<xliff version="1.2" xmlns="urn:oasis:names:tc:xliff:document:1.2">
+  <file source-language="sv" datatype="plaintext" original="app">
+    <body>
+      <trans-unit id="1">
+        <source>Hej <g id="1">världen</g><x id="2"/> <ph id="3">{0}</ph></source>
+      </trans-unit>
+      <trans-unit id="2" translate="no">
+        <source>Spara</source>
+      </trans-unit>
+      <group translate="no"><trans-unit id="3"><source>Avbryt</source></trans-unit></group>
+      <trans-unit id="4">
+        <source>Öppna</source>
+        <target state="final">Open it</target>
+      </trans-unit>
+    </body>
+  </file>
+</xliff>
+"#;
+
+        let (output, texts) = translate(source);
+
+        assert_eq!(
+            output,
+            source
+                .replace(
+                    "datatype=\"plaintext\" original=\"app\">",
+                    "datatype=\"plaintext\" original=\"app\" target-language=\"en\">"
+                )
+                .replace(
+                    "<ph id=\"3\">{0}</ph></source>\n",
+                    "<ph id=\"3\">{0}</ph></source>\n        <target state=\"needs-review-translation\">Hello <g id=\"1\">world</g><x id=\"2\"/> <ph id=\"3\">{0}</ph></target>\n"
+                )
+        );
+        assert_eq!(texts, ["Hej ⟦1⟧världen⟦2⟧ ⟦3⟧"]);
+    }
+
+    #[test]
+    fn fills_xliff_2_0_targets_and_marks_segments() {
+        let source = r#"<xliff xmlns="urn:oasis:names:tc:xliff:document:2.0" version="2.0" srcLang="sv" trgLang="de">
+<file id="f1"><unit id="u1"><segment><source>Hej <pc id="1">världen</pc><ph id="2"/></source><target/></segment></unit>
+<unit id="u2" translate="no"><segment><source>Spara</source></segment></unit></file>
+</xliff>"#;
+
+        let (output, texts) = translate(source);
+
+        assert_eq!(
+            output,
+            r#"<xliff xmlns="urn:oasis:names:tc:xliff:document:2.0" version="2.0" srcLang="sv" trgLang="en">
+<file id="f1"><unit id="u1"><segment state="translated"><source>Hej <pc id="1">världen</pc><ph id="2"/></source><target>Hello <pc id="1">world</pc><ph id="2"/></target></segment></unit>
+<unit id="u2" translate="no"><segment><source>Spara</source></segment></unit></file>
+</xliff>"#
+        );
+        assert_eq!(texts, ["Hej ⟦1⟧världen⟦2⟧"]);
+    }
+
+    #[test]
+    fn keeps_alt_trans_proposals_out_of_the_unit() {
+        let source = r#"<xliff version="1.2"><file source-language="sv" original="app"><body>
+<trans-unit id="1">
+  <source>Hej</source>
+  <alt-trans match-quality="80"><source>Hej du</source><target>Hi you</target></alt-trans>
+  <alt-trans><target/></alt-trans>
+</trans-unit>
+</body></file></xliff>"#;
+
+        let (output, texts) = translate(source);
+
+        assert_eq!(
+            output,
+            source
+                .replace("original=\"app\">", "original=\"app\" target-language=\"en\">")
+                .replace(
+                    "<source>Hej</source>\n",
+                    "<source>Hej</source>\n  <target state=\"needs-review-translation\">Hello</target>\n"
+                )
+        );
+        assert_eq!(texts, ["Hej"]);
+    }
+
+    #[test]
+    fn rejects_documents_without_a_version() {
+        let inference = FakeInference::replacing(PHRASES);
+        let translator = SegmentTranslator::new(&inference, "sv", "en", 1024);
+
+        let error = translate_xliff(&translator, "<xliff><file/></xliff>")
+            .expect_err("missing version must fail");
+
+        assert!(error.to_string().contains("version"));
+    }
+}
@@ -0,0 +1,269 @@
+use std::ops::Range;
+
+use anyhow::{Result, bail};
+
+use super::html::decode_entities;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum XmlKind {
+    Start,
+    End,
+    Empty,
+    Text,
+    /// Comments, processing instructions, CDATA sections, and doctypes.
+    Other,
+}
+
+#[derive(Debug)]
+pub struct XmlToken<'a> {
+    pub kind: XmlKind,
+    pub range: Range<usize>,
+    /// Qualified element name, such as `w:t`; empty for text and other tokens.
+    pub name: &'a str,
+}
+
+/// Splits well-formed XML into tokens whose ranges cover the source without gaps.
+///
+/// Callers rewrite a document by replacing token ranges, so every byte they leave alone,
+/// including namespace declarations and whitespace, is copied unchanged.
+pub fn tokenize(source: &str) -> Result<Vec<XmlToken<'_>>> {
+    let mut tokens = Vec::new();
+    let mut position = 0;
+    while position < source.len() {
+        let rest = &source[position..];
+        if !rest.starts_with('<') {
+            let end = rest
+                .find('<')
+                .map_or(source.len(), |found| position + found);
+            tokens.push(XmlToken {
+                kind: XmlKind::Text,
+                range: position..end,
+                name: "",
+            });
+            position = end;
+            continue;
+        }
+
+        let delimited = [
+            ("<!--", "-->"),
+            ("<![CDATA[", "]]>"),
+            ("<?", "?>"),
+            ("<!", ">"),
+        ]
+        .into_iter()
+        .find(|(open, _)| rest.starts_with(open));
+        let token = match delimited {
+            Some((open, close)) => rest[open.len()..].find(close).map(|found| XmlToken {
+                kind: XmlKind::Other,
+                range: position..position + open.len() + found + close.len(),
+                name: "",
+            }),
+            None => tag_end(rest).map(|end| {
+                let tag = &rest[..end];
+                let kind = if tag.starts_with("</") {
+                    XmlKind::End
+                } else if tag.ends_with("/>") {
+                    XmlKind::Empty
+                } else {
+                    XmlKind::Start
+                };
+                XmlToken {
+                    kind,
+                    range: position..position + end,
+                    name: tag_name(tag),
+                }
+            }),
+        };
+        let Some(token) = token else {
+            bail!("Malformed XML: unterminated markup at byte {position}");
+        };
+        position = token.range.end;
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+/// Returns the name of the root element, skipping a byte order mark, the XML declaration,
+/// comments, processing instructions, and the doctype. Text that does not start like an XML
+/// document has none.
+pub fn root_element(text: &str) -> Option<&str> {
+    let mut rest = text.trim_start_matches('\u{feff}');
+    loop {
+        rest = rest.trim_start();
+        let skipped = if rest.starts_with("<!--") {
+            rest.find("-->").map(|end| end + 3)
+        } else if rest.starts_with("<?") {
+            rest.find("?>").map(|end| end + 2)
+        } else if rest.starts_with("<!") {
+            // A doctype may hold an internal subset in brackets, with `>` inside it.
+            match (rest.find('['), rest.find('>')) {
+                (Some(open), Some(close)) if open < close => {
+                    rest[open..].find(']').and_then(|end| {
+                        rest[open + end..]
+                            .find('>')
+                            .map(|close| open + end + close + 1)
+                    })
+                }
+                (_, close) => close.map(|close| close + 1),
+            }
+        } else {
+            break;
+        };
+        rest = &rest[skipped?..];
+    }
+    Some(tag_name(rest.strip_prefix('<')?)).filter(|name| !name.is_empty())
+}
+
+/// Returns the length of the tag at the start of `text`, skipping `>` inside quoted values.
+fn tag_end(text: &str) -> Option<usize> {
+    let mut quote = None;
+    for (index, character) in text.char_indices() {
+        match (quote, character) {
+            (None, '"' | '\'') => quote = Some(character),
+            (Some(open), _) if open == character => quote = None,
+            (None, '>') => return Some(index + 1),
+            _ => {}
+        }
+    }
+    None
+}
+
+fn tag_name(tag: &str) -> &str {
+    let name = tag.trim_start_matches('<').trim_start_matches('/');
+    let end = name
+        .find(|c: char| c.is_whitespace() || c == '/' || c == '>')
+        .unwrap_or(name.len());
+    &name[..end]
+}
+
+/// Returns the range of an attribute's raw value within `tag`.
+pub fn attribute_range(tag: &str, name: &str) -> Option<Range<usize>> {
+    let mut position = tag.find(|c: char| c.is_whitespace())?;
+    loop {
+        let rest = &tag[position..];
+        let start = position + rest.find(|c: char| !c.is_whitespace())?;
+        let equals = start + tag[start..].find('=')?;
+        let quote_start = equals + tag[equals..].find(['"', '\''])?;
+        let quote = &tag[quote_start..quote_start + 1];
+        let value_end = quote_start + 1 + tag[quote_start + 1..].find(quote)?;
+        if tag[start..equals].trim_end() == name {
+            return Some(quote_start + 1..value_end);
+        }
+        position = value_end + 1;
+    }
+}
+
+/// Returns an attribute's unescaped value.
+pub fn attribute(tag: &str, name: &str) -> Option<String> {
+    attribute_range(tag, name).map(|range| unescape(&tag[range]))
+}
+
+/// Returns `tag` with the attribute set to `value`, appending the attribute when it is missing.
+pub fn set_attribute(tag: &str, name: &str, value: &str) -> String {
+    let value = escape_attribute(value);
+    if let Some(range) = attribute_range(tag, name) {
+        return format!("{}{value}{}", &tag[..range.start], &tag[range.end..]);
+    }
+    let end = if tag.ends_with("/>") {
+        tag.len() - 2
+    } else {
+        tag.len() - 1
+    };
+    let insert_at = tag[..end].trim_end().len();
+    format!(
+        "{} {name}=\"{value}\"{}",
+        &tag[..insert_at],
+        &tag[insert_at..]
+    )
+}
+
+/// Replaces non-overlapping source ranges and copies everything else unchanged.
+pub fn apply_edits(source: &str, mut edits: Vec<(Range<usize>, String)>) -> String {
+    edits.sort_by_key(|(range, _)| range.start);
+    let mut output = String::with_capacity(source.len());
+    let mut position = 0;
+    for (range, replacement) in edits {
+        output.push_str(&source[position..range.start]);
+        output.push_str(&replacement);
+        position = range.end;
+    }
+    output.push_str(&source[position..]);
+    output
+}
+
+pub fn unescape(text: &str) -> String {
+    decode_entities(text)
+}
+
+pub fn escape_text(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+pub fn escape_attribute(text: &str) -> String {
+    escape_text(text).replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{XmlKind, attribute_range, root_element, set_attribute, tokenize};
+
+    #[test]
+    fn tokenizes_without_gaps() {
+        let source = "<?xml version=\"1.0\"?><a x=\"1 > 0\"><!-- c --><b/>t &amp; u</a>";
+
+        let tokens = tokenize(source).expect("XML should tokenize");
+
+        let kinds: Vec<_> = tokens
+            .iter()
+            .map(|token| (token.kind, token.name))
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                (XmlKind::Other, ""),
+                (XmlKind::Start, "a"),
+                (XmlKind::Other, ""),
+                (XmlKind::Empty, "b"),
+                (XmlKind::Text, ""),
+                (XmlKind::End, "a"),
+            ]
+        );
+        let rebuilt: String = tokens
+            .iter()
+            .map(|token| &source[token.range.clone()])
+            .collect();
+        assert_eq!(rebuilt, source);
+        assert!(tokenize("<a><b").is_err());
+    }
+
+    #[test]
+    fn finds_the_root_element_after_the_prolog() {
+        let source = "\u{feff}<?xml version=\"1.0\"?>\n<!-- <x> -->\n<!DOCTYPE TS [<!ENTITY a \"b>\">]>\n<TS version=\"2.1\">";
+
+        assert_eq!(root_element(source), Some("TS"));
+        assert_eq!(root_element("<resources/>"), Some("resources"));
+        assert_eq!(root_element("export const a = 1;"), None);
+        assert_eq!(root_element("<!-- unterminated"), None);
+    }
+
+    #[test]
+    fn reads_and_sets_attributes() {
+        let tag = "<w:t id='a&amp;b' xml:space=\"default\">";
+
+        assert_eq!(
+            attribute_range(tag, "id").map(|range| &tag[range]),
+            Some("a&amp;b")
+        );
+        assert_eq!(attribute_range(tag, "space"), None);
+        assert_eq!(
+            set_attribute(tag, "xml:space", "preserve"),
+            "<w:t id='a&amp;b' xml:space=\"preserve\">"
+        );
+        assert_eq!(
+            set_attribute("<w:t/>", "xml:lang", "en"),
+            "<w:t xml:lang=\"en\"/>"
+        );
+    }
+}
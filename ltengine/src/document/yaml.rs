@@ -0,0 +1,699 @@
+use std::collections::HashMap;
+use std::ops::Range;
+
+use anyhow::{Result, bail};
+
+use super::placeholders::{interpolations, is_address};
+use super::segments::SegmentTranslator;
+use super::xml;
+use crate::languages::{is_language_key, language_tag};
+use crate::translation::Inference;
+
+/// Plain scalars that YAML 1.1 and 1.2 loaders read as nulls or booleans rather than strings.
+const NON_STRING_PLAIN: &[&str] = &[
+    "~", "null", "Null", "NULL", "true", "True", "TRUE", "false", "False", "FALSE", "yes", "Yes",
+    "YES", "no", "No", "NO", "on", "On", "ON", "off", "Off", "OFF",
+];
+
+enum Style {
+    Plain,
+    Single,
+    Double,
+    Literal(String),
+    Folded(String),
+}
+
+struct Scalar {
+    range: Range<usize>,
+    value: String,
+    style: Style,
+}
+
+struct Scanner<'a> {
+    source: &'a str,
+    /// Line contents without their line endings.
+    lines: Vec<Range<usize>>,
+    scalars: Vec<Scalar>,
+    /// Keys of the root mapping: the key range and the unquoted key.
+    top_keys: Vec<(Range<usize>, String)>,
+}
+
+/// Translates the string scalars of a YAML locale file.
+///
+/// Only scalar text changes: keys, comments, anchors, aliases, tags, key order, and
+/// indentation are copied, and nulls, booleans, numbers, and flow collections are left alone.
+/// Scalars keep their quoting style where the translation allows it. Interpolations such as
+/// `%{count}` and `{{ .Name }}` are protected. With `rename_language_key`, a root key naming the
+/// source language, such as Rails' `sv:`, becomes the target tag.
+pub fn translate_yaml(
+    translator: &SegmentTranslator<'_, impl Inference>,
+    source: &str,
+    rename_language_key: bool,
+) -> Result<String> {
+    let mut lines = Vec::new();
+    let mut start = 0;
+    for line in source.split_inclusive('\n') {
+        let content = line.trim_end_matches(['\r', '\n']);
+        lines.push(start..start + content.len());
+        start += line.len();
+    }
+    let mut scanner = Scanner {
+        source,
+        lines,
+        scalars: Vec::new(),
+        top_keys: Vec::new(),
+    };
+    scanner.scan()?;
+
+    let newline = if source.contains("\r\n") {
+        "\r\n"
+    } else {
+        "\n"
+    };
+    let mut translations: HashMap<String, String> = HashMap::new();
+    let mut edits = Vec::new();
+    for scalar in scanner.scalars {
+        if !scalar.value.contains(char::is_alphabetic) || is_address(&scalar.value) {
+            continue;
+        }
+        let translated = match translations.get(&scalar.value) {
+            Some(translated) => translated.clone(),
+            None => {
+                let translated = translator.translate_protected(
+                    &scalar.value,
+                    &interpolations(&scalar.value),
+                    "text",
+                )?;
+                translations.insert(scalar.value, translated.clone());
+                translated
+            }
+        };
+        edits.push((
+            scalar.range,
+            encode_scalar(&translated, &scalar.style, newline),
+        ));
+    }
+    if rename_language_key {
+        edits.extend(rename_language_keys(
+            source,
+            scanner.top_keys,
+            translator.source(),
+            translator.target(),
+        ));
+    }
+    Ok(xml::apply_edits(source, edits))
+}
+
+/// Replaces the root keys naming the source language with the target tag, keeping their quotes.
+///
+/// With an `auto` source the keys are renamed only when they all name the same language.
+pub fn rename_language_keys(
+    source: &str,
+    keys: Vec<(Range<usize>, String)>,
+    source_language: &str,
+    target: &str,
+) -> Vec<(Range<usize>, String)> {
+    let keys: Vec<_> = keys
+        .into_iter()
+        .filter(|(_, key)| is_language_key(key, source_language))
+        .collect();
+    if source_language == "auto" && keys.iter().any(|(_, key)| *key != keys[0].1) {
+        return Vec::new();
+    }
+    keys.into_iter()
+        .map(|(range, _)| {
+            let quote = source[range.clone()]
+                .chars()
+                .next()
+                .filter(|quote| ['"', '\''].contains(quote));
+            let tag = language_tag(target);
+            let key = match quote {
+                Some(quote) => format!("{quote}{tag}{quote}"),
+                None => tag.to_string(),
+            };
+            (range, key)
+        })
+        .collect()
+}
+
+impl<'a> Scanner<'a> {
+    fn scan(&mut self) -> Result<()> {
+        let mut index = 0;
+        while index < self.lines.len() {
+            let line = self.line(index);
+            let content = line.trim_start_matches(' ');
+            let indent = line.len() - content.len();
+            let marker = indent == 0
+                && (content.starts_with('%')
+                    || ["---", "..."].iter().any(|marker| {
+                        content
+                            .strip_prefix(marker)
+                            .is_some_and(|rest| rest.is_empty() || rest.starts_with([' ', '\t']))
+                    }));
+            if content.is_empty() || content.starts_with('#') || marker {
+                index += 1;
+                continue;
+            }
+
+            let mut column = indent;
+            let mut parent = indent as isize - 1;
+            let mut in_sequence = false;
+            while let Some(rest) = line[column..].strip_prefix('-') {
+                if !(rest.is_empty() || rest.starts_with([' ', '\t'])) {
+                    break;
+                }
+                parent = column as isize;
+                in_sequence = true;
+                column = line.len() - rest.trim_start().len();
+            }
+            if column >= line.len() {
+                index += 1;
+                continue;
+            }
+
+            index = match self.mapping_key(index, column) {
+                Some((key, value_column)) => {
+                    if indent == 0 && !in_sequence {
+                        let name = self.source[key.clone()].trim_matches(['"', '\'']);
+                        self.top_keys.push((key, name.to_string()));
+                    }
+                    self.value(index, value_column, column as isize)?
+                }
+                None => self.value(index, column, parent)?,
+            };
+        }
+        Ok(())
+    }
+
+    fn line(&self, index: usize) -> &'a str {
+        &self.source[self.lines[index].clone()]
+    }
+
+    fn line_of(&self, position: usize) -> usize {
+        self.lines.partition_point(|line| line.start <= position) - 1
+    }
+
+    /// Finds a `key:` at `column`, returning the key range and the column of its value.
+    fn mapping_key(&self, index: usize, column: usize) -> Option<(Range<usize>, usize)> {
+        let line = self.line(index);
+        let rest = &line[column..];
+        let key_end = match rest.chars().next()? {
+            quote @ ('"' | '\'') => {
+                let close = quoted_end(rest, quote)?;
+                let after = rest[close..].trim_start_matches([' ', '\t']);
+                let colon = rest.len() - after.len();
+                let value = after.strip_prefix(':')?;
+                (value.is_empty() || value.starts_with([' ', '\t'])).then_some(())?;
+                return Some((
+                    self.lines[index].start + column..self.lines[index].start + column + close,
+                    column + colon + 1,
+                ));
+            }
+            '[' | '{' | '|' | '>' | '&' | '*' | '!' | '?' | '#' => return None,
+            _ => {
+                let mut found = None;
+                let mut previous = ' ';
+                for (offset, character) in rest.char_indices() {
+                    if character == '#' && previous.is_whitespace() {
+                        return None;
+                    }
+                    if character == ':'
+                        && rest[offset + 1..]
+                            .chars()
+                            .next()
+                            .is_none_or(|next| next == ' ' || next == '\t')
+                    {
+                        found = Some(offset);
+                        break;
+                    }
+                    previous = character;
+                }
+                found?
+            }
+        };
+        let key = rest[..key_end].trim_end();
+        Some((
+            self.lines[index].start + column..self.lines[index].start + column + key.len(),
+            column + key_end + 1,
+        ))
+    }
+
+    /// Records the scalar starting at `column`, returning the index of the next line to scan.
+    fn value(&mut self, index: usize, column: usize, parent: isize) -> Result<usize> {
+        let line = self.line(index);
+        let mut column = column + (line[column..].len() - line[column..].trim_start().len());
+        while line[column..].starts_with(['&', '!']) {
+            let rest = &line[column..];
+            let property = rest.find([' ', '\t']).unwrap_or(rest.len());
+            column += property + (rest[property..].len() - rest[property..].trim_start().len());
+        }
+        let rest = &line[column..];
+        let start = self.lines[index].start + column;
+        match rest.chars().next() {
+            None | Some('#' | '*') => Ok(index + 1),
+            Some('[' | '{') => Ok(self.line_of(self.flow_end(start)?) + 1),
+            Some('|' | '>') => self.block_scalar(index, rest, parent),
+            Some(quote @ ('"' | '\'')) => {
+                let Some(length) = quoted_end(&self.source[start..], quote) else {
+                    bail!(
+                        "Malformed YAML: unterminated quoted scalar on line {}",
+                        index + 1
+                    );
+                };
+                let raw = &self.source[start + 1..start + length - 1];
+                let (value, style) = if quote == '"' {
+                    (unescape_double(&fold_lines(raw)), Style::Double)
+                } else {
+                    (fold_lines(raw).replace("''", "'"), Style::Single)
+                };
+                self.scalars.push(Scalar {
+                    range: start..start + length,
+                    value,
+                    style,
+                });
+                Ok(self.line_of(start + length - 1) + 1)
+            }
+            Some(_) => Ok(self.plain(index, column, parent)),
+        }
+    }
+
+    fn plain(&mut self, index: usize, column: usize, parent: isize) -> usize {
+        let first = strip_comment(&self.line(index)[column..]);
+        let start = self.lines[index].start + column;
+        let mut end = start + first.len();
+        let mut text = first.to_string();
+        let mut next = index + 1;
+        let mut blank_lines = 0;
+        while next < self.lines.len() {
+            let line = self.line(next);
+            let content = line.trim_start_matches(' ');
+            let indent = line.len() - content.len();
+            if content.trim().is_empty() {
+                blank_lines += 1;
+                next += 1;
+                continue;
+            }
+            if indent as isize <= parent
+                || content.starts_with('#')
+                || self.mapping_key(next, indent).is_some()
+            {
+                break;
+            }
+            let content = strip_comment(content);
+            text.push_str(&if blank_lines == 0 {
+                " ".to_string()
+            } else {
+                "\n".repeat(blank_lines)
+            });
+            text.push_str(content);
+            blank_lines = 0;
+            end = self.lines[next].start + indent + content.len();
+            next += 1;
+        }
+
+        if !is_non_string(&text) {
+            self.scalars.push(Scalar {
+                range: start..end,
+                value: text,
+                style: Style::Plain,
+            });
+        }
+        self.line_of(end) + 1
+    }
+
+    fn block_scalar(&mut self, index: usize, header: &str, parent: isize) -> Result<usize> {
+        let folded = header.starts_with('>');
+        let indicators = &header[1..];
+        let explicit = indicators
+            .chars()
+            .take_while(|character| !character.is_whitespace())
+            .find_map(|character| character.to_digit(10))
+            .map(|digit| parent.max(0) as usize + digit as usize);
+
+        let mut content_indent = explicit;
+        let mut content = None;
+        let mut next = index + 1;
+        while next < self.lines.len() {
+            let line = self.line(next);
+            let indent = line.len() - line.trim_start_matches(' ').len();
+            if line.trim().is_empty() {
+                next += 1;
+                continue;
+            }
+            if indent as isize <= parent || content_indent.is_some_and(|expected| indent < expected)
+            {
+                break;
+            }
+            content_indent.get_or_insert(indent);
+            content.get_or_insert(next..next).end = next + 1;
+            next += 1;
+        }
+        let (Some(lines), Some(content_indent)) = (content, content_indent) else {
+            return Ok(index + 1);
+        };
+
+        let first_line = index + 1;
+        let text: Vec<&str> = (first_line..lines.end)
+            .map(|line| self.line(line).get(content_indent..).unwrap_or_default())
+            .collect();
+        let indent = " ".repeat(content_indent);
+        let (value, style) = if folded {
+            (fold_lines(&text.join("\n")), Style::Folded(indent))
+        } else {
+            (text.join("\n"), Style::Literal(indent))
+        };
+        self.scalars.push(Scalar {
+            range: self.lines[first_line].start..self.lines[lines.end - 1].end,
+            value,
+            style,
+        });
+        Ok(lines.end)
+    }
+
+    /// Returns the position after the bracket closing the flow collection at `start`.
+    fn flow_end(&self, start: usize) -> Result<usize> {
+        let mut depth = 0;
+        let mut position = start;
+        while let Some(character) = self.source[position..].chars().next() {
+            match character {
+                '[' | '{' => depth += 1,
+                ']' | '}' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Ok(position + 1);
+                    }
+                }
+                '"' | '\'' => {
+                    if let Some(length) = quoted_end(&self.source[position..], character) {
+                        position += length;
+                        continue;
+                    }
+                }
+                _ => {}
+            }
+            position += character.len_utf8();
+        }
+        bail!(
+            "Malformed YAML: unclosed flow collection on line {}",
+            self.line_of(start) + 1
+        )
+    }
+}
+
+/// Returns the length of the quoted scalar at the start of `text`, closing quote included.
+fn quoted_end(text: &str, quote: char) -> Option<usize> {
+    let mut characters = text.char_indices().skip(1);
+    while let Some((offset, character)) = characters.next() {
+        match character {
+            '\\' if quote == '"' => {
+                characters.next();
+            }
+            '\'' if quote == '\'' && text[offset + 1..].starts_with('\'') => {
+                characters.next();
+            }
+            character if character == quote => return Some(offset + 1),
+            _ => {}
+        }
+    }
+    None
+}
+
+fn strip_comment(text: &str) -> &str {
+    let comment = text
+        .match_indices(" #")
+        .chain(text.match_indices("\t#"))
+        .map(|(index, _)| index)
+        .min()
+        .unwrap_or(text.len());
+    text[..comment].trim_end()
+}
+
+/// Folds line breaks like YAML flow and folded scalars: a single break becomes a space and
+/// each further empty line a newline.
+fn fold_lines(text: &str) -> String {
+    let mut folded = String::with_capacity(text.len());
+    let mut breaks = 0;
+    for (index, line) in text.split('\n').enumerate() {
+        let line = line.trim_end_matches('\r');
+        let line = if index == 0 { line } else { line.trim_start() };
+        if index > 0 && line.is_empty() {
+            breaks += 1;
+            continue;
+        }
+        if index > 0 {
+            if folded.ends_with('\\') && !folded.ends_with("\\\\") {
+                folded.pop();
+            } else if breaks == 0 {
+                folded.push(' ');
+            }
+            folded.push_str(&"\n".repeat(breaks));
+        }
+        folded.push_str(line.trim_end_matches([' ', '\t']));
+        breaks = 0;
+    }
+    folded.push_str(&"\n".repeat(breaks));
+    folded
+}
+
+fn unescape_double(text: &str) -> String {
+    let mut value = String::with_capacity(text.len());
+    let mut characters = text.chars();
+    while let Some(character) = characters.next() {
+        if character != '\\' {
+            value.push(character);
+            continue;
+        }
+        let Some(escaped) = characters.next() else {
+            break;
+        };
+        let hex_length = match escaped {
+            'x' => 2,
+            'u' => 4,
+            'U' => 8,
+            _ => 0,
+        };
+        if hex_length > 0 {
+            let digits: String = characters.by_ref().take(hex_length).collect();
+            let decoded = u32::from_str_radix(&digits, 16)
+                .ok()
+                .and_then(char::from_u32)
+                .unwrap_or(char::REPLACEMENT_CHARACTER);
+            value.push(decoded);
+            continue;
+        }
+        value.push(match escaped {
+            '0' => '\0',
+            'a' => '\u{7}',
+            'b' => '\u{8}',
+            't' => '\t',
+            'n' => '\n',
+            'v' => '\u{b}',
+            'f' => '\u{c}',
+            'r' => '\r',
+            'e' => '\u{1b}',
+            'N' => '\u{85}',
+            '_' => '\u{a0}',
+            other => other,
+        });
+    }
+    value
+}
+
+fn is_non_string(text: &str) -> bool {
+    NON_STRING_PLAIN.contains(&text)
+        || text.parse::<f64>().is_ok()
+        || [".inf", "-.inf", ".nan", ".Inf", ".NaN"].contains(&text)
+        || ["0x", "0o"].iter().any(|prefix| {
+            text.strip_prefix(prefix)
+                .is_some_and(|digits| u64::from_str_radix(digits, 16).is_ok())
+        })
+        || is_timestamp(text)
+}
+
+/// Whether a plain scalar resolves to a YAML timestamp, such as `2024-05-01`,
+/// `2024-05-01T10:00:00Z`, or `2024-05-01 10:00:00.5 +02:00`.
+fn is_timestamp(text: &str) -> bool {
+    let digits = |part: &str, max: usize| {
+        (1..=max).contains(&part.len()) && part.bytes().all(|byte| byte.is_ascii_digit())
+    };
+    let (date, time) = text.split_once(['T', 't', ' ', '\t']).unwrap_or((text, ""));
+    let time = time.trim_matches([' ', '\t']);
+    let (time, zone) = match time.strip_suffix('Z') {
+        Some(time) => (time, "0"),
+        None => time.split_once(['+', '-']).unwrap_or((time, "0")),
+    };
+    let (time, fraction) = time
+        .trim_end()
+        .split_once('.')
+        .unwrap_or((time.trim_end(), "0"));
+    let time: Vec<&str> = time.split(':').collect();
+    matches!(date.split('-').collect::<Vec<_>>()[..], [year, month, day]
+        if year.len() == 4 && digits(year, 4) && digits(month, 2) && digits(day, 2))
+        && (date.len() == text.len()
+            || matches!(time[..], [hour, minute, second]
+                if digits(hour, 2) && digits(minute, 2) && digits(second, 2))
+                && digits(fraction, 9)
+                && zone.split(':').all(|part| digits(part, 2)))
+}
+
+fn encode_scalar(text: &str, style: &Style, newline: &str) -> String {
+    match style {
+        Style::Plain if is_plain_safe(text) => text.to_string(),
+        Style::Single if !text.contains(['\n', '\r']) => format!("'{}'", text.replace('\'', "''")),
+        Style::Literal(indent) => text
+            .split('\n')
+            .map(|line| match line {
+                "" => String::new(),
+                line => format!("{indent}{line}"),
+            })
+            .collect::<Vec<_>>()
+            .join(newline),
+        Style::Folded(indent) => text
+            .split('\n')
+            .map(|line| format!("{indent}{line}"))
+            .collect::<Vec<_>>()
+            .join(&format!("{newline}{newline}")),
+        _ => {
+            let mut escaped = String::with_capacity(text.len() + 2);
+            escaped.push('"');
+            for character in text.chars() {
+                match character {
+                    '"' => escaped.push_str("\\\""),
+                    '\\' => escaped.push_str("\\\\"),
+                    '\n' => escaped.push_str("\\n"),
+                    '\r' => escaped.push_str("\\r"),
+                    '\t' => escaped.push_str("\\t"),
+                    character if character.is_control() => {
+                        escaped.push_str(&format!("\\u{:04x}", character as u32));
+                    }
+                    character => escaped.push(character),
+                }
+            }
+            escaped.push('"');
+            escaped
+        }
+    }
+}
+
+fn is_plain_safe(text: &str) -> bool {
+    !text.is_empty()
+        && text.trim() == text
+        && !text.starts_with([
+            '-', '?', ':', ',', '[', ']', '{', '}', '#', '&', '*', '!', '|', '>', '\'', '"', '%',
+            '@', '`',
+        ])
+        && !text.contains([':', '#', '\n', '\r', '\t'])
+        && !is_non_string(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::translate_yaml;
+    use crate::document::testing::translate_with;
+
+    const PHRASES: &[(&str, &str)] = &[
+        ("Hej %{name}", "Hello %{name}"),
+        ("Spara", "Save"),
+        ("Avbryt", "Cancel"),
+        ("Välkommen: logga in", "Welcome: sign in"),
+        ("Det är 'bra'", "It's 'good'"),
+        ("Första raden.\nAndra raden.", "First line.\nSecond line."),
+        (
+            "En lång mening som fortsätter.",
+            "A long sentence that goes on.",
+        ),
+        ("Ny rad", "New line"),
+        ("Logga in", "Log in: now"),
+    ];
+
+    fn translate(source: &str, rename: bool) -> (String, Vec<String>) {
+        translate_with(PHRASES, "en", |translator| {
+            translate_yaml(translator, source, rename)
+        })
+    }
+
+    #[test]
+    fn translates_string_scalars_and_keeps_structure() {
+        let source = r#"# Swedish strings
+sv:
+  defaults: &defaults
+    save: Spara # button
+    cancel: "Avbryt"
+  form:
+    <<: *defaults
+    greeting: 'Hej %{name}'
+    welcome: "Välkommen: logga in"
+    quote: "Det är 'bra'"
+    count: 3
+    updated: 2024-05-01T10:00:00Z
+    published: 2024-05-01 10:00:00.5 +02:00
+    enabled: true
+    empty: ~
+    tags: [Spara, Avbryt]
+  help: |
+    Första raden.
+    Andra raden.
+  long: >-
+    En lång mening
+    som fortsätter.
+  items:
+    - Spara
+    - label: Ny rad
+      url: https://example.se/spara
+      contact: "info@example.se"
+"#;
+
+        let (output, texts) = translate(source, true);
+
+        assert_eq!(
+            output,
+            r#"# Swedish strings
+en:
+  defaults: &defaults
+    save: Save # button
+    cancel: "Cancel"
+  form:
+    <<: *defaults
+    greeting: 'Hello %{name}'
+    welcome: "Welcome: sign in"
+    quote: "It's 'good'"
+    count: 3
+    updated: 2024-05-01T10:00:00Z
+    published: 2024-05-01 10:00:00.5 +02:00
+    enabled: true
+    empty: ~
+    tags: [Spara, Avbryt]
+  help: |
+    First line.
+    Second line.
+  long: >-
+    A long sentence that goes on.
+  items:
+    - Save
+    - label: New line
+      url: https://example.se/spara
+      contact: "info@example.se"
+"#
+        );
+        assert_eq!(
+            texts,
+            [
+                "Spara",
+                "Avbryt",
+                "Hej %{name}",
+                "Välkommen: logga in",
+                "Det är 'bra'",
+                "Första raden.\nAndra raden.",
+                "En lång mening som fortsätter.",
+                "Ny rad"
+            ]
+        );
+    }
+
+    #[test]
+    fn quotes_plain_scalars_only_when_needed_and_keeps_keys_by_default() {
+        let (output, _) = translate("sv:\n  title: Logga in\n  ok: Spara\n", false);
+
+        assert_eq!(output, "sv:\n  title: \"Log in: now\"\n  ok: Save\n");
+    }
+}
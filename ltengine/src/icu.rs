@@ -0,0 +1,633 @@
+use crate::languages::{ordinal_categories, plural_categories};
+
+/// Deepest nesting of arguments accepted in a message.
+const MAX_DEPTH: usize = 32;
+
+/// A piece of an ICU MessageFormat message.
+#[derive(Clone, Debug, PartialEq)]
+enum Part {
+    /// Literal text with apostrophe quoting removed.
+    Text(String),
+    /// `#`, the number of the enclosing plural argument.
+    Pound,
+    /// A simple argument such as `{name}` or `{count, number}`, kept as written.
+    Argument(String),
+    Choice(Choice),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Kind {
+    Plural,
+    SelectOrdinal,
+    Select,
+}
+
+/// A `plural`, `selectordinal`, or `select` argument.
+#[derive(Clone, Debug, PartialEq)]
+struct Choice {
+    /// Source from `{` up to the first variant key, such as `{count, plural, offset:1 `.
+    head: String,
+    name: String,
+    kind: Kind,
+    variants: Vec<Variant>,
+    /// Whitespace between the last variant and the closing `}`.
+    tail: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Variant {
+    /// Whitespace before the key; the first variant's is part of the choice head.
+    before: String,
+    key: String,
+    /// Whitespace between the key and the message's `{`.
+    gap: String,
+    message: Vec<Part>,
+}
+
+/// An ICU MessageFormat message with at least one plural, selectordinal, or select argument.
+#[derive(Debug)]
+pub struct Message(Vec<Part>);
+
+/// Parses `text` as an ICU message, or returns `None` when it is not valid MessageFormat or has
+/// no plural, selectordinal, or select argument to handle specially.
+pub fn parse(text: &str) -> Option<Message> {
+    let parts = parse_parts(text)?;
+    parts
+        .iter()
+        .any(|part| matches!(part, Part::Choice(_)))
+        .then_some(Message(parts))
+}
+
+/// Whether `text` is valid ICU MessageFormat.
+pub fn is_valid(text: &str) -> bool {
+    parse_parts(text).is_some()
+}
+
+fn parse_parts(text: &str) -> Option<Vec<Part>> {
+    let mut parser = Parser {
+        source: text,
+        position: 0,
+        depth: 0,
+    };
+    let parts = parser.message(false)?;
+    (parser.position == text.len()).then_some(parts)
+}
+
+impl Message {
+    /// Translates the literal text of the message with `translate`, keeping its syntax.
+    ///
+    /// Each text leaf is translated as one sentence with its arguments shown as written and
+    /// `#` shown as a sample number. Plural and selectordinal variants are regenerated for the
+    /// categories `target` needs: a missing category is translated from the source's `other`
+    /// variant, categories the target lacks are dropped, and explicit `=N` variants are kept.
+    pub fn translate<E>(
+        &self,
+        target: &str,
+        mut translate: impl FnMut(&str) -> Result<String, E>,
+    ) -> Result<String, E> {
+        let parts = translate_parts(&self.0, target, None, &mut translate)?;
+        let mut output = String::new();
+        write_parts(&parts, false, false, &mut output);
+        Ok(output)
+    }
+}
+
+struct Parser<'a> {
+    source: &'a str,
+    position: usize,
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.source[self.position..]
+    }
+
+    fn whitespace(&mut self) -> &'a str {
+        let rest = self.rest();
+        let length = rest.len() - rest.trim_start().len();
+        self.position += length;
+        &rest[..length]
+    }
+
+    fn word(&mut self, allowed: impl Fn(char) -> bool) -> &'a str {
+        let rest = self.rest();
+        let length = rest.find(|c: char| !allowed(c)).unwrap_or(rest.len());
+        self.position += length;
+        &rest[..length]
+    }
+
+    fn expect(&mut self, character: char) -> Option<()> {
+        self.rest()
+            .starts_with(character)
+            .then(|| self.position += 1)
+    }
+
+    /// Parses message text up to an unmatched `}` or the end of the source.
+    fn message(&mut self, in_plural: bool) -> Option<Vec<Part>> {
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        while let Some(character) = self.rest().chars().next() {
+            match character {
+                '}' => break,
+                '{' => {
+                    flush(&mut text, &mut parts);
+                    parts.push(self.argument(in_plural)?);
+                }
+                '#' if in_plural => {
+                    flush(&mut text, &mut parts);
+                    parts.push(Part::Pound);
+                    self.position += 1;
+                }
+                '\'' => {
+                    let next = &self.rest()[1..];
+                    self.position += 1;
+                    if next.starts_with('\'') {
+                        text.push('\'');
+                        self.position += 1;
+                    } else if next.starts_with(['{', '}', '|'])
+                        || in_plural && next.starts_with('#')
+                    {
+                        // A quoted literal runs to the next lone apostrophe or the end.
+                        loop {
+                            let rest = self.rest();
+                            let Some(end) = rest.find('\'') else {
+                                text.push_str(rest);
+                                self.position = self.source.len();
+                                break;
+                            };
+                            text.push_str(&rest[..end]);
+                            self.position += end + 1;
+                            if !self.rest().starts_with('\'') {
+                                break;
+                            }
+                            text.push('\'');
+                            self.position += 1;
+                        }
+                    } else {
+                        text.push('\'');
+                    }
+                }
+                _ => {
+                    text.push(character);
+                    self.position += character.len_utf8();
+                }
+            }
+        }
+        flush(&mut text, &mut parts);
+        Some(parts)
+    }
+
+    fn argument(&mut self, in_plural: bool) -> Option<Part> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return None;
+        }
+        let start = self.position;
+        self.position += 1;
+        self.whitespace();
+        let name = self.word(|c| !c.is_whitespace() && !"{},'#".contains(c));
+        if name.is_empty() {
+            return None;
+        }
+        self.whitespace();
+        if self.expect('}').is_some() {
+            self.depth -= 1;
+            return Some(Part::Argument(
+                self.source[start..self.position].to_string(),
+            ));
+        }
+        self.expect(',')?;
+        self.whitespace();
+        let kind = match self.word(|c| c.is_ascii_alphabetic()) {
+            "plural" => Kind::Plural,
+            "selectordinal" => Kind::SelectOrdinal,
+            "select" => Kind::Select,
+            "" => return None,
+            _ => {
+                self.style()?;
+                self.depth -= 1;
+                return Some(Part::Argument(
+                    self.source[start..self.position].to_string(),
+                ));
+            }
+        };
+        self.whitespace();
+        self.expect(',')?;
+        self.whitespace();
+        if kind != Kind::Select && self.rest().starts_with("offset:") {
+            self.position += "offset:".len();
+            self.whitespace();
+            if self.word(|c| c.is_ascii_digit()).is_empty() {
+                return None;
+            }
+            self.whitespace();
+        }
+        let head = self.source[start..self.position].to_string();
+
+        let mut variants: Vec<Variant> = Vec::new();
+        let tail = loop {
+            let before = self.whitespace();
+            if self.expect('}').is_some() {
+                break before.to_string();
+            }
+            let key = if self.expect('=').is_some() {
+                let number = self.word(|c| c.is_ascii_digit() || c == '.');
+                if number.is_empty() {
+                    return None;
+                }
+                format!("={number}")
+            } else {
+                self.word(|c| c.is_alphanumeric() || c == '_' || c == '-')
+                    .to_string()
+            };
+            if key.is_empty()
+                || kind != Kind::Select && key.starts_with(|c: char| c.is_ascii_digit())
+            {
+                return None;
+            }
+            let gap = self.whitespace().to_string();
+            self.expect('{')?;
+            let message = self.message(kind != Kind::Select || in_plural)?;
+            self.expect('}')?;
+            variants.push(Variant {
+                before: before.to_string(),
+                key,
+                gap,
+                message,
+            });
+        };
+        if !variants.iter().any(|variant| variant.key == "other") {
+            return None;
+        }
+        self.depth -= 1;
+        Some(Part::Choice(Choice {
+            head,
+            name: name.to_string(),
+            kind,
+            variants,
+            tail,
+        }))
+    }
+
+    /// Skips the style of a simple argument, such as `, short}`, through its closing `}`.
+    fn style(&mut self) -> Option<()> {
+        let mut depth = 0;
+        let mut quoted = false;
+        while let Some(character) = self.rest().chars().next() {
+            self.position += character.len_utf8();
+            match character {
+                '\'' => quoted = !quoted,
+                _ if quoted => {}
+                '{' => depth += 1,
+                '}' if depth == 0 => return Some(()),
+                '}' => depth -= 1,
+                _ => {}
+            }
+        }
+        None
+    }
+}
+
+fn flush(text: &mut String, parts: &mut Vec<Part>) {
+    if !text.is_empty() {
+        parts.push(Part::Text(std::mem::take(text)));
+    }
+}
+
+fn translate_parts<E>(
+    parts: &[Part],
+    target: &str,
+    pound: Option<&str>,
+    translate: &mut impl FnMut(&str) -> Result<String, E>,
+) -> Result<Vec<Part>, E> {
+    let mut runs = vec![String::new()];
+    let mut markups = Vec::new();
+    for part in parts {
+        let (shown, part) = match part {
+            Part::Text(text) => {
+                runs.last_mut().expect("there is a run").push_str(text);
+                continue;
+            }
+            Part::Pound => (pound.unwrap_or("#").to_string(), Part::Pound),
+            Part::Argument(raw) => (raw.clone(), part.clone()),
+            Part::Choice(choice) => (
+                format!("{{{}}}", choice.name),
+                Part::Choice(translate_choice(choice, target, pound, translate)?),
+            ),
+        };
+        markups.push((shown, part));
+        runs.push(String::new());
+    }
+
+    let runs = if runs.iter().any(|run| run.contains(char::is_alphabetic)) {
+        let shown: Vec<&str> = markups.iter().map(|(shown, _)| shown.as_str()).collect();
+        let mut sentence = runs[0].clone();
+        for (shown, run) in shown.iter().zip(&runs[1..]) {
+            sentence.push_str(shown);
+            sentence.push_str(run);
+        }
+        match split(&translate_trimmed(&sentence, translate)?, &shown) {
+            Some(translated) => translated,
+            None => runs
+                .iter()
+                .map(|run| translate_trimmed(run, translate))
+                .collect::<Result<_, E>>()?,
+        }
+    } else {
+        runs
+    };
+
+    let mut translated = Vec::new();
+    let mut markups = markups.into_iter().map(|(_, part)| part);
+    for (index, run) in runs.into_iter().enumerate() {
+        if index > 0 {
+            translated.extend(markups.next());
+        }
+        if !run.is_empty() {
+            translated.push(Part::Text(run));
+        }
+    }
+    Ok(translated)
+}
+
+fn translate_choice<E>(
+    choice: &Choice,
+    target: &str,
+    pound: Option<&str>,
+    translate: &mut impl FnMut(&str) -> Result<String, E>,
+) -> Result<Choice, E> {
+    let categories = match choice.kind {
+        Kind::Plural => plural_categories(target),
+        Kind::SelectOrdinal => ordinal_categories(target),
+        Kind::Select => {
+            let mut variants = Vec::new();
+            for variant in &choice.variants {
+                variants.push(Variant {
+                    message: translate_parts(&variant.message, target, pound, translate)?,
+                    ..variant.clone()
+                });
+            }
+            return Ok(Choice {
+                variants,
+                ..choice.clone()
+            });
+        }
+    };
+
+    let other = choice
+        .variants
+        .iter()
+        .find(|variant| variant.key == "other")
+        .expect("parsed choices have an other variant");
+    let separator = choice
+        .variants
+        .iter()
+        .map(|variant| variant.before.as_str())
+        .find(|before| !before.is_empty())
+        .unwrap_or(" ");
+    let explicit = choice.variants.iter().filter_map(|variant| {
+        Some((
+            variant,
+            variant.key.strip_prefix('=')?,
+            variant.key.as_str(),
+        ))
+    });
+    let regenerated = categories.iter().map(|(category, sample)| {
+        let source = choice
+            .variants
+            .iter()
+            .find(|variant| variant.key == *category)
+            .unwrap_or(other);
+        (source, *sample, *category)
+    });
+
+    let mut variants = Vec::new();
+    for (source, sample, key) in explicit.collect::<Vec<_>>().into_iter().chain(regenerated) {
+        let before = match (variants.is_empty(), source.before.is_empty()) {
+            (true, _) => String::new(),
+            (false, true) => separator.to_string(),
+            (false, false) => source.before.clone(),
+        };
+        variants.push(Variant {
+            before,
+            key: key.to_string(),
+            gap: source.gap.clone(),
+            message: translate_parts(&source.message, target, Some(sample), translate)?,
+        });
+    }
+    Ok(Choice {
+        variants,
+        ..choice.clone()
+    })
+}
+
+/// Translates `text` without its surrounding whitespace, which is kept.
+fn translate_trimmed<E>(
+    text: &str,
+    translate: &mut impl FnMut(&str) -> Result<String, E>,
+) -> Result<String, E> {
+    let trimmed = text.trim();
+    if !trimmed.contains(char::is_alphabetic) {
+        return Ok(text.to_string());
+    }
+    let start = text.len() - text.trim_start().len();
+    Ok(format!(
+        "{}{}{}",
+        &text[..start],
+        translate(trimmed)?,
+        &text[start + trimmed.len()..]
+    ))
+}
+
+/// Splits a translated sentence around its markups, which must appear once each and in order.
+fn split(text: &str, markups: &[&str]) -> Option<Vec<String>> {
+    let mut runs = Vec::new();
+    let mut rest = text;
+    for markup in markups {
+        let numeric = markup.chars().all(|c| c.is_ascii_digit() || c == '.');
+        let start = rest
+            .match_indices(markup)
+            .map(|(index, _)| index)
+            .find(|&index| {
+                !numeric
+                    || !rest[..index].ends_with(|c: char| c.is_ascii_digit())
+                        && !rest[index + markup.len()..].starts_with(|c: char| c.is_ascii_digit())
+            })?;
+        runs.push(rest[..start].to_string());
+        rest = &rest[start + markup.len()..];
+    }
+    runs.push(rest.to_string());
+    let unique = markups
+        .iter()
+        .all(|markup| runs.iter().all(|run| !run.contains(markup)));
+    unique.then_some(runs)
+}
+
+/// Writes message parts; `nested` parts are followed by the `}` that closes their variant.
+fn write_parts(parts: &[Part], in_plural: bool, nested: bool, output: &mut String) {
+    for (index, part) in parts.iter().enumerate() {
+        match part {
+            Part::Text(text) => {
+                // Every other part starts with `{` or `#`.
+                let syntax_follows = nested || index + 1 < parts.len();
+                write_text(text, in_plural, syntax_follows, output);
+            }
+            Part::Pound => output.push('#'),
+            Part::Argument(raw) => output.push_str(raw),
+            Part::Choice(choice) => {
+                output.push_str(&choice.head);
+                for variant in &choice.variants {
+                    output.push_str(&variant.before);
+                    output.push_str(&variant.key);
+                    output.push_str(&variant.gap);
+                    output.push('{');
+                    let plural = choice.kind != Kind::Select || in_plural;
+                    write_parts(&variant.message, plural, true, output);
+                    output.push('}');
+                }
+                output.push_str(&choice.tail);
+                output.push('}');
+            }
+        }
+    }
+}
+
+/// Writes literal text, quoting the characters that would otherwise be read as syntax.
+///
+/// An apostrophe is literal on its own and only doubled where it would start a quote or be
+/// read as part of one: before another apostrophe, `{`, `}`, `|`, or a plural's `#`, and right
+/// after a quoted character.
+fn write_text(text: &str, in_plural: bool, syntax_follows: bool, output: &mut String) {
+    let mut after_quote = false;
+    let mut characters = text.chars().peekable();
+    while let Some(character) = characters.next() {
+        let quotes =
+            |next: char| matches!(next, '\'' | '{' | '}' | '|') || in_plural && next == '#';
+        after_quote = match character {
+            '\'' if after_quote
+                || characters
+                    .peek()
+                    .map_or(syntax_follows, |&next| quotes(next)) =>
+            {
+                output.push_str("''");
+                false
+            }
+            '{' | '}' => {
+                output.extend(['\'', character, '\'']);
+                true
+            }
+            '#' if in_plural => {
+                output.push_str("'#'");
+                true
+            }
+            _ => {
+                output.push(character);
+                false
+            }
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+
+    use super::{is_valid, parse};
+
+    fn translate(text: &str, target: &str, phrases: &[(&str, &str)]) -> (String, Vec<String>) {
+        let message = parse(text).expect("the message should parse");
+        let mut texts = Vec::new();
+        let output = message
+            .translate(target, |text| {
+                texts.push(text.to_string());
+                Ok::<_, Infallible>(
+                    phrases
+                        .iter()
+                        .find(|(source, _)| *source == text)
+                        .map_or(text, |(_, translated)| translated)
+                        .to_string(),
+                )
+            })
+            .unwrap();
+        assert!(is_valid(&output), "{output} should parse");
+        (output, texts)
+    }
+
+    #[test]
+    fn regenerates_plural_categories_for_the_target_language() {
+        let (output, texts) = translate(
+            "Du har {count, plural, =0 {inga filer} one {# fil} other {# filer}}.",
+            "pl",
+            &[
+                ("Du har {count}.", "Masz {count}."),
+                ("inga filer", "brak plików"),
+                ("1 fil", "1 plik"),
+                ("2 filer", "2 pliki"),
+                ("5 filer", "5 plików"),
+                ("1.5 filer", "1.5 pliku"),
+            ],
+        );
+
+        assert_eq!(
+            output,
+            "Masz {count, plural, =0 {brak plików} one {# plik} few {# pliki} many {# plików} other {# pliku}}."
+        );
+        assert_eq!(
+            texts,
+            [
+                "inga filer",
+                "1 fil",
+                "2 filer",
+                "5 filer",
+                "1.5 filer",
+                "Du har {count}."
+            ]
+        );
+    }
+
+    #[test]
+    fn translates_select_variants_and_keeps_arguments_and_quotes() {
+        let (output, _) = translate(
+            "{gender, select, female {Hon gillar '{'x'}'} other {De gillar {name}s {n, number} böcker}}",
+            "en",
+            &[
+                ("Hon gillar {x}", "She likes {x}"),
+                (
+                    "De gillar {name}s {n, number} böcker",
+                    "They like {name}'s {n, number} books",
+                ),
+            ],
+        );
+
+        assert_eq!(
+            output,
+            "{gender, select, female {She likes '{'x'}'} other {They like {name}'s {n, number} books}}"
+        );
+    }
+
+    #[test]
+    fn quotes_apostrophes_only_where_they_would_be_read_as_syntax() {
+        let translations = [("Hon", "C'est l'amie: '{x}'"), ("De", "They said 'hi'")];
+        let (output, _) = translate(
+            "{gender, select, female {Hon} other {De}}",
+            "en",
+            &translations,
+        );
+
+        assert_eq!(
+            output,
+            "{gender, select, female {C'est l'amie: '''{'x'}'''} other {They said 'hi''}}"
+        );
+        let (_, texts) = translate(&output, "en", &[]);
+        assert_eq!(texts, translations.map(|(_, translated)| translated));
+    }
+
+    #[test]
+    fn ignores_plain_and_malformed_messages() {
+        assert!(parse("Hej {name}!").is_none());
+        assert!(parse("{count, plural, one {# fil} other {# filer}").is_none());
+        assert!(parse("{count, plural, one {# fil}}").is_none());
+        assert!(parse("{count, plural, one {# fil} other {# filer}} {").is_none());
+    }
+}
@@ -56,14 +56,17 @@ const LANGS: &[(&str, &str, &str)] = &[
 pub struct Language {
     pub name: &'static str,
     pub internal_code: &'static str,
+    /// BCP 47 tag for document metadata such as `<html lang>`.
+    pub tag: &'static str,
 }
 
 pub static LANGUAGES: Lazy<Vec<Language>> = Lazy::new(|| {
     LANGS
         .iter()
-        .map(|&(code, _, name)| Language {
+        .map(|&(code, alias, name)| Language {
             name,
             internal_code: code,
+            tag: if alias.is_empty() { code } else { alias },
         })
         .collect()
 });
@@ -86,3 +89,267 @@ pub fn get_language_from_code(code: &str) -> Option<&'static Language> {
     let internal_code = CODE_TO_INTERNAL_CODE_MAP.get(code).unwrap_or(&code);
     LANGUAGES_MAP.get(internal_code).map(|v| &**v)
 }
+
+/// Returns the BCP 47 tag for a supported code, or the code itself when it is unknown.
+pub fn language_tag(code: &str) -> &str {
+    get_language_from_code(code).map_or(code, |language| language.tag)
+}
+
+/// Android resource directory for a language, such as `values-sv`, `values-pt-rBR`, or
+/// `values-b+zh+Hans`.
+pub fn android_values_directory(code: &str) -> String {
+    let tag = language_tag(code);
+    match tag.split_once('-') {
+        Some((language, region)) if region.len() == 2 => format!("values-{language}-r{region}"),
+        Some(_) => format!("values-b+{}", tag.replace('-', "+")),
+        None => format!("values-{tag}"),
+    }
+}
+
+/// Apple localization directory for a language, such as `sv.lproj` or `zh-Hans.lproj`.
+pub fn apple_lproj_directory(code: &str) -> String {
+    format!("{}.lproj", language_tag(code))
+}
+
+/// Whether a locale-file key, such as Rails' top-level `sv:`, names the source language.
+///
+/// With an `auto` source any supported language code matches.
+pub fn is_language_key(key: &str, source: &str) -> bool {
+    match (get_language_from_code(key), source) {
+        (Some(_), "auto") => true,
+        (Some(language), source) => get_language_from_code(source)
+            .is_some_and(|source| source.internal_code == language.internal_code),
+        (None, _) => false,
+    }
+}
+
+/// Gettext plural rule for a language: one sample count per plural form, in form order.
+pub struct PluralForms {
+    pub expression: &'static str,
+    pub samples: &'static [u64],
+}
+
+impl PluralForms {
+    pub fn count(&self) -> usize {
+        self.samples.len()
+    }
+
+    /// Value for a PO `Plural-Forms` header.
+    pub fn header(&self) -> String {
+        format!("nplurals={}; plural={};", self.count(), self.expression)
+    }
+}
+
+const ONE_OTHER: PluralForms = PluralForms {
+    expression: "(n != 1)",
+    samples: &[1, 2],
+};
+const ZERO_ONE_OTHER: PluralForms = PluralForms {
+    expression: "(n > 1)",
+    samples: &[1, 2],
+};
+const SINGLE: PluralForms = PluralForms {
+    expression: "0",
+    samples: &[2],
+};
+const EAST_SLAVIC: PluralForms = PluralForms {
+    expression: "(n%10==1 && n%100!=11 ? 0 : n%10>=2 && n%10<=4 && (n%100<10 || n%100>=20) ? 1 : 2)",
+    samples: &[1, 2, 5],
+};
+const WEST_SLAVIC: PluralForms = PluralForms {
+    expression: "(n==1) ? 0 : (n>=2 && n<=4) ? 1 : 2",
+    samples: &[1, 2, 5],
+};
+
+/// Gettext plural rules by internal code; other languages use `(n != 1)`.
+const PLURAL_FORMS: &[(&str, PluralForms)] = &[
+    (
+        "ar",
+        PluralForms {
+            expression: "(n==0 ? 0 : n==1 ? 1 : n==2 ? 2 : n%100>=3 && n%100<=10 ? 3 : n%100>=11 ? 4 : 5)",
+            samples: &[0, 1, 2, 3, 11, 100],
+        },
+    ),
+    ("cs", WEST_SLAVIC),
+    ("fa", ZERO_ONE_OTHER),
+    ("fr", ZERO_ONE_OTHER),
+    (
+        "ga",
+        PluralForms {
+            expression: "(n==1 ? 0 : n==2 ? 1 : n<7 ? 2 : n<11 ? 3 : 4)",
+            samples: &[1, 2, 3, 7, 11],
+        },
+    ),
+    ("id", SINGLE),
+    ("ja", SINGLE),
+    ("ko", SINGLE),
+    (
+        "lt",
+        PluralForms {
+            expression: "(n%10==1 && n%100!=11 ? 0 : n%10>=2 && (n%100<10 || n%100>=20) ? 1 : 2)",
+            samples: &[1, 2, 10],
+        },
+    ),
+    (
+        "lv",
+        PluralForms {
+            expression: "(n%10==1 && n%100!=11 ? 0 : n != 0 ? 1 : 2)",
+            samples: &[1, 2, 0],
+        },
+    ),
+    ("ms", SINGLE),
+    ("pb", ZERO_ONE_OTHER),
+    (
+        "pl",
+        PluralForms {
+            expression: "(n==1 ? 0 : n%10>=2 && n%10<=4 && (n%100<10 || n%100>=20) ? 1 : 2)",
+            samples: &[1, 2, 5],
+        },
+    ),
+    (
+        "ro",
+        PluralForms {
+            expression: "(n==1 ? 0 : (n==0 || (n%100 > 0 && n%100 < 20)) ? 1 : 2)",
+            samples: &[1, 2, 20],
+        },
+    ),
+    ("ru", EAST_SLAVIC),
+    ("sk", WEST_SLAVIC),
+    (
+        "sl",
+        PluralForms {
+            expression: "(n%100==1 ? 0 : n%100==2 ? 1 : n%100==3 || n%100==4 ? 2 : 3)",
+            samples: &[1, 2, 3, 5],
+        },
+    ),
+    ("sr", EAST_SLAVIC),
+    ("th", SINGLE),
+    ("tl", ZERO_ONE_OTHER),
+    ("uk", EAST_SLAVIC),
+    ("vi", SINGLE),
+    ("zh", SINGLE),
+    ("zt", SINGLE),
+];
+
+/// Returns the gettext plural rule for a language code or tag.
+pub fn plural_forms(code: &str) -> &'static PluralForms {
+    let internal_code =
+        get_language_from_code(code).map_or(code, |language| language.internal_code);
+    PLURAL_FORMS
+        .iter()
+        .find(|(code, _)| *code == internal_code)
+        .map_or(&ONE_OTHER, |(_, forms)| forms)
+}
+
+/// CLDR plural categories, each paired with a sample number that falls into it.
+pub type PluralCategories = &'static [(&'static str, &'static str)];
+
+const CARDINAL_ONE_OTHER: PluralCategories = &[("one", "1"), ("other", "2")];
+const CARDINAL_ONE_MANY_OTHER: PluralCategories =
+    &[("one", "1"), ("many", "1000000"), ("other", "2")];
+const CARDINAL_SLAVIC: PluralCategories =
+    &[("one", "1"), ("few", "2"), ("many", "5"), ("other", "1.5")];
+const CARDINAL_WEST_SLAVIC: PluralCategories =
+    &[("one", "1"), ("few", "2"), ("many", "1.5"), ("other", "5")];
+const ONLY_OTHER: PluralCategories = &[("other", "2")];
+
+/// CLDR cardinal categories by internal code; other languages use `one` and `other`.
+const CARDINAL_CATEGORIES: &[(&str, PluralCategories)] = &[
+    (
+        "ar",
+        &[
+            ("zero", "0"),
+            ("one", "1"),
+            ("two", "2"),
+            ("few", "3"),
+            ("many", "11"),
+            ("other", "100"),
+        ],
+    ),
+    ("ca", CARDINAL_ONE_MANY_OTHER),
+    ("cs", CARDINAL_WEST_SLAVIC),
+    ("es", CARDINAL_ONE_MANY_OTHER),
+    ("fr", CARDINAL_ONE_MANY_OTHER),
+    (
+        "ga",
+        &[
+            ("one", "1"),
+            ("two", "2"),
+            ("few", "3"),
+            ("many", "7"),
+            ("other", "11"),
+        ],
+    ),
+    ("he", &[("one", "1"), ("two", "2"), ("other", "3")]),
+    ("id", ONLY_OTHER),
+    ("it", CARDINAL_ONE_MANY_OTHER),
+    ("ja", ONLY_OTHER),
+    ("ko", ONLY_OTHER),
+    (
+        "lt",
+        &[("one", "1"), ("few", "2"), ("many", "0.5"), ("other", "10")],
+    ),
+    ("lv", &[("zero", "0"), ("one", "1"), ("other", "2")]),
+    ("ms", ONLY_OTHER),
+    ("pb", CARDINAL_ONE_MANY_OTHER),
+    ("pl", CARDINAL_SLAVIC),
+    ("pt", CARDINAL_ONE_MANY_OTHER),
+    ("ro", &[("one", "1"), ("few", "2"), ("other", "20")]),
+    ("ru", CARDINAL_SLAVIC),
+    ("sk", CARDINAL_WEST_SLAVIC),
+    (
+        "sl",
+        &[("one", "1"), ("two", "2"), ("few", "3"), ("other", "5")],
+    ),
+    ("sr", &[("one", "1"), ("few", "2"), ("other", "5")]),
+    ("th", ONLY_OTHER),
+    ("uk", CARDINAL_SLAVIC),
+    ("vi", ONLY_OTHER),
+    ("zh", ONLY_OTHER),
+    ("zt", ONLY_OTHER),
+];
+
+/// CLDR ordinal categories by internal code; other languages use only `other`.
+const ORDINAL_CATEGORIES: &[(&str, PluralCategories)] = &[
+    (
+        "ca",
+        &[("one", "1"), ("two", "2"), ("few", "4"), ("other", "5")],
+    ),
+    (
+        "en",
+        &[("one", "1"), ("two", "2"), ("few", "3"), ("other", "4")],
+    ),
+    ("fr", &[("one", "1"), ("other", "2")]),
+    ("ga", &[("one", "1"), ("other", "2")]),
+    ("hu", &[("one", "1"), ("other", "2")]),
+    ("it", &[("many", "8"), ("other", "1")]),
+    ("ms", &[("one", "1"), ("other", "2")]),
+    ("ro", &[("one", "1"), ("other", "2")]),
+    ("sq", &[("one", "1"), ("many", "4"), ("other", "2")]),
+    ("sv", &[("one", "1"), ("other", "3")]),
+    ("tl", &[("one", "1"), ("other", "2")]),
+    ("vi", &[("one", "1"), ("other", "2")]),
+];
+
+fn categories(
+    table: &[(&str, PluralCategories)],
+    code: &str,
+    default: PluralCategories,
+) -> PluralCategories {
+    let internal_code =
+        get_language_from_code(code).map_or(code, |language| language.internal_code);
+    table
+        .iter()
+        .find(|(code, _)| *code == internal_code)
+        .map_or(default, |(_, categories)| categories)
+}
+
+/// Returns the CLDR cardinal plural categories for a language code or tag.
+pub fn plural_categories(code: &str) -> PluralCategories {
+    categories(CARDINAL_CATEGORIES, code, CARDINAL_ONE_OTHER)
+}
+
+/// Returns the CLDR ordinal plural categories for a language code or tag.
+pub fn ordinal_categories(code: &str) -> PluralCategories {
+    categories(ORDINAL_CATEGORIES, code, ONLY_OTHER)
+}
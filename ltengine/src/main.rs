@@ -2,10 +2,12 @@ use clap::Parser;
 
 mod cli;
 mod document;
+mod icu;
 mod languages;
 mod llm;
 mod models;
 mod prompt;
+mod segmentation;
 mod translation;
 
 use cli::{Args, Command};
@@ -34,12 +34,21 @@ impl PromptBuilder {
     }
 
     pub fn build(&self, q: &String) -> Prompt {
-        let system = if self.format == "html" {
+        let mut system = if self.format == "html" {
             "You are an expert linguist, specializing in translation. You are able to capture the nuances of the languages you translate. You pay attention to masculine/feminine/plural and proper use of articles and grammar. You always provide natural sounding translations that fully preserve the meaning of the original text. You never provide explanations for your work. You must preserve all HTML tags and elements in the translation. You always answer with the translated text and nothing else."
+        } else if self.format == "markdown" {
+            "You are an expert linguist, specializing in translation. You are able to capture the nuances of the languages you translate. You pay attention to masculine/feminine/plural and proper use of articles and grammar. You always provide natural sounding translations that fully preserve the meaning of the original text. You never provide explanations for your work. You must preserve all Markdown syntax in the translation. You always answer with the translated text and nothing else."
         } else {
             "You are an expert linguist, specializing in translation. You are able to capture the nuances of the languages you translate. You pay attention to masculine/feminine/plural and proper use of articles and grammar. You always provide natural sounding translations that fully preserve the meaning of the original text. You never provide explanations for your work. You always answer with the translated text and nothing else."
         }.to_string();
 
+        // Document segments replace markup the model must not touch with numbered placeholders.
+        if q.contains('⟦') {
+            system.push_str(
+                " You must keep every placeholder such as ⟦1⟧ unchanged and in the same order.",
+            );
+        }
+
         let user = (if self.source_language == "auto" {
             format!(
                 "Translate the text below to {}.\n\nText: {}\n\n{}:\n",
@@ -0,0 +1,260 @@
+use std::ops::Range;
+
+const SWEDISH_ABBREVIATIONS: &[&str] = &[
+    "ang.", "avd.", "bil.", "bl.a.", "ca.", "d.v.s.", "dvs.", "e.d.", "enl.", "exkl.", "f.d.",
+    "f.n.", "f.ö.", "fig.", "fr.o.m.", "forts.", "inkl.", "jfr.", "kap.", "kl.", "m.fl.", "m.m.",
+    "mom.", "nr.", "o.d.", "o.s.v.", "obs.", "osv.", "p.g.a.", "prop.", "resp.", "s.", "s.k.",
+    "sid.", "st.", "t.ex.", "t.h.", "t.o.m.", "t.v.", "tel.", "tr.",
+];
+
+const ENGLISH_ABBREVIATIONS: &[&str] = &[
+    "a.m.", "al.", "approx.", "cf.", "co.", "dept.", "dr.", "e.g.", "etc.", "fig.", "i.e.", "inc.",
+    "jr.", "ltd.", "mr.", "mrs.", "ms.", "no.", "p.m.", "prof.", "sr.", "st.", "u.s.", "vs.",
+];
+
+const GERMAN_ABBREVIATIONS: &[&str] = &[
+    "bzw.", "ca.", "d.h.", "dr.", "evtl.", "ggf.", "inkl.", "nr.", "s.", "u.a.", "usw.", "vgl.",
+    "z.b.", "z.t.",
+];
+
+const CLOSING_MARKS: &[char] = &['"', '\'', '”', '’', '»', ')', ']', '」', '』', '）'];
+const OPENING_MARKS: &[char] = &['"', '\'', '“', '‘', '«', '»', '(', '[', '「', '『', '（'];
+
+/// Returns the byte ranges of the sentences in `text`, in order.
+///
+/// Ranges exclude the whitespace between sentences, so the text is rebuilt exactly by copying
+/// the gaps between consecutive ranges. `language` selects abbreviation rules; `auto` applies
+/// every known table.
+pub fn split_sentences(text: &str, language: &str) -> Vec<Range<usize>> {
+    let abbreviations = abbreviations(language);
+    let chars: Vec<(usize, char)> = text.char_indices().collect();
+    let offset_at = |index: usize| chars.get(index).map_or(text.len(), |&(offset, _)| offset);
+    let mut sentences = Vec::new();
+    let mut start = None;
+    let mut index = 0;
+
+    while index < chars.len() {
+        let (offset, character) = chars[index];
+        if start.is_none() {
+            if character.is_whitespace() {
+                index += 1;
+                continue;
+            }
+            start = Some(offset);
+        }
+        if !is_terminator(character) {
+            index += 1;
+            continue;
+        }
+
+        let mut end = index + 1;
+        while end < chars.len() && is_terminator(chars[end].1) {
+            end += 1;
+        }
+        while end < chars.len() && CLOSING_MARKS.contains(&chars[end].1) {
+            end += 1;
+        }
+
+        let terminators = &text[offset..offset_at(end)];
+        let sentence_start = start.expect("sentence start is set");
+        if is_boundary(
+            text,
+            sentence_start,
+            terminators,
+            offset_at(end),
+            abbreviations,
+        ) {
+            sentences.push(sentence_start..offset_at(end));
+            start = None;
+        }
+        index = end;
+    }
+
+    if let Some(start) = start {
+        sentences.push(start..text.trim_end().len());
+    }
+    sentences
+}
+
+fn is_terminator(character: char) -> bool {
+    matches!(character, '.' | '!' | '?' | '…' | '。' | '！' | '？')
+}
+
+fn is_boundary(
+    text: &str,
+    sentence_start: usize,
+    terminators: &str,
+    end: usize,
+    abbreviations: &[&[&str]],
+) -> bool {
+    // CJK full stops end a sentence without a following space.
+    if terminators.contains(['。', '！', '？']) {
+        return true;
+    }
+
+    let rest = &text[end..];
+    if rest.is_empty() {
+        return true;
+    }
+    // Decimal numbers, "t.ex" inside the abbreviation, URLs, and file names.
+    if !rest.starts_with(char::is_whitespace) {
+        return false;
+    }
+    // A following lowercase word continues the sentence after abbreviations and ellipses.
+    let next = rest
+        .trim_start()
+        .trim_start_matches(OPENING_MARKS)
+        .chars()
+        .next();
+    if next.is_some_and(char::is_lowercase) {
+        return false;
+    }
+
+    if terminators.trim_end_matches(CLOSING_MARKS) != "." {
+        return true;
+    }
+    let period_end = text[..end].trim_end_matches(CLOSING_MARKS).len();
+    let word = text[sentence_start..period_end]
+        .rsplit(char::is_whitespace)
+        .next()
+        .unwrap_or_default()
+        .trim_start_matches(OPENING_MARKS);
+    !is_initial(word) && !is_abbreviation(word, abbreviations)
+}
+
+fn is_initial(word: &str) -> bool {
+    let mut characters = word.chars();
+    matches!(
+        (characters.next(), characters.next(), characters.next()),
+        (Some(initial), Some('.'), None) if initial.is_uppercase()
+    )
+}
+
+fn is_abbreviation(word: &str, abbreviations: &[&[&str]]) -> bool {
+    let word = word.to_lowercase();
+    abbreviations
+        .iter()
+        .any(|table| table.contains(&word.as_str()))
+}
+
+fn abbreviations(language: &str) -> &'static [&'static [&'static str]] {
+    match language {
+        "sv" => &[SWEDISH_ABBREVIATIONS],
+        "en" => &[ENGLISH_ABBREVIATIONS],
+        "de" => &[GERMAN_ABBREVIATIONS],
+        "auto" => &[
+            SWEDISH_ABBREVIATIONS,
+            ENGLISH_ABBREVIATIONS,
+            GERMAN_ABBREVIATIONS,
+        ],
+        _ => &[],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::split_sentences;
+
+    fn sentences<'a>(text: &'a str, language: &str) -> Vec<&'a str> {
+        split_sentences(text, language)
+            .into_iter()
+            .map(|range| &text[range])
+            .collect()
+    }
+
+    #[test]
+    fn keeps_swedish_abbreviations_inside_sentences() {
+        let text = "Vi behöver t.ex. mjölk, bl.a. ost och s.k. knäckebröd. Sedan går vi hem.";
+
+        assert_eq!(
+            sentences(text, "sv"),
+            [
+                "Vi behöver t.ex. mjölk, bl.a. ost och s.k. knäckebröd.",
+                "Sedan går vi hem."
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_abbreviations_followed_by_capitals_for_the_selected_language() {
+        let text = "Mötet börjar kl. Tio personer kommer. Mr. Smith is late.";
+
+        assert_eq!(
+            sentences(text, "sv"),
+            [
+                "Mötet börjar kl. Tio personer kommer.",
+                "Mr.",
+                "Smith is late."
+            ]
+        );
+        assert_eq!(
+            sentences(text, "auto"),
+            [
+                "Mötet börjar kl. Tio personer kommer.",
+                "Mr. Smith is late."
+            ]
+        );
+    }
+
+    #[test]
+    fn does_not_split_decimal_numbers_or_initials() {
+        let text = "Priset steg 3.5 procent enligt J. Andersson. Det var väntat.";
+
+        assert_eq!(
+            sentences(text, "sv"),
+            [
+                "Priset steg 3.5 procent enligt J. Andersson.",
+                "Det var väntat."
+            ]
+        );
+    }
+
+    #[test]
+    fn handles_ellipses_and_question_marks() {
+        let text = "Vänta... vad sa du? Jag vet inte… Kanske imorgon!";
+
+        assert_eq!(
+            sentences(text, "sv"),
+            ["Vänta... vad sa du?", "Jag vet inte…", "Kanske imorgon!"]
+        );
+    }
+
+    #[test]
+    fn keeps_closing_quotes_with_their_sentence() {
+        let text = "Hon sa: \"Kom hit.\" Han kom. »Varför?» frågade han.";
+
+        assert_eq!(
+            sentences(text, "sv"),
+            ["Hon sa: \"Kom hit.\"", "Han kom.", "»Varför?» frågade han."]
+        );
+    }
+
+    #[test]
+    fn splits_cjk_full_stops_without_spaces() {
+        let text = "今日は晴れです。明日は雨です！「本当？」はい。";
+
+        assert_eq!(
+            sentences(text, "ja"),
+            ["今日は晴れです。", "明日は雨です！", "「本当？」", "はい。"]
+        );
+    }
+
+    #[test]
+    fn offsets_rebuild_the_original_text() {
+        let text = "  Första meningen.\n  Andra meningen!   Sista utan punkt  \n";
+        let ranges = split_sentences(text, "sv");
+
+        let mut rebuilt = String::new();
+        let mut position = 0;
+        for range in &ranges {
+            rebuilt.push_str(&text[position..range.start]);
+            rebuilt.push_str(&text[range.clone()]);
+            position = range.end;
+        }
+        rebuilt.push_str(&text[position..]);
+
+        assert_eq!(ranges.len(), 3);
+        assert_eq!(&text[ranges[2].clone()], "Sista utan punkt");
+        assert_eq!(rebuilt, text);
+    }
+}
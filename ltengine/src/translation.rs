@@ -1,5 +1,6 @@
 use anyhow::Result;
 
+use crate::icu;
 use crate::languages::get_language_from_code;
 use crate::prompt::PromptBuilder;
 
@@ -24,6 +25,7 @@ pub enum TranslationError {
     InvalidFormat(String),
     UnsupportedLanguage(String),
     Inference(anyhow::Error),
+    InvalidMessage(String),
 }
 
 impl std::fmt::Display for TranslationError {
@@ -34,6 +36,12 @@ impl std::fmt::Display for TranslationError {
                 write!(formatter, "{language} is not supported")
             }
             Self::Inference(error) => write!(formatter, "Translation failed: {error}"),
+            Self::InvalidMessage(message) => {
+                write!(
+                    formatter,
+                    "Translated ICU message does not parse: {message}"
+                )
+            }
         }
     }
 }
@@ -44,7 +52,7 @@ pub fn translate(
     inference: &impl Inference,
     request: TranslationRequest<'_>,
 ) -> Result<Translation, TranslationError> {
-    if !matches!(request.format, "text" | "html") {
+    if !matches!(request.format, "text" | "html" | "markdown") {
         return Err(TranslationError::InvalidFormat(request.format.to_string()));
     }
 
@@ -59,23 +67,34 @@ pub fn translate(
         .ok_or_else(|| TranslationError::UnsupportedLanguage(request.target.to_string()))?
         .name;
 
-    let translated_text = if request.source == request.target {
-        request.text.to_string()
-    } else {
+    if request.source == request.target {
+        return Ok(Translation {
+            text: improve_formatting(request.text, request.text),
+        });
+    }
+    let run = |text: &str| {
         let mut prompt_builder = PromptBuilder::new();
         prompt_builder
             .set_format(request.format)
             .set_source_language(source_language)
             .set_target_language(target_language);
-        let source_text = request.text.to_string();
-        let prompt = prompt_builder.build(&source_text);
-        inference
+        let prompt = prompt_builder.build(&text.to_string());
+        let translated = inference
             .run_prompt(prompt.system, prompt.user)
-            .map_err(TranslationError::Inference)?
+            .map_err(TranslationError::Inference)?;
+        Ok(improve_formatting(text, &translated))
     };
 
+    // ICU plural and select messages are translated leaf by leaf so their braces survive.
+    if let Some(message) = icu::parse(request.text) {
+        let text = message.translate(request.target, run)?;
+        if !icu::is_valid(&text) {
+            return Err(TranslationError::InvalidMessage(text));
+        }
+        return Ok(Translation { text });
+    }
     Ok(Translation {
-        text: improve_formatting(request.text, &translated_text),
+        text: run(request.text)?,
     })
 }
 
@@ -310,7 +329,7 @@ mod tests {
                 text: "Hej.",
                 source: "sv",
                 target: "en",
-                format: "markdown",
+                format: "pdf",
             },
         )
         .expect_err("unsupported format must fail");